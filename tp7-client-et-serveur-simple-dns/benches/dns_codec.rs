@@ -0,0 +1,46 @@
+use std::hint::black_box;
+use std::net::Ipv4Addr;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+// Le crate n'expose qu'un binaire: le module de format wire est inclus directement
+#[allow(dead_code)]
+#[path = "../src/message.rs"]
+mod message;
+
+use message::{DnsAnswer, DnsMessage, DnsQuestion};
+
+// Réponse typique: une question, quatre enregistrements A et un OPT
+fn sample_response() -> DnsMessage {
+    let question = DnsQuestion::new("server.local".to_string());
+    let answers = (1..=4)
+        .map(|i| DnsAnswer::new_a_record("server.local".to_string(), Ipv4Addr::new(192, 168, 1, i), 300))
+        .collect();
+    DnsMessage::new_response(0x1234, vec![question], answers)
+        .with_additionals(vec![DnsAnswer::new_opt(1232, false)])
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let response = sample_response();
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Bytes(response.encoded_len() as u64));
+
+    group.bench_function("encode_into", |b| {
+        let mut buf = [0u8; 512];
+        b.iter(|| black_box(&response).encode_into(black_box(&mut buf)).unwrap())
+    });
+    group.bench_function("to_bytes", |b| b.iter(|| black_box(&response).to_bytes().unwrap()));
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let bytes = sample_response().to_bytes().unwrap();
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+    group.bench_function("from_bytes", |b| b.iter(|| DnsMessage::from_bytes(black_box(&bytes)).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_decode);
+criterion_main!(benches);
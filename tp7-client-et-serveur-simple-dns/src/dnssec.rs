@@ -0,0 +1,639 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use byteorder::{BigEndian, WriteBytesExt};
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use signature::{Signer, Verifier};
+
+use crate::message::{encode_name, record_types, DnsAnswer};
+use crate::DEFAULT_TTL;
+
+/// Durée de validité des signatures RRSIG (7 jours)
+const SIGNATURE_VALIDITY: u32 = 7 * 24 * 3600;
+
+/// Marge appliquée au début de validité pour tolérer les décalages d'horloge
+const CLOCK_SKEW: u32 = 3600;
+
+/// Une signature pré-calculée est refaite quand il lui reste moins d'un jour de validité
+const RESIGN_MARGIN: u32 = 24 * 3600;
+
+/// Flags DNSKEY: Zone Key (bit 7) + Secure Entry Point (bit 15)
+const DNSKEY_FLAGS: u16 = 257;
+
+/// Champ protocole d'un DNSKEY, toujours 3 (RFC 4034 §2.1.2)
+const DNSKEY_PROTOCOL: u8 = 3;
+
+/// Type de digest des DS produits: SHA-256 (RFC 4509)
+const DS_DIGEST_SHA256: u8 = 2;
+
+/// Algorithmes de signature supportés (numéros IANA)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    EcdsaP256Sha256 = 13,
+    Ed25519 = 15,
+}
+
+impl Algorithm {
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_number(number: u8) -> Option<Self> {
+        match number {
+            13 => Some(Algorithm::EcdsaP256Sha256),
+            15 => Some(Algorithm::Ed25519),
+            _ => None,
+        }
+    }
+
+    /// Vérifie une signature RRSIG avec la clé publique d'un DNSKEY
+    pub fn verify(self, public_key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+        match self {
+            Algorithm::Ed25519 => {
+                let Ok(public_key) = <[u8; 32]>::try_from(public_key) else {
+                    return false;
+                };
+                let (Ok(key), Ok(signature)) = (
+                    ed25519_dalek::VerifyingKey::from_bytes(&public_key),
+                    ed25519_dalek::Signature::from_slice(signature),
+                ) else {
+                    return false;
+                };
+                key.verify(data, &signature).is_ok()
+            }
+            Algorithm::EcdsaP256Sha256 => {
+                let mut point = vec![0x04];
+                point.extend_from_slice(public_key);
+                let (Ok(key), Ok(signature)) = (
+                    p256::ecdsa::VerifyingKey::from_sec1_bytes(&point),
+                    p256::ecdsa::Signature::from_slice(signature),
+                ) else {
+                    return false;
+                };
+                key.verify(data, &signature).is_ok()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Algorithm::EcdsaP256Sha256 => write!(f, "ECDSAP256SHA256"),
+            Algorithm::Ed25519 => write!(f, "ED25519"),
+        }
+    }
+}
+
+/// Clé privée de signature de zone
+pub enum SigningKey {
+    Ed25519(ed25519_dalek::SigningKey),
+    EcdsaP256(p256::ecdsa::SigningKey),
+}
+
+impl SigningKey {
+    /// Génère une nouvelle clé aléatoire pour l'algorithme demandé
+    pub fn generate(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Ed25519 => SigningKey::Ed25519(ed25519_dalek::SigningKey::generate(&mut OsRng)),
+            Algorithm::EcdsaP256Sha256 => SigningKey::EcdsaP256(p256::ecdsa::SigningKey::random(&mut OsRng)),
+        }
+    }
+
+    /// Charge une clé existante (signature hors-ligne) depuis ses 32 octets secrets
+    pub fn from_bytes(algorithm: Algorithm, secret: &[u8]) -> Result<Self, String> {
+        match algorithm {
+            Algorithm::Ed25519 => {
+                let secret: [u8; 32] = secret
+                    .try_into()
+                    .map_err(|_| "Une clé Ed25519 fait 32 octets".to_string())?;
+                Ok(SigningKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(&secret)))
+            }
+            Algorithm::EcdsaP256Sha256 => p256::ecdsa::SigningKey::from_slice(secret)
+                .map(SigningKey::EcdsaP256)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        match self {
+            SigningKey::Ed25519(_) => Algorithm::Ed25519,
+            SigningKey::EcdsaP256(_) => Algorithm::EcdsaP256Sha256,
+        }
+    }
+
+    /// Clé publique au format DNSKEY (RFC 8080 pour Ed25519, RFC 6605 pour P-256)
+    pub fn public_key(&self) -> Vec<u8> {
+        match self {
+            SigningKey::Ed25519(key) => key.verifying_key().to_bytes().to_vec(),
+            SigningKey::EcdsaP256(key) => {
+                // Point non compressé sans le préfixe 0x04: X || Y
+                key.verifying_key().to_encoded_point(false).as_bytes()[1..].to_vec()
+            }
+        }
+    }
+
+    fn sign(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            SigningKey::Ed25519(key) => key.sign(data).to_bytes().to_vec(),
+            SigningKey::EcdsaP256(key) => {
+                let signature: p256::ecdsa::Signature = key.sign(data);
+                signature.to_bytes().to_vec()
+            }
+        }
+    }
+}
+
+/// Moment où les signatures sont calculées
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningMode {
+    /// Toute la zone est signée au chargement, les réponses réutilisent les signatures
+    Offline,
+    /// Chaque RRset est signé au moment où il est servi
+    OnTheFly,
+}
+
+/// Zone signée: DNSKEY à l'apex, chaîne NSEC et signatures RRSIG
+pub struct DnssecZone {
+    apex: String,
+    key: SigningKey,
+    dnskey_rdata: Vec<u8>,
+    key_tag: u16,
+    mode: SigningMode,
+    /// Noeuds de la zone dans l'ordre canonique, avec les types présents
+    nodes: Vec<(String, Vec<u16>)>,
+    /// Signatures pré-calculées en mode hors-ligne, indexées par (nom, type),
+    /// avec leur date d'expiration pour les refaire avant qu'elles ne périment
+    signatures: RwLock<HashMap<(String, u16), (DnsAnswer, u32)>>,
+}
+
+impl DnssecZone {
+    /// Construit la zone à partir des enregistrements A du serveur situés sous `apex`
+    pub fn new(apex: &str, key: SigningKey, mode: SigningMode, records: &HashMap<String, Ipv4Addr>) -> Self {
+        let apex = normalize(apex);
+        let dnskey_rdata = dnskey_rdata(&key);
+        let key_tag = key_tag(&dnskey_rdata);
+
+        let mut nodes: Vec<(String, Vec<u16>)> = vec![(apex.clone(), vec![record_types::DNSKEY])];
+        for name in records.keys() {
+            let name = normalize(name);
+            if !is_in_zone(&name, &apex) {
+                continue;
+            }
+            match nodes.iter_mut().find(|(owner, _)| *owner == name) {
+                Some((_, types)) => types.push(record_types::A),
+                None => nodes.push((name, vec![record_types::A])),
+            }
+        }
+        nodes.sort_by(|(a, _), (b, _)| canonical_cmp(a, b));
+
+        let mut zone = DnssecZone {
+            apex,
+            key,
+            dnskey_rdata,
+            key_tag,
+            mode,
+            nodes,
+            signatures: RwLock::new(HashMap::new()),
+        };
+
+        if mode == SigningMode::Offline {
+            zone.sign_zone(records);
+        }
+        zone
+    }
+
+    // Pré-calcule les RRSIG de tous les RRsets de la zone
+    fn sign_zone(&mut self, records: &HashMap<String, Ipv4Addr>) {
+        let (inception, expiration) = validity_period(unix_now());
+
+        let mut rrsets = vec![vec![self.dnskey_record()]];
+        for (name, ip) in records {
+            if self.contains(name) {
+                rrsets.push(vec![DnsAnswer::new_a_record(name.clone(), *ip, DEFAULT_TTL)]);
+            }
+        }
+        for index in 0..self.nodes.len() {
+            rrsets.push(vec![self.nsec_record(index)]);
+        }
+
+        for rrset in rrsets {
+            let key = (normalize(&rrset[0].name), rrset[0].atype);
+            let rrsig = self.create_rrsig(&rrset, inception, expiration);
+            self.signatures.get_mut().unwrap().insert(key, (rrsig, expiration));
+        }
+    }
+
+    pub fn apex(&self) -> &str {
+        &self.apex
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.key.algorithm()
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    /// Vrai si `name` est l'apex de la zone ou un nom situé en dessous
+    pub fn contains(&self, name: &str) -> bool {
+        is_in_zone(&normalize(name), &self.apex)
+    }
+
+    pub fn is_apex(&self, name: &str) -> bool {
+        normalize(name) == self.apex
+    }
+
+    /// Enregistrement DNSKEY publié à l'apex
+    pub fn dnskey_record(&self) -> DnsAnswer {
+        DnsAnswer::new(self.apex.clone(), record_types::DNSKEY, DEFAULT_TTL, self.dnskey_rdata.clone())
+    }
+
+    /// Enregistrement DS à publier dans la zone parente (ou à utiliser comme ancre de confiance)
+    pub fn ds_record(&self) -> DnsAnswer {
+        DnsAnswer::new(self.apex.clone(), record_types::DS, DEFAULT_TTL, ds_rdata(&self.apex, &self.dnskey_rdata))
+    }
+
+    /// Retourne le RRSIG couvrant `rrset` (tous les enregistrements ont même nom et même type)
+    pub fn sign_rrset(&self, rrset: &[DnsAnswer]) -> DnsAnswer {
+        self.sign_rrset_at(rrset, unix_now())
+    }
+
+    // En mode hors-ligne, la signature en cache est réutilisée tant qu'elle n'approche pas
+    // de son expiration; sinon elle est refaite et remplace l'ancienne
+    fn sign_rrset_at(&self, rrset: &[DnsAnswer], now: u32) -> DnsAnswer {
+        let key = (normalize(&rrset[0].name), rrset[0].atype);
+        if self.mode == SigningMode::Offline {
+            if let Some((rrsig, expiration)) = self.signatures.read().unwrap().get(&key) {
+                if now.saturating_add(RESIGN_MARGIN) < *expiration {
+                    return rrsig.clone();
+                }
+            }
+        }
+
+        let (inception, expiration) = validity_period(now);
+        let rrsig = self.create_rrsig(rrset, inception, expiration);
+        if self.mode == SigningMode::Offline {
+            if let Some(cached) = self.signatures.write().unwrap().get_mut(&key) {
+                *cached = (rrsig.clone(), expiration);
+            }
+        }
+        rrsig
+    }
+
+    /// Enregistrements NSEC (et leurs RRSIG) prouvant l'absence de réponse pour `name`:
+    /// le NSEC du nom lui-même s'il existe (NODATA), sinon les NSEC couvrant le nom
+    /// et le wildcard de son plus proche ancêtre existant (NXDOMAIN)
+    pub fn denial_of_existence(&self, name: &str) -> Vec<DnsAnswer> {
+        let name = normalize(name);
+        let mut indexes = Vec::new();
+
+        if let Some(index) = self.node_index(&name) {
+            indexes.push(index);
+        } else {
+            indexes.push(self.covering_index(&name));
+
+            let wildcard = format!("*.{}", self.closest_encloser(&name));
+            let wildcard_index = self.covering_index(&wildcard);
+            if !indexes.contains(&wildcard_index) {
+                indexes.push(wildcard_index);
+            }
+        }
+
+        indexes
+            .into_iter()
+            .flat_map(|index| {
+                let nsec = self.nsec_record(index);
+                let rrsig = self.sign_rrset(std::slice::from_ref(&nsec));
+                [nsec, rrsig]
+            })
+            .collect()
+    }
+
+    fn node_index(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|(owner, _)| owner == name)
+    }
+
+    // Dernier noeud qui précède `name` dans l'ordre canonique (l'apex précède toujours)
+    fn covering_index(&self, name: &str) -> usize {
+        self.nodes
+            .iter()
+            .rposition(|(owner, _)| canonical_cmp(owner, name) == Ordering::Less)
+            .unwrap_or(0)
+    }
+
+    fn closest_encloser(&self, name: &str) -> String {
+        let mut candidate = name;
+        while let Some((_, parent)) = candidate.split_once('.') {
+            if self.node_index(parent).is_some() {
+                return parent.to_string();
+            }
+            candidate = parent;
+        }
+        self.apex.clone()
+    }
+
+    fn nsec_record(&self, index: usize) -> DnsAnswer {
+        let (owner, types) = &self.nodes[index];
+        let (next, _) = &self.nodes[(index + 1) % self.nodes.len()];
+
+        let mut types = types.clone();
+        types.extend([record_types::RRSIG, record_types::NSEC]);
+
+        let mut rdata = Vec::new();
+        encode_name(next, &mut rdata);
+        rdata.extend_from_slice(&type_bitmap(&types));
+        DnsAnswer::new(owner.clone(), record_types::NSEC, DEFAULT_TTL, rdata)
+    }
+
+    fn create_rrsig(&self, rrset: &[DnsAnswer], inception: u32, expiration: u32) -> DnsAnswer {
+        let first = &rrset[0];
+        let owner = normalize(&first.name);
+
+        let mut rdata = Vec::new();
+        rdata.write_u16::<BigEndian>(first.atype).unwrap();
+        rdata.push(self.key.algorithm().number());
+        rdata.push(label_count(&owner));
+        rdata.write_u32::<BigEndian>(first.ttl).unwrap();
+        rdata.write_u32::<BigEndian>(expiration).unwrap();
+        rdata.write_u32::<BigEndian>(inception).unwrap();
+        rdata.write_u16::<BigEndian>(self.key_tag).unwrap();
+        encode_name(&self.apex, &mut rdata);
+
+        let signature = self.key.sign(&signed_data(&rdata, &owner, first.ttl, rrset));
+        rdata.extend_from_slice(&signature);
+
+        DnsAnswer::new(first.name.clone(), record_types::RRSIG, first.ttl, rdata)
+    }
+}
+
+/// Nom en minuscules, sans point final (forme canonique, RFC 4034 §6.2)
+pub fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+pub fn is_in_zone(name: &str, apex: &str) -> bool {
+    apex.is_empty() || name == apex || name.ends_with(&format!(".{}", apex))
+}
+
+/// Ordre canonique des noms (RFC 4034 §6.1): comparaison label par label en partant de la droite
+pub fn canonical_cmp(a: &str, b: &str) -> Ordering {
+    let labels = |name: &str| -> Vec<Vec<u8>> {
+        name.split('.')
+            .filter(|label| !label.is_empty())
+            .rev()
+            .map(|label| label.to_ascii_lowercase().into_bytes())
+            .collect()
+    };
+    labels(a).cmp(&labels(b))
+}
+
+/// Nombre de labels du propriétaire, sans la racine ni un éventuel wildcard
+pub fn label_count(owner: &str) -> u8 {
+    owner
+        .split('.')
+        .filter(|label| !label.is_empty() && *label != "*")
+        .count() as u8
+}
+
+fn dnskey_rdata(key: &SigningKey) -> Vec<u8> {
+    let mut rdata = Vec::new();
+    rdata.write_u16::<BigEndian>(DNSKEY_FLAGS).unwrap();
+    rdata.push(DNSKEY_PROTOCOL);
+    rdata.push(key.algorithm().number());
+    rdata.extend_from_slice(&key.public_key());
+    rdata
+}
+
+/// RDATA d'un DS (RFC 4034 §5.1): key tag, algorithme, type de digest et
+/// SHA-256 du nom du propriétaire suivi de la RDATA du DNSKEY
+pub fn ds_rdata(owner: &str, dnskey_rdata: &[u8]) -> Vec<u8> {
+    let mut digest_input = Vec::new();
+    encode_name(&normalize(owner), &mut digest_input);
+    digest_input.extend_from_slice(dnskey_rdata);
+
+    let mut rdata = Vec::new();
+    rdata.write_u16::<BigEndian>(key_tag(dnskey_rdata)).unwrap();
+    rdata.push(dnskey_rdata.get(3).copied().unwrap_or(0));
+    rdata.push(DS_DIGEST_SHA256);
+    rdata.extend_from_slice(&Sha256::digest(&digest_input));
+    rdata
+}
+
+/// Key tag d'un DNSKEY (RFC 4034, annexe B)
+pub fn key_tag(dnskey_rdata: &[u8]) -> u16 {
+    let mut accumulator: u32 = 0;
+    for (i, byte) in dnskey_rdata.iter().enumerate() {
+        accumulator += if i % 2 == 0 { (*byte as u32) << 8 } else { *byte as u32 };
+    }
+    accumulator += (accumulator >> 16) & 0xFFFF;
+    (accumulator & 0xFFFF) as u16
+}
+
+/// Données signées par un RRSIG (RFC 4034 §3.1.8.1): RDATA du RRSIG sans la signature,
+/// suivie des enregistrements du RRset sous forme canonique, triés par RDATA
+pub fn signed_data(rrsig_rdata: &[u8], owner: &str, original_ttl: u32, rrset: &[DnsAnswer]) -> Vec<u8> {
+    let mut rdatas: Vec<&[u8]> = rrset.iter().map(|rr| rr.rdata.as_slice()).collect();
+    rdatas.sort();
+    rdatas.dedup();
+
+    let mut data = rrsig_rdata.to_vec();
+    for rdata in rdatas {
+        encode_name(owner, &mut data);
+        data.write_u16::<BigEndian>(rrset[0].atype).unwrap();
+        data.write_u16::<BigEndian>(rrset[0].aclass).unwrap();
+        data.write_u32::<BigEndian>(original_ttl).unwrap();
+        data.write_u16::<BigEndian>(rdata.len() as u16).unwrap();
+        data.extend_from_slice(rdata);
+    }
+    data
+}
+
+/// Champ "Type Bit Maps" d'un NSEC (RFC 4034 §4.1.2)
+pub fn type_bitmap(types: &[u16]) -> Vec<u8> {
+    let mut types = types.to_vec();
+    types.sort_unstable();
+    types.dedup();
+
+    let mut bytes = Vec::new();
+    let mut i = 0;
+    while i < types.len() {
+        let window = types[i] >> 8;
+        let mut bitmap = [0u8; 32];
+        let mut length = 0;
+
+        while i < types.len() && types[i] >> 8 == window {
+            let low = (types[i] & 0xFF) as usize;
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+            length = low / 8 + 1;
+            i += 1;
+        }
+
+        bytes.push(window as u8);
+        bytes.push(length as u8);
+        bytes.extend_from_slice(&bitmap[..length]);
+    }
+    bytes
+}
+
+/// Décode un champ "Type Bit Maps" en liste de types
+pub fn parse_type_bitmap(bytes: &[u8]) -> Vec<u16> {
+    let mut types = Vec::new();
+    let mut i = 0;
+    while i + 2 <= bytes.len() {
+        let window = bytes[i] as u16;
+        let length = bytes[i + 1] as usize;
+        let bitmap = &bytes[(i + 2).min(bytes.len())..(i + 2 + length).min(bytes.len())];
+
+        for (byte_index, byte) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push((window << 8) | (byte_index * 8 + bit) as u16);
+                }
+            }
+        }
+        i += 2 + length;
+    }
+    types
+}
+
+pub fn unix_now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+fn validity_period(now: u32) -> (u32, u32) {
+    (now.saturating_sub(CLOCK_SKEW), now.saturating_add(SIGNATURE_VALIDITY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signature::Verifier;
+
+    fn test_records() -> HashMap<String, Ipv4Addr> {
+        let mut records = HashMap::new();
+        records.insert("test.local".to_string(), Ipv4Addr::new(127, 0, 0, 1));
+        records.insert("server.local".to_string(), Ipv4Addr::new(192, 168, 1, 100));
+        records.insert("dns.local".to_string(), Ipv4Addr::new(192, 168, 1, 1));
+        records.insert("example.com".to_string(), Ipv4Addr::new(93, 184, 216, 34));
+        records
+    }
+
+    fn rrsig_signature(rrsig: &DnsAnswer, signer: &str) -> (Vec<u8>, Vec<u8>) {
+        let prefix_len = 18 + signer.len() + 2;
+        (rrsig.rdata[..prefix_len].to_vec(), rrsig.rdata[prefix_len..].to_vec())
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        let mut names = vec!["z.local", "local", "*.local", "a.b.local", "B.local"];
+        names.sort_by(|a, b| canonical_cmp(a, b));
+        assert_eq!(names, vec!["local", "*.local", "B.local", "a.b.local", "z.local"]);
+    }
+
+    #[test]
+    fn test_type_bitmap() {
+        // Exemple de la RFC 4034 §4.3: A MX RRSIG NSEC TYPE1234
+        let bitmap = type_bitmap(&[1, 15, 46, 47, 1234]);
+        let mut expected = vec![0x00, 0x06, 0x40, 0x01, 0x00, 0x00, 0x00, 0x03, 0x04, 0x1b];
+        expected.extend_from_slice(&[0x00; 26]);
+        expected.push(0x20);
+        assert_eq!(bitmap, expected);
+        assert_eq!(parse_type_bitmap(&bitmap), vec![1, 15, 46, 47, 1234]);
+    }
+
+    #[test]
+    fn test_ed25519_rrsig_verifies() {
+        let key = SigningKey::generate(Algorithm::Ed25519);
+        let public_key: [u8; 32] = key.public_key().try_into().unwrap();
+        let zone = DnssecZone::new("local", key, SigningMode::OnTheFly, &test_records());
+
+        let rrset = vec![DnsAnswer::new_a_record("test.local".to_string(), Ipv4Addr::new(127, 0, 0, 1), DEFAULT_TTL)];
+        let rrsig = zone.sign_rrset(&rrset);
+        let (prefix, signature) = rrsig_signature(&rrsig, "local");
+
+        let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(&public_key).unwrap();
+        let signature = ed25519_dalek::Signature::from_slice(&signature).unwrap();
+        let data = signed_data(&prefix, "test.local", DEFAULT_TTL, &rrset);
+        assert!(verifying_key.verify(&data, &signature).is_ok());
+    }
+
+    #[test]
+    fn test_ecdsa_p256_offline_rrsig_verifies() {
+        let key = SigningKey::generate(Algorithm::EcdsaP256Sha256);
+        let mut point = vec![0x04];
+        point.extend_from_slice(&key.public_key());
+        let zone = DnssecZone::new("local", key, SigningMode::Offline, &test_records());
+
+        let dnskey = vec![zone.dnskey_record()];
+        let rrsig = zone.sign_rrset(&dnskey);
+        let (prefix, signature) = rrsig_signature(&rrsig, "local");
+
+        let verifying_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&point).unwrap();
+        let signature = p256::ecdsa::Signature::from_slice(&signature).unwrap();
+        let data = signed_data(&prefix, "local", DEFAULT_TTL, &dnskey);
+        assert!(verifying_key.verify(&data, &signature).is_ok());
+    }
+
+    #[test]
+    fn test_offline_rrsig_resigned_before_expiry() {
+        let zone = DnssecZone::new("local", SigningKey::generate(Algorithm::Ed25519), SigningMode::Offline, &test_records());
+        let dnskey = vec![zone.dnskey_record()];
+        let expiration = |rrsig: &DnsAnswer| u32::from_be_bytes(rrsig.rdata[8..12].try_into().unwrap());
+
+        let now = unix_now();
+        let cached = zone.sign_rrset_at(&dnskey, now);
+        assert_eq!(zone.sign_rrset_at(&dnskey, now + 3600).rdata, cached.rdata);
+
+        // Six jours et plus tard: la signature en cache expire dans moins d'un jour
+        let later = now + SIGNATURE_VALIDITY - 3600;
+        let renewed = zone.sign_rrset_at(&dnskey, later);
+        assert!(expiration(&cached) < later + RESIGN_MARGIN);
+        assert_eq!(expiration(&renewed), later + SIGNATURE_VALIDITY);
+
+        // La nouvelle signature remplace l'ancienne dans le cache
+        assert_eq!(zone.sign_rrset_at(&dnskey, later + 3600).rdata, renewed.rdata);
+    }
+
+    #[test]
+    fn test_validity_period_saturates() {
+        assert_eq!(validity_period(60), (0, 60 + SIGNATURE_VALIDITY));
+        assert_eq!(validity_period(u32::MAX).1, u32::MAX);
+
+        let zone = DnssecZone::new("local", SigningKey::generate(Algorithm::Ed25519), SigningMode::OnTheFly, &test_records());
+        let rrsig = zone.sign_rrset_at(&[zone.dnskey_record()], 0);
+        assert_eq!(&rrsig.rdata[12..16], &[0; 4]);
+    }
+
+    #[test]
+    fn test_denial_of_existence() {
+        let zone = DnssecZone::new("local", SigningKey::generate(Algorithm::Ed25519), SigningMode::Offline, &test_records());
+        assert!(!zone.contains("example.com"));
+
+        // Chaîne: local -> dns.local -> server.local -> test.local -> local
+        let nxdomain = zone.denial_of_existence("nonexistent.local");
+        let owners: Vec<(&str, u16)> = nxdomain.iter().map(|rr| (rr.name.as_str(), rr.atype)).collect();
+        assert_eq!(
+            owners,
+            vec![
+                ("dns.local", record_types::NSEC),
+                ("dns.local", record_types::RRSIG),
+                ("local", record_types::NSEC),
+                ("local", record_types::RRSIG),
+            ]
+        );
+
+        let nodata = zone.denial_of_existence("test.local");
+        assert_eq!(nodata.len(), 2);
+        assert_eq!(nodata[0].name, "test.local");
+    }
+}
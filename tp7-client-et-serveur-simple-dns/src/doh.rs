@@ -0,0 +1,306 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+use crate::message::{record_types, DnsMessage, MAX_MESSAGE_SIZE};
+use crate::DnsServer;
+
+/// Chemin de l'endpoint DNS-over-HTTPS (RFC 8484 §3)
+const DOH_PATH: &str = "/dns-query";
+
+/// Type MIME des messages DNS transportés en HTTP
+const DNS_MESSAGE_CONTENT_TYPE: &str = "application/dns-message";
+
+/// Nombre maximal de lignes d'en-tête acceptées par requête
+const MAX_HEADERS: usize = 100;
+
+/// Longueur maximale de la ligne de requête et de chaque ligne d'en-tête
+pub const MAX_LINE_LEN: usize = 8192;
+
+/// Délai maximal d'attente de chaque lecture, y compris entre deux requêtes keep-alive
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Requête HTTP/1.1 décodée
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: Option<String>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .as_deref()?
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+
+    // HTTP/1.1 garde la connexion ouverte sauf demande explicite du client
+    fn keep_alive(&self) -> bool {
+        !self.header("connection").is_some_and(|value| value.eq_ignore_ascii_case("close"))
+    }
+}
+
+/// Réponse HTTP à renvoyer au client
+struct HttpResponse {
+    status: u16,
+    reason: &'static str,
+    content_type: &'static str,
+    extra_headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn error(status: u16, reason: &'static str) -> Self {
+        HttpResponse {
+            status,
+            reason,
+            content_type: "text/plain; charset=utf-8",
+            extra_headers: vec![],
+            body: format!("{} {}\n", status, reason).into_bytes(),
+        }
+    }
+
+    fn dns_message(body: Vec<u8>, max_age: u32) -> Self {
+        HttpResponse {
+            status: 200,
+            reason: "OK",
+            content_type: DNS_MESSAGE_CONTENT_TYPE,
+            extra_headers: vec![("Cache-Control", format!("max-age={}", max_age))],
+            body,
+        }
+    }
+
+    fn to_bytes(&self, keep_alive: bool) -> Vec<u8> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: {}\r\n",
+            self.status,
+            self.reason,
+            self.content_type,
+            self.body.len(),
+            if keep_alive { "keep-alive" } else { "close" }
+        );
+        for (name, value) in &self.extra_headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+impl DnsServer {
+    /// Expose `handle_query` sur HTTP selon le RFC 8484 (GET avec `dns=` en base64url,
+    /// POST avec un corps `application/dns-message`)
+    pub async fn start_doh(self: Arc<Self>, bind_addr: &str) -> Result<(), String> {
+        let listener = TcpListener::bind(bind_addr).await.map_err(|e| e.to_string())?;
+        println!("🌍 Endpoint DNS-over-HTTP démarré sur http://{}{}", bind_addr, DOH_PATH);
+
+        self.serve_doh(listener).await
+    }
+
+    pub async fn serve_doh(self: Arc<Self>, listener: TcpListener) -> Result<(), String> {
+        loop {
+            let (stream, src) = listener.accept().await.map_err(|e| e.to_string())?;
+            let server = Arc::clone(&self);
+
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_http_connection(stream).await {
+                    eprintln!("❌ Erreur HTTP avec {}: {}", src, e);
+                }
+            });
+        }
+    }
+
+    async fn handle_http_connection(&self, stream: TcpStream) -> Result<(), String> {
+        let mut reader = BufReader::new(stream);
+
+        loop {
+            let (response, keep_alive) = match read_request(&mut reader).await {
+                Ok(Some(request)) => {
+                    println!("📨 Requête HTTP {} {}", request.method, request.path);
+                    (self.handle_http_request(&request).await, request.keep_alive())
+                }
+                Ok(None) => break, // Connexion fermée par le client
+                Err(response) => (response, false),
+            };
+
+            reader
+                .get_mut()
+                .write_all(&response.to_bytes(keep_alive))
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if !keep_alive {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_http_request(&self, request: &HttpRequest) -> HttpResponse {
+        if request.path != DOH_PATH {
+            return HttpResponse::error(404, "Not Found");
+        }
+
+        let query_bytes = match request.method.as_str() {
+            "GET" => {
+                let Some(encoded) = request.query_param("dns") else {
+                    return HttpResponse::error(400, "Bad Request");
+                };
+                match URL_SAFE_NO_PAD.decode(encoded.trim_end_matches('=')) {
+                    Ok(bytes) => bytes,
+                    Err(_) => return HttpResponse::error(400, "Bad Request"),
+                }
+            }
+            "POST" => {
+                // Seul le type MIME compte, les paramètres (`; charset=...`) sont ignorés
+                let media_type = request
+                    .header("content-type")
+                    .map(|value| value.split(';').next().unwrap_or("").trim());
+                if !media_type.is_some_and(|media_type| media_type.eq_ignore_ascii_case(DNS_MESSAGE_CONTENT_TYPE)) {
+                    return HttpResponse::error(415, "Unsupported Media Type");
+                }
+                request.body.clone()
+            }
+            _ => {
+                let mut response = HttpResponse::error(405, "Method Not Allowed");
+                response.extra_headers.push(("Allow", "GET, POST".to_string()));
+                return response;
+            }
+        };
+
+        match self.handle_query(&query_bytes).await {
+            Ok(response_bytes) => {
+                let max_age = min_ttl(&response_bytes);
+                HttpResponse::dns_message(response_bytes, max_age)
+            }
+            Err(e) => {
+                eprintln!("❌ Erreur lors du traitement de la requête: {}", e);
+                HttpResponse::error(400, "Bad Request")
+            }
+        }
+    }
+}
+
+/// Résultat de la lecture d'une ligne de l'en-tête HTTP
+enum Line {
+    Read(String),
+    Closed,
+    TimedOut,
+    TooLong,
+    Invalid,
+}
+
+// Lit une ligne d'au plus MAX_LINE_LEN octets en attendant au plus READ_TIMEOUT
+async fn read_line_limited(reader: &mut BufReader<TcpStream>) -> Line {
+    let mut line = String::new();
+    let mut limited = reader.take(MAX_LINE_LEN as u64 + 1);
+    match timeout(READ_TIMEOUT, limited.read_line(&mut line)).await {
+        Err(_) => Line::TimedOut,
+        Ok(Ok(0)) => Line::Closed,
+        Ok(Ok(_)) if line.len() > MAX_LINE_LEN => Line::TooLong,
+        Ok(Ok(_)) => Line::Read(line),
+        Ok(Err(_)) => Line::Invalid,
+    }
+}
+
+// Lit une requête HTTP/1.1 complète; Ok(None) si le client a fermé la connexion
+// ou est resté inactif, Err avec la réponse d'erreur à renvoyer si la requête est mal formée
+async fn read_request(reader: &mut BufReader<TcpStream>) -> Result<Option<HttpRequest>, HttpResponse> {
+    let bad_request = || HttpResponse::error(400, "Bad Request");
+    let timed_out = || HttpResponse::error(408, "Request Timeout");
+
+    // Une connexion keep-alive inactive est simplement fermée
+    let request_line = match read_line_limited(reader).await {
+        Line::Read(line) => line,
+        Line::Closed | Line::TimedOut => return Ok(None),
+        Line::TooLong | Line::Invalid => return Err(bad_request()),
+    };
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(bad_request());
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        let line = match read_line_limited(reader).await {
+            Line::Read(line) => line,
+            Line::TimedOut => return Err(timed_out()),
+            Line::TooLong => return Err(HttpResponse::error(431, "Request Header Fields Too Large")),
+            Line::Closed | Line::Invalid => return Err(bad_request()),
+        };
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() >= MAX_HEADERS {
+            return Err(HttpResponse::error(431, "Request Header Fields Too Large"));
+        }
+        let (name, value) = line.split_once(':').ok_or_else(bad_request)?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let mut request = HttpRequest {
+        method: method.to_string(),
+        path,
+        query,
+        headers,
+        body: Vec::new(),
+    };
+
+    if let Some(length) = request.header("content-length") {
+        let length: usize = length.parse().map_err(|_| bad_request())?;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(HttpResponse::error(413, "Payload Too Large"));
+        }
+        request.body = vec![0; length];
+        timeout(READ_TIMEOUT, reader.read_exact(&mut request.body))
+            .await
+            .map_err(|_| timed_out())?
+            .map_err(|_| bad_request())?;
+    }
+
+    Ok(Some(request))
+}
+
+// Durée de mise en cache de la réponse: plus petit TTL des enregistrements (RFC 8484 §5.1)
+fn min_ttl(response_bytes: &[u8]) -> u32 {
+    DnsMessage::from_bytes(response_bytes)
+        .ok()
+        .and_then(|response| {
+            response
+                .answers
+                .iter()
+                .chain(&response.authorities)
+                .chain(&response.additionals)
+                .filter(|rr| rr.atype != record_types::OPT)
+                .map(|rr| rr.ttl)
+                .min()
+        })
+        .unwrap_or(0)
+}
@@ -1,288 +1,124 @@
+mod dnssec;
+mod doh;
+mod message;
+mod validator;
+
+#[cfg(test)]
+mod tests;
+
 use std::collections::HashMap;
 use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::UdpSocket;
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::Cursor;
-use rand::Rng;
-
-// Structure pour l'en-tête DNS selon RFC 1035
-#[derive(Debug, Clone)]
-struct DnsHeader {
-    id: u16,           // Identifiant de la requête
-    flags: u16,        // Flags (QR, OPCODE, AA, TC, RD, RA, Z, RCODE)
-    qdcount: u16,      // Nombre de questions
-    ancount: u16,      // Nombre de réponses
-    nscount: u16,      // Nombre d'enregistrements d'autorité
-    arcount: u16,      // Nombre d'enregistrements additionnels
-}
-
-impl DnsHeader {
-    fn new_query(id: u16) -> Self {
-        DnsHeader {
-            id,
-            flags: 0x0100, // RD (Recursion Desired) = 1
-            qdcount: 1,
-            ancount: 0,
-            nscount: 0,
-            arcount: 0,
-        }
-    }
 
-    fn new_response(id: u16, answer_count: u16) -> Self {
-        DnsHeader {
-            id,
-            flags: 0x8180, // QR=1, RD=1, RA=1
-            qdcount: 1,
-            ancount: answer_count,
-            nscount: 0,
-            arcount: 0,
-        }
-    }
+use dnssec::{Algorithm, DnssecZone, SigningKey, SigningMode};
+use message::{classes, rcodes, record_types, DnsAnswer, DnsMessage, DnsQuestion, Truncated};
+use validator::{LookupResult, TrustAnchor};
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.write_u16::<BigEndian>(self.id).unwrap();
-        bytes.write_u16::<BigEndian>(self.flags).unwrap();
-        bytes.write_u16::<BigEndian>(self.qdcount).unwrap();
-        bytes.write_u16::<BigEndian>(self.ancount).unwrap();
-        bytes.write_u16::<BigEndian>(self.nscount).unwrap();
-        bytes.write_u16::<BigEndian>(self.arcount).unwrap();
-        bytes
-    }
+/// TTL des enregistrements servis (5 minutes)
+const DEFAULT_TTL: u32 = 300;
 
-    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
-        let mut cursor = Cursor::new(bytes);
-        Ok(DnsHeader {
-            id: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
-            flags: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
-            qdcount: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
-            ancount: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
-            nscount: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
-            arcount: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
-        })
-    }
-}
+/// Taille d'une réponse UDP sans EDNS (RFC 1035 §4.2.1)
+const UDP_PAYLOAD_SIZE: usize = 512;
 
-// Structure pour une question DNS
-#[derive(Debug, Clone)]
-struct DnsQuestion {
-    name: String,
-    qtype: u16,    // Type de requête (A = 1, AAAA = 28, etc.)
-    qclass: u16,   // Classe (IN = 1 pour Internet)
-}
+/// Taille maximale des messages UDP reçus et envoyés, même si le client annonce
+/// davantage via EDNS; c'est aussi la taille annoncée dans l'OPT des réponses
+const MAX_UDP_PAYLOAD_SIZE: usize = 4096;
 
-impl DnsQuestion {
-    fn new(name: String) -> Self {
-        DnsQuestion {
-            name,
-            qtype: 1,  // Type A (IPv4)
-            qclass: 1, // Classe IN (Internet)
-        }
-    }
+// Client DNS
+struct DnsClient;
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        
-        // Encoder le nom de domaine
-        for part in self.name.split('.') {
-            bytes.push(part.len() as u8);
-            bytes.extend_from_slice(part.as_bytes());
-        }
-        bytes.push(0); // Fin du nom
+impl DnsClient {
+    async fn resolve(domain: &str, dns_server: &str) -> Result<Option<Ipv4Addr>, String> {
+        let server_addr = Self::server_addr(dns_server)?;
+        let query = DnsMessage::new_query(domain.to_string());
         
-        bytes.write_u16::<BigEndian>(self.qtype).unwrap();
-        bytes.write_u16::<BigEndian>(self.qclass).unwrap();
-        bytes
-    }
-
-    fn from_bytes(bytes: &[u8], offset: &mut usize) -> Result<Self, String> {
-        let mut name_parts = Vec::new();
+        println!("📤 Envoi de la requête DNS pour {} vers {}", domain, dns_server);
+        let response = Self::exchange(server_addr, &query).await?;
         
-        while *offset < bytes.len() {
-            let len = bytes[*offset] as usize;
-            *offset += 1;
-            
-            if len == 0 {
-                break;
+        if response.rcode() == rcodes::NOERROR {
+            if let Some(ip) = response.addresses().first() {
+                println!("✅ Résolution réussie: {} -> {}", domain, ip);
+                return Ok(Some(*ip));
             }
-            
-            let part = String::from_utf8(bytes[*offset..*offset + len].to_vec())
-                .map_err(|e| e.to_string())?;
-            name_parts.push(part);
-            *offset += len;
         }
         
-        let name = name_parts.join(".");
-        
-        let mut cursor = Cursor::new(&bytes[*offset..]);
-        let qtype = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
-        let qclass = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
-        *offset += 4;
-        
-        Ok(DnsQuestion { name, qtype, qclass })
-    }
-}
-
-// Structure pour une réponse DNS
-#[derive(Debug, Clone)]
-struct DnsAnswer {
-    name: String,
-    atype: u16,    // Type de l'enregistrement
-    aclass: u16,   // Classe
-    ttl: u32,      // Time To Live
-    rdlength: u16, // Longueur des données
-    rdata: Vec<u8>, // Données de la réponse
-}
-
-impl DnsAnswer {
-    fn new_a_record(name: String, ip: Ipv4Addr, ttl: u32) -> Self {
-        DnsAnswer {
-            name,
-            atype: 1,  // Type A
-            aclass: 1, // Classe IN
-            ttl,
-            rdlength: 4,
-            rdata: ip.octets().to_vec(),
-        }
+        println!("❌ Échec de la résolution pour {}", domain);
+        Ok(None)
     }
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        
-        // Encoder le nom (même format que dans la question)
-        for part in self.name.split('.') {
-            bytes.push(part.len() as u8);
-            bytes.extend_from_slice(part.as_bytes());
-        }
-        bytes.push(0);
+    // Résolution validante: demande les enregistrements DNSSEC (bit DO) et vérifie la
+    // chaîne de confiance depuis l'ancre jusqu'à la réponse
+    async fn resolve_validated(
+        domain: &str,
+        dns_server: &str,
+        trust_anchor: &TrustAnchor,
+    ) -> Result<LookupResult, String> {
+        let server_addr = Self::server_addr(dns_server)?;
         
-        bytes.write_u16::<BigEndian>(self.atype).unwrap();
-        bytes.write_u16::<BigEndian>(self.aclass).unwrap();
-        bytes.write_u32::<BigEndian>(self.ttl).unwrap();
-        bytes.write_u16::<BigEndian>(self.rdlength).unwrap();
-        bytes.extend_from_slice(&self.rdata);
-        
-        bytes
-    }
-}
-
-// Structure complète d'un message DNS
-#[derive(Debug)]
-struct DnsMessage {
-    header: DnsHeader,
-    questions: Vec<DnsQuestion>,
-    answers: Vec<DnsAnswer>,
-}
-
-impl DnsMessage {
-    fn new_query(domain: String) -> Self {
-        let id = rand::thread_rng().gen::<u16>();
-        DnsMessage {
-            header: DnsHeader::new_query(id),
-            questions: vec![DnsQuestion::new(domain)],
-            answers: vec![],
-        }
+        println!("🔐 Résolution validée (DNSSEC) de {} vers {}", domain, dns_server);
+        let result = validator::validated_lookup(domain, server_addr, trust_anchor).await?;
+        println!("{} Statut DNSSEC pour {}: {}",
+            if result.is_secure() { "🔒" } else { "🔓" }, domain, result.status);
+        Ok(result)
     }
 
-    fn new_response(query_id: u16, question: DnsQuestion, answers: Vec<DnsAnswer>) -> Self {
-        DnsMessage {
-            header: DnsHeader::new_response(query_id, answers.len() as u16),
-            questions: vec![question],
-            answers,
+    // Gérer le cas où le serveur DNS inclut déjà le port
+    fn server_addr(dns_server: &str) -> Result<SocketAddr, String> {
+        if dns_server.contains(':') {
+            dns_server.parse().map_err(|e: std::net::AddrParseError| e.to_string())
+        } else {
+            format!("{}:53", dns_server).parse()
+                .map_err(|e: std::net::AddrParseError| e.to_string())
         }
     }
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    // Envoie une requête et attend la réponse correspondante
+    async fn exchange(server_addr: SocketAddr, query: &DnsMessage) -> Result<DnsMessage, String> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+        let query_bytes = query.to_bytes().map_err(|_| "Requête trop grande pour être encodée".to_string())?;
+        socket.send_to(&query_bytes, server_addr).await.map_err(|e| e.to_string())?;
         
-        bytes.extend_from_slice(&self.header.to_bytes());
+        let mut buf = [0u8; 4096];
+        let (size, _) = tokio::time::timeout(Duration::from_secs(5), socket.recv_from(&mut buf))
+            .await
+            .map_err(|_| "Pas de réponse du serveur DNS".to_string())?
+            .map_err(|e| e.to_string())?;
         
-        for question in &self.questions {
-            bytes.extend_from_slice(&question.to_bytes());
+        let response = DnsMessage::from_bytes(&buf[..size])?;
+        if response.header.id != query.header.id {
+            return Err("Identifiant de réponse inattendu".to_string());
         }
-        
-        for answer in &self.answers {
-            bytes.extend_from_slice(&answer.to_bytes());
-        }
-        
-        bytes
-    }
-
-    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
-        let header = DnsHeader::from_bytes(&bytes[0..12])?;
-        let mut offset = 12;
-        
-        let mut questions = Vec::new();
-        for _ in 0..header.qdcount {
-            let question = DnsQuestion::from_bytes(bytes, &mut offset)?;
-            questions.push(question);
+        if response.is_truncated() {
+            println!("⚠️  Réponse tronquée par le serveur (bit TC)");
         }
-        
-        // Pour simplifier, on ne parse pas les réponses dans ce TP
-        let answers = Vec::new();
-        
-        Ok(DnsMessage {
-            header,
-            questions,
-            answers,
-        })
+        Ok(response)
     }
 }
 
-// Client DNS
-struct DnsClient;
+/// Traitement des requêtes contenant plusieurs questions (QDCOUNT > 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MultiQuestionPolicy {
+    /// Réponse FORMERR, comme la plupart des serveurs (par défaut)
+    Reject,
+    /// Chaque question reçoit ses réponses dans le même message
+    AnswerEach,
+}
 
-impl DnsClient {
-    async fn resolve(domain: &str, dns_server: &str) -> Result<Option<Ipv4Addr>, String> {
-        let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
-        
-        // Gérer le cas où le serveur DNS inclut déjà le port
-        let server_addr: SocketAddr = if dns_server.contains(':') {
-            dns_server.parse().map_err(|e: std::net::AddrParseError| e.to_string())?
-        } else {
-            format!("{}:53", dns_server).parse()
-                .map_err(|e: std::net::AddrParseError| e.to_string())?
-        };
-        
-        let query = DnsMessage::new_query(domain.to_string());
-        let query_bytes = query.to_bytes();
-        
-        println!("📤 Envoi de la requête DNS pour {} vers {}", domain, dns_server);
-        socket.send_to(&query_bytes, server_addr).await.map_err(|e| e.to_string())?;
-        
-        let mut buf = [0u8; 512];
-        let (size, _) = socket.recv_from(&mut buf).await.map_err(|e| e.to_string())?;
-        
-        // Parse simple de la réponse (extraction de l'IP depuis les données brutes)
-        if size > 12 {
-            let _response = DnsMessage::from_bytes(&buf[..size])?;
-            
-            // Recherche de l'IP dans les données brutes (position approximative)
-            // Cette implémentation est simplifiée pour le TP
-            if size >= 40 && buf[3] & 0x0F == 0 { // Pas d'erreur
-                // L'IP se trouve généralement vers la fin du paquet
-                let ip_offset = size - 4;
-                if ip_offset >= 4 {
-                    let ip = Ipv4Addr::new(
-                        buf[ip_offset - 4],
-                        buf[ip_offset - 3],
-                        buf[ip_offset - 2],
-                        buf[ip_offset - 1]
-                    );
-                    println!("✅ Résolution réussie: {} -> {}", domain, ip);
-                    return Ok(Some(ip));
-                }
-            }
-        }
-        
-        println!("❌ Échec de la résolution pour {}", domain);
-        Ok(None)
-    }
+// Réponses à une seule question de la requête
+struct QuestionAnswer {
+    answers: Vec<DnsAnswer>,
+    authorities: Vec<DnsAnswer>,
+    name_exists: bool,
 }
 
 // Serveur DNS simple
 struct DnsServer {
     records: HashMap<String, Ipv4Addr>,
+    dnssec: Option<DnssecZone>,
+    multi_question: MultiQuestionPolicy,
+    hostname: String,
 }
 
 impl DnsServer {
@@ -296,7 +132,28 @@ impl DnsServer {
         records.insert("dns.local".to_string(), Ipv4Addr::new(192, 168, 1, 1));
         records.insert("67gigs.com".to_string(), Ipv4Addr::new(67, 67, 67, 67));
         
-        DnsServer { records }
+        // Nom renvoyé pour hostname.bind (classe CHAOS)
+        let hostname = std::env::var("HOSTNAME")
+            .or_else(|_| std::env::var("COMPUTERNAME"))
+            .unwrap_or_else(|_| "tp7-dns".to_string());
+        
+        DnsServer {
+            records,
+            dnssec: None,
+            multi_question: MultiQuestionPolicy::Reject,
+            hostname,
+        }
+    }
+
+    fn with_multi_question_policy(mut self, policy: MultiQuestionPolicy) -> Self {
+        self.multi_question = policy;
+        self
+    }
+
+    // Signe la zone `apex` (et les domaines en dessous) avec la clé fournie
+    fn with_dnssec(mut self, apex: &str, key: SigningKey, mode: SigningMode) -> Self {
+        self.dnssec = Some(DnssecZone::new(apex, key, mode, &self.records));
+        self
     }
 
     async fn start(&self, bind_addr: &str) -> Result<(), String> {
@@ -306,17 +163,26 @@ impl DnsServer {
         for (domain, ip) in &self.records {
             println!("   {} -> {}", domain, ip);
         }
+        if let Some(zone) = &self.dnssec {
+            println!("🔐 Zone signée (DNSSEC): {} (algorithme {}, key tag {})",
+                zone.apex(), zone.algorithm(), zone.key_tag());
+        }
         println!();
 
-        let mut buf = [0u8; 512];
+        self.serve(socket).await
+    }
+
+    async fn serve(&self, socket: UdpSocket) -> Result<(), String> {
+        let mut buf = [0u8; MAX_UDP_PAYLOAD_SIZE];
+        let mut response_buf = [0u8; MAX_UDP_PAYLOAD_SIZE];
         
         loop {
             let (size, src) = socket.recv_from(&mut buf).await.map_err(|e| e.to_string())?;
             println!("📨 Requête reçue de {} ({} bytes)", src, size);
             
-            match self.handle_query(&buf[..size]).await {
-                Ok(response) => {
-                    socket.send_to(&response, src).await.map_err(|e| e.to_string())?;
+            match self.handle_udp_query(&buf[..size], &mut response_buf) {
+                Ok(len) => {
+                    socket.send_to(&response_buf[..len], src).await.map_err(|e| e.to_string())?;
                     println!("📤 Réponse envoyée à {}", src);
                 }
                 Err(e) => {
@@ -326,39 +192,190 @@ impl DnsServer {
         }
     }
 
-    async fn handle_query(&self, query_bytes: &[u8]) -> Result<Vec<u8>, String> {
+    // Encode la réponse directement dans `response_buf`, limitée à la taille UDP
+    // acceptée par le client; au-delà, renvoie une réponse tronquée (bit TC)
+    fn handle_udp_query(&self, query_bytes: &[u8], response_buf: &mut [u8]) -> Result<usize, String> {
         let query = DnsMessage::from_bytes(query_bytes)?;
+        let response = self.build_response(&query)?;
+        
+        let limit = query.edns()
+            .map_or(UDP_PAYLOAD_SIZE, |opt| (opt.aclass as usize).max(UDP_PAYLOAD_SIZE))
+            .min(response_buf.len());
+        let response_buf = &mut response_buf[..limit];
         
+        match response.encode_into(response_buf) {
+            Ok(len) => Ok(len),
+            Err(Truncated) => {
+                println!("✂️  Réponse tronquée (limite de {} octets)", limit);
+                response.truncated()
+                    .encode_into(response_buf)
+                    .map_err(|_| "Question trop longue pour une réponse UDP".to_string())
+            }
+        }
+    }
+
+    async fn handle_query(&self, query_bytes: &[u8]) -> Result<Vec<u8>, String> {
+        let query = DnsMessage::from_bytes(query_bytes)?;
+        self.build_response(&query)?
+            .to_bytes()
+            .map_err(|_| "Réponse trop grande pour être encodée".to_string())
+    }
+
+    fn build_response(&self, query: &DnsMessage) -> Result<DnsMessage, String> {
         if query.questions.is_empty() {
             return Err("Aucune question dans la requête".to_string());
         }
         
-        let question = &query.questions[0];
-        println!("🔍 Recherche de: {} (type: {})", question.name, question.qtype);
+        let mut response = if query.questions.len() > 1
+            && self.multi_question == MultiQuestionPolicy::Reject
+        {
+            println!("❌ {} questions dans la requête: FORMERR", query.questions.len());
+            DnsMessage::new_response(query.header.id, query.questions.clone(), vec![])
+                .with_rcode(rcodes::FORMERR)
+        } else {
+            let mut answers = Vec::new();
+            let mut authorities = Vec::new();
+            let mut name_exists = false;
+            
+            for question in &query.questions {
+                let result = self.answer_question(question, query.dnssec_ok());
+                answers.extend(result.answers);
+                authorities.extend(result.authorities);
+                name_exists |= result.name_exists;
+            }
+            
+            // NXDOMAIN seulement si aucun des noms demandés n'existe
+            let response = DnsMessage::new_response(
+                query.header.id,
+                query.questions.clone(),
+                answers
+            ).with_authorities(authorities);
+            
+            if name_exists {
+                response
+            } else {
+                response.with_rcode(rcodes::NXDOMAIN)
+            }
+        };
+        
+        if query.edns().is_some() {
+            response = response.with_additionals(vec![DnsAnswer::new_opt(MAX_UDP_PAYLOAD_SIZE as u16, query.dnssec_ok())]);
+        }
+        
+        Ok(response)
+    }
+
+    fn answer_question(&self, question: &DnsQuestion, dnssec_ok: bool) -> QuestionAnswer {
+        println!("🔍 Recherche de: {} (type: {}, classe: {})",
+            question.name, question.qtype, question.qclass);
         
+        if question.qclass == classes::CH {
+            return self.answer_chaos(question);
+        }
+        
+        // Les noms DNS sont insensibles à la casse (RFC 4343): les enregistrements sont
+        // cherchés sous la forme canonique, comme dans la zone signée
+        let name = dnssec::normalize(&question.name);
+        let zone = self.dnssec.as_ref().filter(|zone| zone.contains(&question.name));
+        let name_exists = self.records.contains_key(&name)
+            || zone.is_some_and(|zone| zone.is_apex(&question.name));
         let mut answers = Vec::new();
         
-        if question.qtype == 1 { // Type A
-            if let Some(ip) = self.records.get(&question.name) {
+        if question.qtype == record_types::A || question.qtype == record_types::ANY {
+            if let Some(ip) = self.records.get(&name) {
                 let answer = DnsAnswer::new_a_record(
                     question.name.clone(),
                     *ip,
-                    300 // TTL de 5 minutes
+                    DEFAULT_TTL
                 );
                 answers.push(answer);
                 println!("✅ Réponse trouvée: {} -> {}", question.name, ip);
             } else {
                 println!("❌ Domaine non trouvé: {}", question.name);
             }
+        } else if question.qtype == record_types::DNSKEY {
+            if let Some(zone) = zone.filter(|zone| zone.is_apex(&question.name)) {
+                answers.push(zone.dnskey_record());
+            }
+        }
+        
+        // ANY: un seul RRset au lieu de tous (RFC 8482 §4.1); à défaut d'enregistrement A,
+        // un HINFO synthétisé indique que la requête ANY n'est pas servie (§4.2)
+        if question.qtype == record_types::ANY && answers.is_empty() && name_exists {
+            answers.push(rfc8482_hinfo(question.name.clone()));
         }
         
-        let response = DnsMessage::new_response(
-            query.header.id,
-            question.clone(),
-            answers
-        );
+        // Signatures et preuves de non-existence uniquement si le client a positionné le bit DO
+        let mut authorities = Vec::new();
+        if let Some(zone) = zone.filter(|_| dnssec_ok) {
+            if answers.is_empty() {
+                authorities = zone.denial_of_existence(&question.name);
+            } else {
+                answers.push(zone.sign_rrset(&answers));
+            }
+        }
         
-        Ok(response.to_bytes())
+        QuestionAnswer { answers, authorities, name_exists }
+    }
+
+    // Requêtes de diagnostic en classe CHAOS (version.bind, hostname.bind)
+    fn answer_chaos(&self, question: &DnsQuestion) -> QuestionAnswer {
+        let text = if question.name.eq_ignore_ascii_case("version.bind") {
+            Some(concat!("tp7-dns ", env!("CARGO_PKG_VERSION")))
+        } else if question.name.eq_ignore_ascii_case("hostname.bind") {
+            Some(self.hostname.as_str())
+        } else {
+            None
+        };
+        
+        let answers = match text {
+            Some(text) if question.qtype == record_types::TXT || question.qtype == record_types::ANY => {
+                println!("✅ Réponse CHAOS: {} -> \"{}\"", question.name, text);
+                vec![DnsAnswer::new_chaos_txt(question.name.clone(), text)]
+            }
+            _ => vec![],
+        };
+        
+        QuestionAnswer { answers, authorities: vec![], name_exists: text.is_some() }
+    }
+}
+
+// HINFO "RFC8482" "" renvoyé aux requêtes ANY (RFC 8482 §4.2)
+fn rfc8482_hinfo(name: String) -> DnsAnswer {
+    let mut rdata = vec![7];
+    rdata.extend_from_slice(b"RFC8482");
+    rdata.push(0);
+    DnsAnswer::new(name, record_types::HINFO, DEFAULT_TTL, rdata)
+}
+
+// Plusieurs questions par requête: FORMERR par défaut, une réponse par question
+// avec DNS_MULTI_QUESTION=answer
+fn multi_question_policy() -> MultiQuestionPolicy {
+    if std::env::var("DNS_MULTI_QUESTION").is_ok_and(|value| value == "answer") {
+        MultiQuestionPolicy::AnswerEach
+    } else {
+        MultiQuestionPolicy::Reject
+    }
+}
+
+// Clé de signature de la zone: lue depuis DNSSEC_KEY_FILE (32 octets bruts) si la
+// variable est définie, sinon générée au démarrage
+fn load_zone_key() -> Result<SigningKey, String> {
+    match std::env::var("DNSSEC_KEY_FILE") {
+        Ok(path) => {
+            let secret = std::fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+            SigningKey::from_bytes(Algorithm::Ed25519, &secret)
+        }
+        Err(_) => Ok(SigningKey::generate(Algorithm::Ed25519)),
+    }
+}
+
+// Signature de toute la zone au démarrage par défaut, ou à la volée avec DNSSEC_ON_THE_FLY=1
+fn zone_signing_mode() -> SigningMode {
+    if std::env::var("DNSSEC_ON_THE_FLY").is_ok_and(|value| value == "1") {
+        SigningMode::OnTheFly
+    } else {
+        SigningMode::Offline
     }
 }
 
@@ -367,8 +384,24 @@ async fn main() -> Result<(), String> {
     println!("🌐 TP 7: Client et Serveur DNS Simples");
     println!("=====================================");
     
-    // Démarrer le serveur DNS en arrière-plan
-    let server = DnsServer::new();
+    // Démarrer le serveur DNS en arrière-plan, avec la zone .local signée par une clé Ed25519
+    let server = DnsServer::new()
+        .with_dnssec("local", load_zone_key()?, zone_signing_mode())
+        .with_multi_question_policy(multi_question_policy());
+    let ds = server.dnssec.as_ref()
+        .map(|zone| zone.ds_record())
+        .ok_or("Zone DNSSEC non configurée")?;
+    let trust_anchor = TrustAnchor::from_ds(&ds.name, &ds.rdata);
+    let server = Arc::new(server);
+    
+    // Endpoint HTTP (RFC 8484) pour les clients qui ne peuvent pas utiliser UDP
+    let doh_server = Arc::clone(&server);
+    tokio::spawn(async move {
+        if let Err(e) = doh_server.start_doh("0.0.0.0:8054").await {
+            eprintln!("Erreur serveur DNS-over-HTTP: {}", e);
+        }
+    });
+    
     let server_task = tokio::spawn(async move {
         // Utiliser un port plus élevé pour éviter les problèmes de permissions
         if let Err(e) = server.start("0.0.0.0:8053").await {
@@ -401,6 +434,17 @@ async fn main() -> Result<(), String> {
         println!();
     }
     
+    println!("\n🔐 Test du client DNS validant (ancre de confiance: {}):", trust_anchor.zone());
+    println!("------------------------------------------------------");
+    
+    for domain in ["test.local", "nonexistent.local", "example.com"] {
+        match DnsClient::resolve_validated(domain, "127.0.0.1:8053", &trust_anchor).await {
+            Ok(result) => println!("   {} -> {:?} ({})", domain, result.addresses, result.status),
+            Err(e) => println!("❌ Erreur pour {}: {}", domain, e),
+        }
+        println!();
+    }
+    
     println!("\n🌍 Test avec des serveurs DNS publics:");
     println!("--------------------------------------");
     
@@ -421,6 +465,8 @@ async fn main() -> Result<(), String> {
     
     println!("💡 Le serveur DNS continue de fonctionner sur 127.0.0.1:8053");
     println!("   Vous pouvez tester avec: nslookup test.local 127.0.0.1 -port=8053");
+    println!("   Réponses signées (DNSSEC): dig @127.0.0.1 -p 8053 +dnssec test.local");
+    println!("   DNS-over-HTTP: curl -s 'http://127.0.0.1:8054/dns-query?dns=AAABAAABAAAAAAAABHRlc3QFbG9jYWwAAAEAAQ' | xxd");
     
     // Maintenir le serveur en vie
     server_task.await.map_err(|e| e.to_string())?;
@@ -0,0 +1,545 @@
+use std::io::Cursor;
+use std::net::Ipv4Addr;
+use byteorder::{BigEndian, ReadBytesExt};
+use rand::Rng;
+
+/// Taille maximale d'un message DNS (champ longueur 16 bits en TCP et en DoH)
+pub const MAX_MESSAGE_SIZE: usize = 65535;
+
+/// Types d'enregistrements DNS utilisés par le serveur
+pub mod record_types {
+    pub const A: u16 = 1;
+    pub const NS: u16 = 2;
+    pub const CNAME: u16 = 5;
+    pub const HINFO: u16 = 13;
+    pub const TXT: u16 = 16;
+    pub const OPT: u16 = 41;
+    pub const DS: u16 = 43;
+    pub const RRSIG: u16 = 46;
+    pub const NSEC: u16 = 47;
+    pub const DNSKEY: u16 = 48;
+    pub const ANY: u16 = 255;
+}
+
+/// Classes DNS
+pub mod classes {
+    pub const IN: u16 = 1;
+    pub const CH: u16 = 3;
+}
+
+/// Codes de retour (RCODE) de l'en-tête DNS
+pub mod rcodes {
+    pub const NOERROR: u16 = 0;
+    pub const FORMERR: u16 = 1;
+    pub const NXDOMAIN: u16 = 3;
+}
+
+// Encode un nom de domaine au format DNS (suite de labels terminée par 0)
+pub fn encode_name(name: &str, bytes: &mut Vec<u8>) {
+    for part in name.split('.').filter(|part| !part.is_empty()) {
+        bytes.push(part.len() as u8);
+        bytes.extend_from_slice(part.as_bytes());
+    }
+    bytes.push(0); // Fin du nom
+}
+
+// Taille d'un nom encodé par `encode_name`
+fn encoded_name_len(name: &str) -> usize {
+    name.split('.').filter(|part| !part.is_empty()).map(|part| part.len() + 1).sum::<usize>() + 1
+}
+
+/// Le message ne tient pas dans le tampon fourni à l'encodeur
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncated;
+
+/// Encodeur sans allocation: écrit dans un tampon fourni par l'appelant et
+/// signale `Truncated` dès qu'une écriture dépasse sa capacité
+pub struct DnsEncoder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> DnsEncoder<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        DnsEncoder { buf, len: 0 }
+    }
+
+    /// Nombre d'octets déjà écrits
+    pub fn position(&self) -> usize {
+        self.len
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Truncated> {
+        let end = self.len + bytes.len();
+        self.buf.get_mut(self.len..end).ok_or(Truncated)?.copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    pub fn write_u8(&mut self, value: u8) -> Result<(), Truncated> {
+        self.write_bytes(&[value])
+    }
+
+    pub fn write_u16(&mut self, value: u16) -> Result<(), Truncated> {
+        self.write_bytes(&value.to_be_bytes())
+    }
+
+    pub fn write_u32(&mut self, value: u32) -> Result<(), Truncated> {
+        self.write_bytes(&value.to_be_bytes())
+    }
+
+    pub fn write_name(&mut self, name: &str) -> Result<(), Truncated> {
+        for part in name.split('.').filter(|part| !part.is_empty()) {
+            self.write_u8(part.len() as u8)?;
+            self.write_bytes(part.as_bytes())?;
+        }
+        self.write_u8(0) // Fin du nom
+    }
+}
+
+// Décode un nom de domaine en suivant les pointeurs de compression (RFC 1035 §4.1.4)
+pub fn read_name(bytes: &[u8], offset: &mut usize) -> Result<String, String> {
+    let mut labels = Vec::new();
+    let mut pos = *offset;
+    let mut jumps = 0;
+
+    loop {
+        let len = *bytes.get(pos).ok_or("Nom de domaine tronqué")? as usize;
+
+        if len & 0xC0 == 0xC0 {
+            // Pointeur de compression: les 14 bits suivants donnent la position du reste du nom
+            let low = *bytes.get(pos + 1).ok_or("Pointeur de compression tronqué")? as usize;
+            if jumps == 0 {
+                *offset = pos + 2;
+            }
+            jumps += 1;
+            if jumps > 32 {
+                return Err("Boucle de pointeurs de compression".to_string());
+            }
+            pos = ((len & 0x3F) << 8) | low;
+            continue;
+        }
+
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+
+        let label = bytes.get(pos..pos + len).ok_or("Label tronqué")?;
+        labels.push(String::from_utf8(label.to_vec()).map_err(|e| e.to_string())?);
+        pos += len;
+    }
+
+    if jumps == 0 {
+        *offset = pos;
+    }
+    Ok(labels.join("."))
+}
+
+// Structure pour l'en-tête DNS selon RFC 1035
+#[derive(Debug, Clone)]
+pub struct DnsHeader {
+    pub id: u16,           // Identifiant de la requête
+    pub flags: u16,        // Flags (QR, OPCODE, AA, TC, RD, RA, Z, RCODE)
+    pub qdcount: u16,      // Nombre de questions
+    pub ancount: u16,      // Nombre de réponses
+    pub nscount: u16,      // Nombre d'enregistrements d'autorité
+    pub arcount: u16,      // Nombre d'enregistrements additionnels
+}
+
+impl DnsHeader {
+    pub fn new_query(id: u16) -> Self {
+        DnsHeader {
+            id,
+            flags: 0x0100, // RD (Recursion Desired) = 1
+            qdcount: 1,
+            ancount: 0,
+            nscount: 0,
+            arcount: 0,
+        }
+    }
+
+    pub fn new_response(id: u16, question_count: u16, answer_count: u16) -> Self {
+        DnsHeader {
+            id,
+            flags: 0x8180, // QR=1, RD=1, RA=1
+            qdcount: question_count,
+            ancount: answer_count,
+            nscount: 0,
+            arcount: 0,
+        }
+    }
+
+    pub fn encode(&self, encoder: &mut DnsEncoder) -> Result<(), Truncated> {
+        encoder.write_u16(self.id)?;
+        encoder.write_u16(self.flags)?;
+        encoder.write_u16(self.qdcount)?;
+        encoder.write_u16(self.ancount)?;
+        encoder.write_u16(self.nscount)?;
+        encoder.write_u16(self.arcount)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut cursor = Cursor::new(bytes);
+        Ok(DnsHeader {
+            id: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
+            flags: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
+            qdcount: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
+            ancount: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
+            nscount: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
+            arcount: cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?,
+        })
+    }
+}
+
+// Structure pour une question DNS
+#[derive(Debug, Clone)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,    // Type de requête (A = 1, AAAA = 28, etc.)
+    pub qclass: u16,   // Classe (IN = 1 pour Internet)
+}
+
+impl DnsQuestion {
+    pub fn new(name: String) -> Self {
+        DnsQuestion {
+            name,
+            qtype: 1,  // Type A (IPv4)
+            qclass: classes::IN, // Classe IN (Internet)
+        }
+    }
+
+    pub fn with_type(name: String, qtype: u16) -> Self {
+        DnsQuestion { qtype, ..DnsQuestion::new(name) }
+    }
+
+    pub fn encode(&self, encoder: &mut DnsEncoder) -> Result<(), Truncated> {
+        // Encoder le nom de domaine
+        encoder.write_name(&self.name)?;
+        
+        encoder.write_u16(self.qtype)?;
+        encoder.write_u16(self.qclass)
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        encoded_name_len(&self.name) + 4
+    }
+
+    pub fn from_bytes(bytes: &[u8], offset: &mut usize) -> Result<Self, String> {
+        let name = read_name(bytes, offset)?;
+        
+        let mut cursor = Cursor::new(&bytes[*offset..]);
+        let qtype = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
+        let qclass = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
+        *offset += 4;
+        
+        Ok(DnsQuestion { name, qtype, qclass })
+    }
+}
+
+// Structure pour une réponse DNS
+#[derive(Debug, Clone)]
+pub struct DnsAnswer {
+    pub name: String,
+    pub atype: u16,    // Type de l'enregistrement
+    pub aclass: u16,   // Classe
+    pub ttl: u32,      // Time To Live
+    pub rdlength: u16, // Longueur des données
+    pub rdata: Vec<u8>, // Données de la réponse
+}
+
+impl DnsAnswer {
+    pub fn new_a_record(name: String, ip: Ipv4Addr, ttl: u32) -> Self {
+        DnsAnswer {
+            name,
+            atype: 1,  // Type A
+            aclass: classes::IN,
+            ttl,
+            rdlength: 4,
+            rdata: ip.octets().to_vec(),
+        }
+    }
+
+    pub fn new(name: String, atype: u16, ttl: u32, rdata: Vec<u8>) -> Self {
+        DnsAnswer {
+            name,
+            atype,
+            aclass: classes::IN,
+            ttl,
+            rdlength: rdata.len() as u16,
+            rdata,
+        }
+    }
+
+    // Enregistrement TXT de classe CHAOS (version.bind, hostname.bind)
+    pub fn new_chaos_txt(name: String, text: &str) -> Self {
+        let text = &text.as_bytes()[..text.len().min(255)];
+        let mut rdata = vec![text.len() as u8];
+        rdata.extend_from_slice(text);
+        DnsAnswer {
+            aclass: classes::CH,
+            ..DnsAnswer::new(name, record_types::TXT, 0, rdata)
+        }
+    }
+
+    // Pseudo-enregistrement OPT (EDNS0, RFC 6891): la classe porte la taille UDP
+    // acceptée et le TTL porte les flags étendus, dont le bit DO (DNSSEC OK)
+    pub fn new_opt(udp_payload_size: u16, dnssec_ok: bool) -> Self {
+        DnsAnswer {
+            name: String::new(),
+            atype: record_types::OPT,
+            aclass: udp_payload_size,
+            ttl: if dnssec_ok { 0x8000 } else { 0 },
+            rdlength: 0,
+            rdata: vec![],
+        }
+    }
+
+    pub fn encode(&self, encoder: &mut DnsEncoder) -> Result<(), Truncated> {
+        // Encoder le nom (même format que dans la question)
+        encoder.write_name(&self.name)?;
+        
+        encoder.write_u16(self.atype)?;
+        encoder.write_u16(self.aclass)?;
+        encoder.write_u32(self.ttl)?;
+        encoder.write_u16(self.rdlength)?;
+        encoder.write_bytes(&self.rdata)
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        encoded_name_len(&self.name) + 10 + self.rdata.len()
+    }
+
+    pub fn from_bytes(bytes: &[u8], offset: &mut usize) -> Result<Self, String> {
+        let name = read_name(bytes, offset)?;
+        
+        let mut cursor = Cursor::new(bytes.get(*offset..).ok_or("Enregistrement tronqué")?);
+        let atype = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
+        let aclass = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
+        let ttl = cursor.read_u32::<BigEndian>().map_err(|e| e.to_string())?;
+        let rdlength = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
+        *offset += 10;
+        
+        let rdata = bytes
+            .get(*offset..*offset + rdlength as usize)
+            .ok_or("Données de l'enregistrement tronquées")?
+            .to_vec();
+        *offset += rdlength as usize;
+        
+        Ok(DnsAnswer { name, atype, aclass, ttl, rdlength, rdata })
+    }
+}
+
+// Structure complète d'un message DNS
+#[derive(Debug)]
+pub struct DnsMessage {
+    pub header: DnsHeader,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsAnswer>,
+    pub authorities: Vec<DnsAnswer>,
+    pub additionals: Vec<DnsAnswer>,
+}
+
+impl DnsMessage {
+    pub fn new_query(domain: String) -> Self {
+        Self::new_typed_query(domain, record_types::A)
+    }
+
+    pub fn new_typed_query(domain: String, qtype: u16) -> Self {
+        let id = rand::thread_rng().gen::<u16>();
+        DnsMessage {
+            header: DnsHeader::new_query(id),
+            questions: vec![DnsQuestion::with_type(domain, qtype)],
+            answers: vec![],
+            authorities: vec![],
+            additionals: vec![],
+        }
+    }
+
+    pub fn new_response(query_id: u16, questions: Vec<DnsQuestion>, answers: Vec<DnsAnswer>) -> Self {
+        DnsMessage {
+            header: DnsHeader::new_response(query_id, questions.len() as u16, answers.len() as u16),
+            questions,
+            answers,
+            authorities: vec![],
+            additionals: vec![],
+        }
+    }
+
+    pub fn with_authorities(mut self, authorities: Vec<DnsAnswer>) -> Self {
+        self.header.nscount = authorities.len() as u16;
+        self.authorities = authorities;
+        self
+    }
+
+    pub fn with_additionals(mut self, additionals: Vec<DnsAnswer>) -> Self {
+        self.header.arcount = additionals.len() as u16;
+        self.additionals = additionals;
+        self
+    }
+
+    pub fn with_rcode(mut self, rcode: u16) -> Self {
+        self.header.flags = (self.header.flags & !0x000F) | (rcode & 0x000F);
+        self
+    }
+
+    pub fn rcode(&self) -> u16 {
+        self.header.flags & 0x000F
+    }
+
+    // Adresses IPv4 des enregistrements A de la section réponse
+    pub fn addresses(&self) -> Vec<Ipv4Addr> {
+        self.answers
+            .iter()
+            .filter(|rr| rr.atype == record_types::A && rr.rdata.len() == 4)
+            .map(|rr| Ipv4Addr::new(rr.rdata[0], rr.rdata[1], rr.rdata[2], rr.rdata[3]))
+            .collect()
+    }
+
+    // Enregistrement OPT (EDNS0) présent dans la section additionnelle
+    pub fn edns(&self) -> Option<&DnsAnswer> {
+        self.additionals.iter().find(|rr| rr.atype == record_types::OPT)
+    }
+
+    // Vrai si la requête demande les enregistrements DNSSEC (bit DO)
+    pub fn dnssec_ok(&self) -> bool {
+        self.edns().is_some_and(|opt| opt.ttl & 0x8000 != 0)
+    }
+
+    // Réponse tronquée (bit TC) à renvoyer quand la réponse complète dépasse la taille
+    // UDP du client: en-tête, questions et enregistrement OPT uniquement
+    pub fn truncated(&self) -> DnsMessage {
+        let mut header = self.header.clone();
+        header.flags |= 0x0200; // TC=1
+        let additionals: Vec<DnsAnswer> = self.edns().cloned().into_iter().collect();
+        header.ancount = 0;
+        header.nscount = 0;
+        header.arcount = additionals.len() as u16;
+        
+        DnsMessage {
+            header,
+            questions: self.questions.clone(),
+            answers: vec![],
+            authorities: vec![],
+            additionals,
+        }
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.header.flags & 0x0200 != 0
+    }
+
+    /// Encode le message dans `buf` sans allocation et retourne le nombre d'octets écrits
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, Truncated> {
+        let mut encoder = DnsEncoder::new(buf);
+        
+        self.header.encode(&mut encoder)?;
+        
+        for question in &self.questions {
+            question.encode(&mut encoder)?;
+        }
+        
+        for record in self.answers.iter().chain(&self.authorities).chain(&self.additionals) {
+            record.encode(&mut encoder)?;
+        }
+        
+        Ok(encoder.position())
+    }
+
+    /// Taille exacte du message encodé
+    pub fn encoded_len(&self) -> usize {
+        12 + self.questions.iter().map(DnsQuestion::encoded_len).sum::<usize>()
+            + self.answers.iter().chain(&self.authorities).chain(&self.additionals)
+                .map(DnsAnswer::encoded_len)
+                .sum::<usize>()
+    }
+
+    /// Encode le message dans un tampon alloué à sa taille exacte; `Truncated` si le
+    /// message dépasse la taille maximale d'un message DNS
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Truncated> {
+        let encoded_len = self.encoded_len();
+        if encoded_len > MAX_MESSAGE_SIZE {
+            return Err(Truncated);
+        }
+
+        let mut bytes = vec![0; encoded_len];
+        let len = self.encode_into(&mut bytes)?;
+        bytes.truncate(len);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let header = DnsHeader::from_bytes(bytes.get(0..12).ok_or("Message DNS trop court")?)?;
+        let mut offset = 12;
+        
+        let mut questions = Vec::new();
+        for _ in 0..header.qdcount {
+            let question = DnsQuestion::from_bytes(bytes, &mut offset)?;
+            questions.push(question);
+        }
+        
+        let mut parse_section = |count: u16| -> Result<Vec<DnsAnswer>, String> {
+            (0..count).map(|_| DnsAnswer::from_bytes(bytes, &mut offset)).collect()
+        };
+        let answers = parse_section(header.ancount)?;
+        let authorities = parse_section(header.nscount)?;
+        let additionals = parse_section(header.arcount)?;
+        
+        Ok(DnsMessage {
+            header,
+            questions,
+            answers,
+            authorities,
+            additionals,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_response() -> DnsMessage {
+        let question = DnsQuestion::new("test.local".to_string());
+        let answers = vec![DnsAnswer::new_a_record("test.local".to_string(), Ipv4Addr::new(127, 0, 0, 1), 300)];
+        DnsMessage::new_response(0x1234, vec![question], answers)
+            .with_additionals(vec![DnsAnswer::new_opt(1232, true)])
+    }
+
+    #[test]
+    fn test_encode_into_matches_to_bytes() {
+        let response = sample_response();
+        let mut buf = [0u8; 512];
+
+        let len = response.encode_into(&mut buf).unwrap();
+        assert_eq!(len, response.encoded_len());
+        assert_eq!(&buf[..len], response.to_bytes().unwrap().as_slice());
+
+        let decoded = DnsMessage::from_bytes(&buf[..len]).unwrap();
+        assert_eq!(decoded.addresses(), vec![Ipv4Addr::new(127, 0, 0, 1)]);
+        assert!(decoded.dnssec_ok());
+    }
+
+    #[test]
+    fn test_encode_into_signals_truncation() {
+        let response = sample_response();
+        let mut buf = vec![0u8; response.encoded_len() - 1];
+        assert_eq!(response.encode_into(&mut buf), Err(Truncated));
+
+        let truncated = response.truncated();
+        let len = truncated.encode_into(&mut buf).unwrap();
+        let decoded = DnsMessage::from_bytes(&buf[..len]).unwrap();
+        assert!(decoded.is_truncated());
+        assert!(decoded.answers.is_empty());
+        assert_eq!(decoded.questions[0].name, "test.local");
+    }
+
+    #[test]
+    fn test_to_bytes_rejects_oversized_message() {
+        let question = DnsQuestion::new("test.local".to_string());
+        let answers = vec![DnsAnswer::new("test.local".to_string(), record_types::TXT, 300, vec![0; MAX_MESSAGE_SIZE])];
+        let response = DnsMessage::new_response(0x1234, vec![question], answers);
+        assert_eq!(response.to_bytes(), Err(Truncated));
+    }
+}
@@ -0,0 +1,107 @@
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use rand::Rng;
+
+// Client de test autonome: interroge le serveur DNS (127.0.0.1:8053 par défaut, ou
+// l'adresse passée en argument) pour les domaines prédéfinis et affiche les réponses
+
+const DOMAINS: [&str; 6] = ["example.com", "test.local", "server.local", "dns.local", "67gigs.com", "inconnu.local"];
+
+// Requête A/IN avec récursion demandée (RFC 1035 §4.1)
+fn build_query(id: u16, domain: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&id.to_be_bytes());
+    bytes.extend_from_slice(&0x0100u16.to_be_bytes()); // RD = 1
+    bytes.extend_from_slice(&1u16.to_be_bytes()); // Une question
+    bytes.extend_from_slice(&[0; 6]);
+
+    for label in domain.split('.').filter(|label| !label.is_empty()) {
+        bytes.push(label.len() as u8);
+        bytes.extend_from_slice(label.as_bytes());
+    }
+    bytes.push(0);
+    bytes.extend_from_slice(&1u16.to_be_bytes()); // Type A
+    bytes.extend_from_slice(&1u16.to_be_bytes()); // Classe IN
+    bytes
+}
+
+// Avance après un nom de domaine, compressé ou non
+fn skip_name(bytes: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *bytes.get(offset)? as usize;
+        if len & 0xC0 == 0xC0 {
+            return Some(offset + 2);
+        }
+        offset += 1;
+        if len == 0 {
+            return Some(offset);
+        }
+        offset += len;
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]))
+}
+
+// Adresses IPv4 de la section réponse
+fn parse_addresses(id: u16, bytes: &[u8]) -> Result<Vec<Ipv4Addr>, String> {
+    if read_u16(bytes, 0) != Some(id) {
+        return Err("Identifiant de réponse inattendu".to_string());
+    }
+    let qdcount = read_u16(bytes, 4).ok_or("Réponse trop courte")?;
+    let ancount = read_u16(bytes, 6).ok_or("Réponse trop courte")?;
+
+    let mut offset = 12;
+    for _ in 0..qdcount {
+        offset = skip_name(bytes, offset).ok_or("Question tronquée")? + 4;
+    }
+
+    let mut addresses = Vec::new();
+    for _ in 0..ancount {
+        offset = skip_name(bytes, offset).ok_or("Réponse tronquée")?;
+        let atype = read_u16(bytes, offset).ok_or("Réponse tronquée")?;
+        let rdlength = read_u16(bytes, offset + 8).ok_or("Réponse tronquée")? as usize;
+        let rdata = bytes.get(offset + 10..offset + 10 + rdlength).ok_or("Réponse tronquée")?;
+        if atype == 1 && rdlength == 4 {
+            addresses.push(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]));
+        }
+        offset += 10 + rdlength;
+    }
+    Ok(addresses)
+}
+
+async fn resolve(socket: &UdpSocket, domain: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let id = rand::thread_rng().gen();
+    socket.send(&build_query(id, domain)).await.map_err(|e| e.to_string())?;
+
+    let mut buf = [0u8; 4096];
+    let size = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+        .await
+        .map_err(|_| "Pas de réponse du serveur DNS".to_string())?
+        .map_err(|e| e.to_string())?;
+    parse_addresses(id, &buf[..size])
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let server = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:8053".to_string());
+    println!("🔍 Client de test DNS, serveur {}", server);
+
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+    socket.connect(&server).await.map_err(|e| e.to_string())?;
+
+    for domain in DOMAINS {
+        match resolve(&socket, domain).await {
+            Ok(addresses) if !addresses.is_empty() => {
+                for ip in addresses {
+                    println!("✅ {} résolu en {}", domain, ip);
+                }
+            }
+            Ok(_) => println!("❌ {} non résolu", domain),
+            Err(e) => println!("❌ Erreur pour {}: {}", domain, e),
+        }
+    }
+    Ok(())
+}
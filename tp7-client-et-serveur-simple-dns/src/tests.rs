@@ -0,0 +1,282 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+use crate::doh::MAX_LINE_LEN;
+use crate::dnssec::{ds_rdata, Algorithm, SigningKey, SigningMode};
+use crate::validator::{TrustAnchor, ValidationStatus};
+use crate::message::{classes, rcodes, record_types, DnsAnswer, DnsMessage, DnsQuestion};
+use crate::{DnsClient, DnsServer, MultiQuestionPolicy, MAX_UDP_PAYLOAD_SIZE};
+
+// Tests d'intégration: serveur DNS signé sur un port éphémère, interrogé
+// par le client validant et par HTTP (RFC 8484)
+
+async fn start_signed_server(algorithm: Algorithm, mode: SigningMode) -> (SocketAddr, TrustAnchor) {
+    let server = DnsServer::new().with_dnssec("local", SigningKey::generate(algorithm), mode);
+    let zone = server.dnssec.as_ref().unwrap();
+    let ds = zone.ds_record();
+    let trust_anchor = TrustAnchor::from_ds(&ds.name, &ds.rdata);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move { server.serve(socket).await });
+
+    (addr, trust_anchor)
+}
+
+#[tokio::test]
+async fn test_validated_answer_is_secure() {
+    let (addr, anchor) = start_signed_server(Algorithm::Ed25519, SigningMode::Offline).await;
+
+    let result = DnsClient::resolve_validated("test.local", &addr.to_string(), &anchor).await.unwrap();
+    assert_eq!(result.status, ValidationStatus::Secure);
+    assert_eq!(result.addresses, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+}
+
+#[tokio::test]
+async fn test_on_the_fly_ecdsa_signing_is_secure() {
+    let (addr, anchor) = start_signed_server(Algorithm::EcdsaP256Sha256, SigningMode::OnTheFly).await;
+
+    let result = DnsClient::resolve_validated("server.local", &addr.to_string(), &anchor).await.unwrap();
+    assert_eq!(result.status, ValidationStatus::Secure);
+    assert_eq!(result.addresses, vec![Ipv4Addr::new(192, 168, 1, 100)]);
+}
+
+#[tokio::test]
+async fn test_mixed_case_query_is_answered() {
+    let (addr, anchor) = start_signed_server(Algorithm::Ed25519, SigningMode::Offline).await;
+
+    let result = DnsClient::resolve_validated("TeSt.LoCaL", &addr.to_string(), &anchor).await.unwrap();
+    assert_eq!(result.status, ValidationStatus::Secure);
+    assert_eq!(result.addresses, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+
+    let server = DnsServer::new();
+    let query = DnsMessage::new_query("ExAmPlE.CoM".to_string());
+    let response = DnsMessage::from_bytes(&server.handle_query(&query.to_bytes().unwrap()).await.unwrap()).unwrap();
+    assert_eq!(response.rcode(), rcodes::NOERROR);
+    assert_eq!(response.addresses(), vec![Ipv4Addr::new(93, 184, 216, 34)]);
+
+    let query = DnsMessage::new_query("WwW.Example.COM".to_string());
+    let response = DnsMessage::from_bytes(&server.handle_query(&query.to_bytes().unwrap()).await.unwrap()).unwrap();
+    assert_eq!(response.rcode(), rcodes::NXDOMAIN);
+}
+
+#[tokio::test]
+async fn test_nxdomain_proof_is_secure() {
+    let (addr, anchor) = start_signed_server(Algorithm::Ed25519, SigningMode::Offline).await;
+
+    let result = DnsClient::resolve_validated("nonexistent.local", &addr.to_string(), &anchor).await.unwrap();
+    assert_eq!(result.status, ValidationStatus::Secure);
+    assert!(result.addresses.is_empty());
+}
+
+#[tokio::test]
+async fn test_name_outside_anchor_is_insecure() {
+    let (addr, anchor) = start_signed_server(Algorithm::Ed25519, SigningMode::Offline).await;
+
+    let result = DnsClient::resolve_validated("example.com", &addr.to_string(), &anchor).await.unwrap();
+    assert_eq!(result.status, ValidationStatus::Insecure);
+    assert_eq!(result.addresses, vec![Ipv4Addr::new(93, 184, 216, 34)]);
+}
+
+#[tokio::test]
+async fn test_wrong_trust_anchor_is_bogus() {
+    let (addr, _) = start_signed_server(Algorithm::Ed25519, SigningMode::Offline).await;
+    let other_key = SigningKey::generate(Algorithm::Ed25519);
+    let mut dnskey = vec![0x01, 0x01, 3, Algorithm::Ed25519.number()];
+    dnskey.extend_from_slice(&other_key.public_key());
+    let wrong_anchor = TrustAnchor::from_ds("local", &ds_rdata("local", &dnskey));
+
+    let result = DnsClient::resolve_validated("test.local", &addr.to_string(), &wrong_anchor).await.unwrap();
+    assert!(matches!(result.status, ValidationStatus::Bogus(_)));
+    assert!(result.addresses.is_empty());
+}
+
+async fn start_doh_server() -> SocketAddr {
+    let server = Arc::new(DnsServer::new());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server.serve_doh(listener));
+    addr
+}
+
+// Envoie une requête HTTP brute et retourne (code de statut, en-têtes, corps)
+async fn http_request(addr: SocketAddr, head: &str, body: &[u8]) -> (u16, String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(body).await.unwrap();
+
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).await.unwrap();
+
+    let split = raw.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let headers = String::from_utf8(raw[..split].to_vec()).unwrap();
+    let status = headers.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, headers, raw[split + 4..].to_vec())
+}
+
+#[tokio::test]
+async fn test_doh_get_request() {
+    let addr = start_doh_server().await;
+    let query = DnsMessage::new_query("test.local".to_string()).to_bytes().unwrap();
+
+    let head = format!(
+        "GET /dns-query?dns={} HTTP/1.1\r\nHost: localhost\r\nAccept: application/dns-message\r\nConnection: close\r\n\r\n",
+        URL_SAFE_NO_PAD.encode(&query)
+    );
+    let (status, headers, body) = http_request(addr, &head, &[]).await;
+
+    assert_eq!(status, 200);
+    assert!(headers.contains("Content-Type: application/dns-message"));
+    assert!(headers.contains("Cache-Control: max-age=300"));
+    let response = DnsMessage::from_bytes(&body).unwrap();
+    assert_eq!(response.addresses(), vec![Ipv4Addr::new(127, 0, 0, 1)]);
+}
+
+#[tokio::test]
+async fn test_doh_post_request() {
+    let addr = start_doh_server().await;
+    let query = DnsMessage::new_query("67gigs.com".to_string()).to_bytes().unwrap();
+
+    let head = format!(
+        "POST /dns-query HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        query.len()
+    );
+    let (status, _, body) = http_request(addr, &head, &query).await;
+
+    assert_eq!(status, 200);
+    let response = DnsMessage::from_bytes(&body).unwrap();
+    assert_eq!(response.addresses(), vec![Ipv4Addr::new(67, 67, 67, 67)]);
+}
+
+#[tokio::test]
+async fn test_doh_rejects_invalid_requests() {
+    let addr = start_doh_server().await;
+
+    let (status, _, _) = http_request(addr, "GET /other HTTP/1.1\r\nConnection: close\r\n\r\n", &[]).await;
+    assert_eq!(status, 404);
+
+    let (status, _, _) = http_request(addr, "GET /dns-query HTTP/1.1\r\nConnection: close\r\n\r\n", &[]).await;
+    assert_eq!(status, 400);
+
+    let head = format!("GET /dns-query?dns={} HTTP/1.1\r\nConnection: close\r\n\r\n", URL_SAFE_NO_PAD.encode([0u8; 4]));
+    let (status, _, _) = http_request(addr, &head, &[]).await;
+    assert_eq!(status, 400);
+
+    let head = "POST /dns-query HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: close\r\n\r\n";
+    let (status, _, _) = http_request(addr, head, b"hi").await;
+    assert_eq!(status, 415);
+
+    let (status, headers, _) = http_request(addr, "PUT /dns-query HTTP/1.1\r\nConnection: close\r\n\r\n", &[]).await;
+    assert_eq!(status, 405);
+    assert!(headers.contains("Allow: GET, POST"));
+}
+
+#[tokio::test]
+async fn test_doh_post_accepts_content_type_parameters() {
+    let addr = start_doh_server().await;
+    let query = DnsMessage::new_query("test.local".to_string()).to_bytes().unwrap();
+
+    let head = format!(
+        "POST /dns-query HTTP/1.1\r\nContent-Type: application/dns-message; charset=binary\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        query.len()
+    );
+    let (status, _, _) = http_request(addr, &head, &query).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn test_doh_rejects_oversized_lines() {
+    let addr = start_doh_server().await;
+
+    // Le serveur s'arrête après MAX_LINE_LEN + 1 octets sans attendre la fin de ligne
+    let header = format!("X-Long: {}", "a".repeat(MAX_LINE_LEN + 1 - "X-Long: ".len()));
+    let head = format!("GET /dns-query HTTP/1.1\r\n{}", header);
+    let (status, _, _) = http_request(addr, &head, &[]).await;
+    assert_eq!(status, 431);
+
+    let head = format!("GET /{}", "a".repeat(MAX_LINE_LEN + 1 - "GET /".len()));
+    let (status, _, _) = http_request(addr, &head, &[]).await;
+    assert_eq!(status, 400);
+}
+
+// Requêtes à plusieurs questions, ANY et CHAOS
+
+fn two_question_query() -> DnsMessage {
+    let mut query = DnsMessage::new_query("test.local".to_string());
+    query.questions.push(DnsQuestion::new("67gigs.com".to_string()));
+    query.header.qdcount = 2;
+    query
+}
+
+#[tokio::test]
+async fn test_multi_question_rejected_with_formerr() {
+    let server = DnsServer::new();
+    let bytes = server.handle_query(&two_question_query().to_bytes().unwrap()).await.unwrap();
+    let response = DnsMessage::from_bytes(&bytes).unwrap();
+
+    assert_eq!(response.rcode(), rcodes::FORMERR);
+    assert_eq!(response.header.qdcount, 2);
+    assert!(response.answers.is_empty());
+}
+
+#[tokio::test]
+async fn test_multi_question_answered_per_question() {
+    let server = DnsServer::new().with_multi_question_policy(MultiQuestionPolicy::AnswerEach);
+    let bytes = server.handle_query(&two_question_query().to_bytes().unwrap()).await.unwrap();
+    let response = DnsMessage::from_bytes(&bytes).unwrap();
+
+    assert_eq!(response.rcode(), rcodes::NOERROR);
+    assert_eq!(response.questions.len(), 2);
+    assert_eq!(response.addresses(), vec![Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(67, 67, 67, 67)]);
+}
+
+#[tokio::test]
+async fn test_any_query_returns_single_rrset() {
+    let server = DnsServer::new().with_dnssec("local", SigningKey::generate(Algorithm::Ed25519), SigningMode::Offline);
+
+    let query = DnsMessage::new_typed_query("test.local".to_string(), record_types::ANY);
+    let response = DnsMessage::from_bytes(&server.handle_query(&query.to_bytes().unwrap()).await.unwrap()).unwrap();
+    assert_eq!(response.answers.len(), 1);
+    assert_eq!(response.answers[0].atype, record_types::A);
+
+    // L'apex n'a pas d'enregistrement A: HINFO synthétisé (RFC 8482 §4.2)
+    let query = DnsMessage::new_typed_query("local".to_string(), record_types::ANY);
+    let response = DnsMessage::from_bytes(&server.handle_query(&query.to_bytes().unwrap()).await.unwrap()).unwrap();
+    assert_eq!(response.answers.len(), 1);
+    assert_eq!(response.answers[0].atype, record_types::HINFO);
+    assert_eq!(response.answers[0].rdata, b"\x07RFC8482\x00");
+}
+
+#[tokio::test]
+async fn test_chaos_version_and_hostname() {
+    let server = DnsServer::new();
+
+    for name in ["version.bind", "hostname.bind"] {
+        let mut query = DnsMessage::new_typed_query(name.to_string(), record_types::TXT);
+        query.questions[0].qclass = classes::CH;
+        let response = DnsMessage::from_bytes(&server.handle_query(&query.to_bytes().unwrap()).await.unwrap()).unwrap();
+
+        assert_eq!(response.rcode(), rcodes::NOERROR);
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].aclass, classes::CH);
+        assert_eq!(response.answers[0].atype, record_types::TXT);
+    }
+
+    let mut query = DnsMessage::new_typed_query("authors.bind".to_string(), record_types::TXT);
+    query.questions[0].qclass = classes::CH;
+    let response = DnsMessage::from_bytes(&server.handle_query(&query.to_bytes().unwrap()).await.unwrap()).unwrap();
+    assert_eq!(response.rcode(), rcodes::NXDOMAIN);
+}
+
+#[tokio::test]
+async fn test_opt_advertises_supported_udp_size() {
+    let server = DnsServer::new();
+
+    let query = DnsMessage::new_query("test.local".to_string()).with_additionals(vec![DnsAnswer::new_opt(1232, false)]);
+    let response = DnsMessage::from_bytes(&server.handle_query(&query.to_bytes().unwrap()).await.unwrap()).unwrap();
+    assert_eq!(response.edns().unwrap().aclass as usize, MAX_UDP_PAYLOAD_SIZE);
+}
@@ -0,0 +1,428 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::dnssec::{
+    canonical_cmp, ds_rdata, is_in_zone, key_tag, label_count, normalize, parse_type_bitmap, signed_data,
+    unix_now, Algorithm,
+};
+use crate::message::{rcodes, read_name, record_types, DnsAnswer, DnsMessage};
+use crate::DnsClient;
+
+/// Taille UDP annoncée dans l'enregistrement OPT des requêtes validantes
+const EDNS_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// Statut DNSSEC d'une réponse (RFC 4035 §4.3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationStatus {
+    /// Chaîne de confiance vérifiée depuis l'ancre jusqu'à la réponse
+    Secure,
+    /// Aucune ancre ne couvre le nom, ou une délégation non signée a été prouvée
+    Insecure,
+    /// La réponse aurait dû être signée mais la vérification a échoué
+    Bogus(String),
+}
+
+impl fmt::Display for ValidationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationStatus::Secure => write!(f, "secure"),
+            ValidationStatus::Insecure => write!(f, "insecure"),
+            ValidationStatus::Bogus(reason) => write!(f, "bogus ({})", reason),
+        }
+    }
+}
+
+/// Résultat d'une résolution validante
+#[derive(Debug, Clone)]
+pub struct LookupResult {
+    pub addresses: Vec<Ipv4Addr>,
+    pub status: ValidationStatus,
+}
+
+impl LookupResult {
+    pub fn is_secure(&self) -> bool {
+        self.status == ValidationStatus::Secure
+    }
+}
+
+/// Ancre de confiance: RDATA d'un DS (SHA-256) pour l'apex d'une zone
+#[derive(Debug, Clone)]
+pub struct TrustAnchor {
+    zone: String,
+    ds_rdata: Vec<u8>,
+}
+
+impl TrustAnchor {
+    /// Ancre définie par la RDATA d'un enregistrement DS publié par la zone parente
+    pub fn from_ds(zone: &str, ds_rdata: &[u8]) -> Self {
+        TrustAnchor { zone: normalize(zone), ds_rdata: ds_rdata.to_vec() }
+    }
+
+    pub fn zone(&self) -> &str {
+        &self.zone
+    }
+}
+
+// Échec de validation: problème réseau (remonté en erreur) ou réponse bogus
+enum ValidationError {
+    Transport(String),
+    Bogus(String),
+}
+
+impl From<String> for ValidationError {
+    fn from(error: String) -> Self {
+        ValidationError::Transport(error)
+    }
+}
+
+fn bogus<T>(reason: String) -> Result<T, ValidationError> {
+    Err(ValidationError::Bogus(reason))
+}
+
+/// Résout `domain` (type A) et valide la réponse depuis l'ancre de confiance
+pub async fn validated_lookup(
+    domain: &str,
+    server_addr: SocketAddr,
+    trust_anchor: &TrustAnchor,
+) -> Result<LookupResult, String> {
+    let domain = normalize(domain);
+
+    if !is_in_zone(&domain, &trust_anchor.zone) {
+        let response = query(server_addr, &domain, record_types::A).await?;
+        return Ok(LookupResult { addresses: response.addresses(), status: ValidationStatus::Insecure });
+    }
+
+    match validate(&domain, server_addr, trust_anchor).await {
+        Ok(result) => Ok(result),
+        Err(ValidationError::Transport(e)) => Err(e),
+        Err(ValidationError::Bogus(reason)) => {
+            Ok(LookupResult { addresses: vec![], status: ValidationStatus::Bogus(reason) })
+        }
+    }
+}
+
+async fn validate(
+    domain: &str,
+    server_addr: SocketAddr,
+    trust_anchor: &TrustAnchor,
+) -> Result<LookupResult, ValidationError> {
+    let mut zone = trust_anchor.zone.clone();
+    let mut keys = fetch_dnskeys(server_addr, &zone, std::slice::from_ref(&trust_anchor.ds_rdata)).await?;
+
+    // Descente depuis l'ancre: chaque nom intermédiaire peut être une délégation signée (DS)
+    for child in names_below(&zone, domain) {
+        let response = query(server_addr, &child, record_types::DS).await?;
+        let ds_set = rrset(&response.answers, &child, record_types::DS);
+
+        if !ds_set.is_empty() {
+            verify_rrset(&ds_set, &response.answers, &zone, &keys)?;
+            let ds_rdatas: Vec<Vec<u8>> = ds_set.iter().map(|ds| ds.rdata.clone()).collect();
+            keys = fetch_dnskeys(server_addr, &child, &ds_rdatas).await?;
+            zone = child;
+            continue;
+        }
+
+        if response.rcode() == rcodes::NXDOMAIN {
+            // Le nom n'existe pas: la preuve est vérifiée avec la réponse finale
+            break;
+        }
+
+        let Some(nsec) = verified_nsecs(&response, &zone, &keys)?.into_iter().find(|nsec| nsec.owner == child) else {
+            return bogus(format!("Absence de DS non prouvée pour {}", child));
+        };
+        if nsec.types.contains(&record_types::NS) {
+            // Délégation prouvée sans DS: la zone fille n'est pas signée
+            let response = query(server_addr, domain, record_types::A).await?;
+            return Ok(LookupResult { addresses: response.addresses(), status: ValidationStatus::Insecure });
+        }
+    }
+
+    let response = query(server_addr, domain, record_types::A).await?;
+    let answers = rrset(&response.answers, domain, record_types::A);
+
+    if !answers.is_empty() {
+        verify_rrset(&answers, &response.answers, &zone, &keys)?;
+        return Ok(LookupResult { addresses: response.addresses(), status: ValidationStatus::Secure });
+    }
+
+    verify_denial(domain, record_types::A, &response, &zone, &keys)?;
+    Ok(LookupResult { addresses: vec![], status: ValidationStatus::Secure })
+}
+
+// Requête avec le bit DO positionné
+async fn query(server_addr: SocketAddr, name: &str, qtype: u16) -> Result<DnsMessage, String> {
+    let query = DnsMessage::new_typed_query(name.to_string(), qtype)
+        .with_additionals(vec![DnsAnswer::new_opt(EDNS_UDP_PAYLOAD_SIZE, true)]);
+    DnsClient::exchange(server_addr, &query).await
+}
+
+// Récupère le DNSKEY RRset de `zone`, l'authentifie avec l'un des DS fournis et
+// vérifie qu'il est signé par la clé correspondante
+async fn fetch_dnskeys(
+    server_addr: SocketAddr,
+    zone: &str,
+    ds_rdatas: &[Vec<u8>],
+) -> Result<Vec<Vec<u8>>, ValidationError> {
+    let response = query(server_addr, zone, record_types::DNSKEY).await?;
+    let dnskeys = rrset(&response.answers, zone, record_types::DNSKEY);
+    if dnskeys.is_empty() {
+        return bogus(format!("Aucun DNSKEY pour {}", zone));
+    }
+
+    let trusted: Vec<Vec<u8>> = dnskeys
+        .iter()
+        .filter(|dnskey| ds_rdatas.contains(&ds_rdata(zone, &dnskey.rdata)))
+        .map(|dnskey| dnskey.rdata.clone())
+        .collect();
+    if trusted.is_empty() {
+        return bogus(format!("Aucun DNSKEY de {} ne correspond au DS", zone));
+    }
+
+    verify_rrset(&dnskeys, &response.answers, zone, &trusted)?;
+    Ok(dnskeys.into_iter().map(|dnskey| dnskey.rdata).collect())
+}
+
+// Enregistrements de `section` ayant le nom et le type demandés
+fn rrset(section: &[DnsAnswer], name: &str, rtype: u16) -> Vec<DnsAnswer> {
+    section
+        .iter()
+        .filter(|rr| rr.atype == rtype && normalize(&rr.name) == name)
+        .cloned()
+        .collect()
+}
+
+// Noms situés entre `zone` (exclu) et `domain` (inclus), du plus haut au plus bas
+fn names_below(zone: &str, domain: &str) -> Vec<String> {
+    let extra_labels = label_count(domain).saturating_sub(label_count(zone)) as usize;
+    let labels: Vec<&str> = domain.split('.').collect();
+    (0..extra_labels).rev().map(|skip| labels[skip..].join(".")).collect()
+}
+
+/// Champs d'un RRSIG (RFC 4034 §3.1)
+struct Rrsig<'a> {
+    type_covered: u16,
+    algorithm: u8,
+    labels: u8,
+    original_ttl: u32,
+    expiration: u32,
+    inception: u32,
+    key_tag: u16,
+    signer: String,
+    /// RDATA sans la signature, première partie des données signées
+    signed_fields: &'a [u8],
+    signature: &'a [u8],
+}
+
+impl<'a> Rrsig<'a> {
+    fn parse(rdata: &'a [u8]) -> Result<Self, String> {
+        let mut cursor = rdata;
+        let type_covered = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
+        let algorithm = cursor.read_u8().map_err(|e| e.to_string())?;
+        let labels = cursor.read_u8().map_err(|e| e.to_string())?;
+        let original_ttl = cursor.read_u32::<BigEndian>().map_err(|e| e.to_string())?;
+        let expiration = cursor.read_u32::<BigEndian>().map_err(|e| e.to_string())?;
+        let inception = cursor.read_u32::<BigEndian>().map_err(|e| e.to_string())?;
+        let key_tag = cursor.read_u16::<BigEndian>().map_err(|e| e.to_string())?;
+
+        let mut offset = 18;
+        let signer = normalize(&read_name(rdata, &mut offset)?);
+
+        Ok(Rrsig {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer,
+            signed_fields: &rdata[..offset],
+            signature: &rdata[offset..],
+        })
+    }
+}
+
+// Vérifie qu'au moins un RRSIG de `section` couvrant `rrset` est valide, signé par
+// `zone` avec l'une des clés `keys` (RFC 4035 §5.3)
+fn verify_rrset(
+    rrset: &[DnsAnswer],
+    section: &[DnsAnswer],
+    zone: &str,
+    keys: &[Vec<u8>],
+) -> Result<(), ValidationError> {
+    let owner = normalize(&rrset[0].name);
+    let rtype = rrset[0].atype;
+    let now = unix_now();
+    let mut last_error = format!("Aucun RRSIG pour {} (type {})", owner, rtype);
+
+    for rrsig_record in rrset_rrsigs(section, &owner) {
+        let rrsig = match Rrsig::parse(&rrsig_record.rdata) {
+            Ok(rrsig) => rrsig,
+            Err(e) => {
+                last_error = e;
+                continue;
+            }
+        };
+        if rrsig.type_covered != rtype {
+            continue;
+        }
+        if rrsig.signer != zone {
+            last_error = format!("RRSIG de {} signé par {} au lieu de {}", owner, rrsig.signer, zone);
+            continue;
+        }
+        if now < rrsig.inception || now > rrsig.expiration {
+            last_error = format!("RRSIG de {} hors de sa période de validité", owner);
+            continue;
+        }
+        let Some(algorithm) = Algorithm::from_number(rrsig.algorithm) else {
+            last_error = format!("Algorithme {} non supporté", rrsig.algorithm);
+            continue;
+        };
+
+        // Un nom synthétisé par un wildcard porte moins de labels que le propriétaire
+        let owner_labels = label_count(&owner);
+        if rrsig.labels > owner_labels {
+            last_error = format!("Champ labels invalide pour {}", owner);
+            continue;
+        }
+        let signed_owner = if rrsig.labels < owner_labels {
+            let labels: Vec<&str> = owner.split('.').collect();
+            format!("*.{}", labels[(owner_labels - rrsig.labels) as usize..].join("."))
+        } else {
+            owner.clone()
+        };
+
+        let data = signed_data(rrsig.signed_fields, &signed_owner, rrsig.original_ttl, rrset);
+        let verified = keys.iter().any(|dnskey| {
+            dnskey.len() > 4
+                && dnskey[3] == rrsig.algorithm
+                && key_tag(dnskey) == rrsig.key_tag
+                && algorithm.verify(&dnskey[4..], &data, rrsig.signature)
+        });
+        if verified {
+            return Ok(());
+        }
+        last_error = format!("Signature invalide pour {} (type {})", owner, rtype);
+    }
+
+    bogus(last_error)
+}
+
+fn rrset_rrsigs<'a>(section: &'a [DnsAnswer], owner: &'a str) -> impl Iterator<Item = &'a DnsAnswer> {
+    section
+        .iter()
+        .filter(move |rr| rr.atype == record_types::RRSIG && normalize(&rr.name) == owner)
+}
+
+/// Enregistrement NSEC décodé
+struct Nsec {
+    owner: String,
+    next: String,
+    types: Vec<u16>,
+}
+
+impl Nsec {
+    // Vrai si `name` se situe strictement entre le propriétaire et le nom suivant
+    // (le dernier NSEC de la chaîne reboucle sur l'apex)
+    fn covers(&self, name: &str) -> bool {
+        canonical_cmp(&self.owner, name) == Ordering::Less
+            && (canonical_cmp(name, &self.next) == Ordering::Less
+                || canonical_cmp(&self.next, &self.owner) != Ordering::Greater)
+    }
+}
+
+// NSEC de la section d'autorité dont la signature a été vérifiée
+fn verified_nsecs(response: &DnsMessage, zone: &str, keys: &[Vec<u8>]) -> Result<Vec<Nsec>, ValidationError> {
+    let mut nsecs = Vec::new();
+    for record in response.authorities.iter().filter(|rr| rr.atype == record_types::NSEC) {
+        verify_rrset(std::slice::from_ref(record), &response.authorities, zone, keys)?;
+
+        let mut offset = 0;
+        let next = normalize(&read_name(&record.rdata, &mut offset)?);
+        nsecs.push(Nsec {
+            owner: normalize(&record.name),
+            next,
+            types: parse_type_bitmap(&record.rdata[offset..]),
+        });
+    }
+    Ok(nsecs)
+}
+
+// Vérifie la preuve NSEC d'une réponse sans enregistrement (RFC 4035 §5.4)
+fn verify_denial(
+    domain: &str,
+    qtype: u16,
+    response: &DnsMessage,
+    zone: &str,
+    keys: &[Vec<u8>],
+) -> Result<(), ValidationError> {
+    let nsecs = verified_nsecs(response, zone, keys)?;
+
+    if response.rcode() == rcodes::NXDOMAIN {
+        let Some(covering) = nsecs.iter().find(|nsec| nsec.covers(domain)) else {
+            return bogus(format!("Aucun NSEC ne prouve la non-existence de {}", domain));
+        };
+
+        // Le plus proche ancêtre existant partage le plus de labels avec le NSEC couvrant
+        let encloser = [&covering.owner, &covering.next]
+            .into_iter()
+            .map(|name| common_ancestor(domain, name))
+            .max_by_key(|ancestor| label_count(ancestor))
+            .unwrap_or_default();
+        let wildcard = format!("*.{}", encloser);
+        if !nsecs.iter().any(|nsec| nsec.covers(&wildcard)) {
+            return bogus(format!("Aucun NSEC ne prouve l'absence de {}", wildcard));
+        }
+        return Ok(());
+    }
+
+    match nsecs.iter().find(|nsec| nsec.owner == domain) {
+        Some(nsec) if !nsec.types.contains(&qtype) && !nsec.types.contains(&record_types::CNAME) => Ok(()),
+        Some(_) => bogus(format!("Le NSEC de {} annonce le type {}", domain, qtype)),
+        None => bogus(format!("Aucun NSEC ne prouve l'absence du type {} pour {}", qtype, domain)),
+    }
+}
+
+// Plus long suffixe commun (en labels) de deux noms
+fn common_ancestor(a: &str, b: &str) -> String {
+    let common: Vec<&str> = a
+        .rsplit('.')
+        .zip(b.rsplit('.'))
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x)
+        .collect();
+    common.into_iter().rev().collect::<Vec<_>>().join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_below() {
+        assert_eq!(names_below("local", "a.b.local"), vec!["b.local", "a.b.local"]);
+        assert!(names_below("local", "local").is_empty());
+    }
+
+    #[test]
+    fn test_nsec_covers() {
+        let nsec = Nsec { owner: "dns.local".to_string(), next: "server.local".to_string(), types: vec![] };
+        assert!(nsec.covers("nonexistent.local"));
+        assert!(!nsec.covers("test.local"));
+        assert!(!nsec.covers("dns.local"));
+
+        // Dernier maillon de la chaîne: reboucle sur l'apex
+        let last = Nsec { owner: "test.local".to_string(), next: "local".to_string(), types: vec![] };
+        assert!(last.covers("zzz.local"));
+        assert!(!last.covers("abc.local"));
+    }
+
+    #[test]
+    fn test_common_ancestor() {
+        assert_eq!(common_ancestor("nonexistent.local", "dns.local"), "local");
+        assert_eq!(common_ancestor("a.b.local", "c.b.local"), "b.local");
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+
+/// Comptes utilisateurs (nom -> hash Argon2 au format PHC), éventuellement
+/// sauvegardés dans un fichier JSON
+pub struct AccountStore {
+    path: Option<PathBuf>,
+    accounts: HashMap<String, String>,
+}
+
+impl AccountStore {
+    /// Crée un annuaire en mémoire, perdu à l'arrêt du serveur
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            accounts: HashMap::new(),
+        }
+    }
+
+    /// Charge les comptes depuis `path`; le fichier est créé au premier enregistrement
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let accounts = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            accounts,
+        })
+    }
+
+    /// Indique si un compte existe pour ce nom d'utilisateur
+    pub fn exists(&self, username: &str) -> bool {
+        self.accounts.contains_key(username)
+    }
+
+    /// Hash du mot de passe d'un utilisateur
+    pub fn password_hash(&self, username: &str) -> Option<String> {
+        self.accounts.get(username).cloned()
+    }
+
+    /// Ajoute un compte dont le mot de passe a déjà été hashé, puis sauvegarde l'annuaire
+    pub fn insert(&mut self, username: String, password_hash: String) -> Result<(), String> {
+        if self.exists(&username) {
+            return Err(format!("Account '{}' already exists", username));
+        }
+
+        self.accounts.insert(username.clone(), password_hash);
+        if let Err(e) = self.save() {
+            self.accounts.remove(&username);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // Écrit dans un fichier temporaire puis le renomme, pour ne jamais laisser
+    // un annuaire à moitié écrit
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let json = serde_json::to_string_pretty(&self.accounts).map_err(|e| e.to_string())?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json).map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+        fs::rename(&tmp_path, path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Hash un mot de passe avec Argon2id et un sel aléatoire
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Vérifie un mot de passe contre un hash au format PHC
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+    }
+
+    #[test]
+    fn test_accounts_persisted() {
+        let path = std::env::temp_dir().join(format!("tp8-accounts-{}.json", uuid::Uuid::new_v4()));
+
+        let mut store = AccountStore::load(&path).unwrap();
+        store.insert("Alice".to_string(), hash_password("secret123").unwrap()).unwrap();
+        assert!(store.insert("Alice".to_string(), "autre".to_string()).is_err());
+
+        let reloaded = AccountStore::load(&path).unwrap();
+        let hash = reloaded.password_hash("Alice").unwrap();
+        assert!(verify_password("secret123", &hash));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
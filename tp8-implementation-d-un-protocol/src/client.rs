@@ -1,43 +1,209 @@
+use std::collections::{HashMap, VecDeque};
 use std::io::{self, Write};
-use tokio::net::TcpStream;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use futures::StreamExt;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
-use crate::protocol::{Message, OpCode, MessagePayload};
+use crate::codec::Framing;
+use crate::connection::ClientConnection;
+use crate::protocol::{
+    Message, OpCode, MessagePayload, PresenceStatus, PROTOCOL_VERSION, SERVER_SENDER, TYPING_TIMEOUT, error_codes, features,
+};
+use crate::tls::TlsClientSettings;
+use crate::transfer::Transfers;
 
-/// Client de messagerie
+/// Nombre de messages d'historique affichés à la connexion et à l'entrée dans un salon
+const RECENT_HISTORY_LIMIT: usize = 20;
+
+/// Nombre de caractères d'un message rappelés dans les accusés
+const RECEIPT_PREVIEW_LEN: usize = 30;
+
+/// Nombre de messages affichés retenus pour pouvoir les modifier ou les supprimer
+const RECENT_MESSAGES_LIMIT: usize = 100;
+
+/// Nombre de tentatives de reconnexion avant d'abandonner
+const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+
+/// Délai avant la première tentative de reconnexion, doublé à chaque échec
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Délai maximal entre deux tentatives de reconnexion
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Dossier où sont enregistrés les fichiers reçus, par défaut
+pub const DEFAULT_DOWNLOAD_DIR: &str = "downloads";
+
+/// Fin d'une session avec le serveur
+#[derive(Debug, PartialEq)]
+enum SessionEnd {
+    /// L'utilisateur a quitté
+    Quit,
+    /// La connexion a été perdue: on tente de la rétablir
+    Lost,
+}
+
+/// Délai avant la tentative de reconnexion numéro `attempt` (à partir de 1)
+fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+/// Durée de sanction: `45s`, `30m`, `2h`, `1d` ou un nombre de secondes
+fn parse_duration(input: &str) -> Option<u64> {
+    let (value, unit) = match input.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => input.split_at(index),
+        None => (input, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    value.parse::<u64>().ok()?.checked_mul(multiplier).filter(|seconds| *seconds > 0)
+}
+
+/// Statut de présence choisi avec `/status`
+fn parse_status(input: &str) -> Option<PresenceStatus> {
+    match input {
+        "online" => Some(PresenceStatus::Online),
+        "away" => Some(PresenceStatus::Away),
+        "busy" => Some(PresenceStatus::Busy),
+        _ => None,
+    }
+}
+
+/// Statut de présence tel qu'affiché dans la liste des utilisateurs
+fn describe_presence(status: PresenceStatus, text: Option<&str>) -> String {
+    let status = match status {
+        PresenceStatus::Online => "🟢 en ligne",
+        PresenceStatus::Away => "🌙 absent",
+        PresenceStatus::Busy => "⛔ occupé",
+    };
+    match text {
+        Some(text) => format!("{} ({})", status, text),
+        None => status.to_string(),
+    }
+}
+
+/// Client de messagerie interactif, en ligne de commande, construit sur `ClientConnection`
 pub struct MessageClient {
     username: String,
+    password: String,
+    /// Crée le compte avant de se connecter
+    register: bool,
+    /// Format des trames demandé au serveur pour la suite de la connexion
+    framing: Framing,
+    /// Fonctionnalités retenues par le serveur dans son `HelloAck`
+    features: Vec<String>,
     connected: bool,
+    /// Salon dans lequel sont envoyés les messages (chat général si absent)
+    current_room: Option<String>,
+    /// Messages privés envoyés en attente d'accusé de lecture (identifiant -> aperçu)
+    awaiting_receipts: HashMap<Uuid, String>,
+    /// Messages privés reçus pas encore signalés comme lus (identifiant, expéditeur)
+    unread: Vec<(Uuid, String)>,
+    /// Jeton du dernier `ConnectAck`, présenté à la reconnexion pour reprendre la session
+    resume_token: Option<Uuid>,
+    /// Paramètres TLS; sans eux la connexion est en clair
+    tls: Option<TlsClientSettings>,
+    /// Expulsé par un administrateur: pas de reconnexion automatique
+    kicked: bool,
+    /// Fichiers en cours d'envoi et de réception
+    transfers: Transfers,
+    /// Utilisateurs dont la saisie en cours a été affichée, et depuis quand
+    typing: HashMap<String, Instant>,
+    /// Derniers messages affichés ou envoyés (identifiant, auteur, texte), pour `/edit` et `/delete`
+    recent: VecDeque<(Uuid, String, String)>,
 }
 
 impl MessageClient {
     /// Crée un nouveau client
-    pub fn new(username: String) -> Self {
+    pub fn new(username: String, password: String) -> Self {
         Self {
+            transfers: Transfers::new(username.clone(), PathBuf::from(DEFAULT_DOWNLOAD_DIR)),
             username,
+            password,
+            register: false,
+            framing: Framing::Json,
+            features: Vec::new(),
             connected: false,
+            current_room: None,
+            awaiting_receipts: HashMap::new(),
+            unread: Vec::new(),
+            resume_token: None,
+            tls: None,
+            kicked: false,
+            typing: HashMap::new(),
+            recent: VecDeque::new(),
         }
     }
 
+    /// Crée le compte sur le serveur avant la connexion
+    pub fn with_registration(mut self) -> Self {
+        self.register = true;
+        self
+    }
+
+    /// Demande un format de trames au serveur (appliqué après le `ConnectAck`)
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Enregistre les fichiers reçus dans `dir` (`downloads` par défaut)
+    pub fn with_download_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.transfers = Transfers::new(self.username.clone(), dir.into());
+        self
+    }
+
+    /// Chiffre la connexion avec TLS (voir `tls::client_connector`)
+    pub fn with_tls(mut self, settings: TlsClientSettings) -> Self {
+        self.tls = Some(settings);
+        self
+    }
+
+    /// Message de connexion portant le format de trames souhaité et le jeton de reprise éventuel
+    fn connect_message(&self) -> Message {
+        let msg = Message::connect(self.username.clone(), self.password.clone()).with_framing(self.framing);
+        match self.resume_token {
+            Some(token) if self.has_feature(features::RESUME) => msg.with_resume_token(token),
+            _ => msg,
+        }
+    }
+
+    /// Indique si le serveur a retenu une fonctionnalité
+    fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
     /// Se connecte au serveur et lance l'interface utilisateur
+    ///
+    /// Une connexion perdue est rétablie automatiquement, avec un délai croissant
+    /// entre les tentatives; le jeton de reprise permet au serveur de restaurer la session.
     pub async fn connect_and_run(&mut self, server_addr: &str) -> Result<(), Box<dyn std::error::Error>> {
         println!("🔗 Connexion au serveur {}...", server_addr);
         
-        let mut stream = TcpStream::connect(server_addr).await?;
-        let (reader, mut writer) = stream.split();
-        let mut buf_reader = BufReader::new(reader);
+        let mut connection = Some(ClientConnection::open(server_addr, self.tls.as_ref()).await?);
 
         println!("✅ Connecté au serveur!");
-        println!("📝 Protocole de messagerie personnalisé v1.0");
+        if self.tls.is_some() {
+            println!("🔒 Connexion chiffrée par TLS");
+        }
+        println!("📝 Protocole de messagerie personnalisé v{}", PROTOCOL_VERSION);
         println!("ℹ️  Tapez /help pour voir les commandes disponibles\n");
 
         // Canal pour les messages utilisateur
         let (tx, mut rx) = mpsc::channel::<String>(100);
 
-        // Task pour lire l'entrée utilisateur
+        // Thread pour lire l'entrée utilisateur: la lecture de stdin est bloquante et
+        // ne doit pas occuper un thread du runtime (ni l'empêcher de s'arrêter)
         let tx_clone = tx.clone();
-        let input_task = tokio::spawn(async move {
+        std::thread::spawn(move || {
             let stdin = io::stdin();
             loop {
                 print!("> ");
@@ -45,12 +211,15 @@ impl MessageClient {
                 
                 let mut input = String::new();
                 match stdin.read_line(&mut input) {
+                    Ok(0) => {
+                        // Fin de l'entrée standard: équivaut à /quit
+                        let _ = tx_clone.blocking_send("/quit".to_string());
+                        break;
+                    }
                     Ok(_) => {
                         let trimmed = input.trim().to_string();
-                        if !trimmed.is_empty() {
-                            if tx_clone.send(trimmed).await.is_err() {
-                                break;
-                            }
+                        if !trimmed.is_empty() && tx_clone.blocking_send(trimmed).is_err() {
+                            break;
                         }
                     }
                     Err(e) => {
@@ -61,123 +230,425 @@ impl MessageClient {
             }
         });
 
-        // Envoyer le message de connexion
-        let connect_msg = Message::connect(self.username.clone());
-        self.send_message_to_writer(&mut writer, &connect_msg).await?;
+        let mut attempt: u32 = 0;
+        loop {
+            if let Some(session) = connection.take() {
+                if self.run_session(session, &mut rx).await == SessionEnd::Quit {
+                    break;
+                }
+                self.transfers.clear();
+                // Une session authentifiée remet le compteur de tentatives à zéro
+                if self.connected {
+                    attempt = 0;
+                }
+                self.connected = false;
+            }
+
+            attempt += 1;
+            if attempt > MAX_RECONNECT_ATTEMPTS {
+                println!("❌ Serveur injoignable après {} tentatives", MAX_RECONNECT_ATTEMPTS);
+                break;
+            }
+            let delay = reconnect_delay(attempt);
+            println!("🔄 Nouvelle tentative de connexion dans {} s (tentative {}/{})...", delay.as_secs(), attempt, MAX_RECONNECT_ATTEMPTS);
+
+            // L'utilisateur peut quitter pendant l'attente
+            let sleep = tokio::time::sleep(delay);
+            tokio::pin!(sleep);
+            let quit = loop {
+                tokio::select! {
+                    _ = &mut sleep => break false,
+                    Some(input) = rx.recv() => {
+                        if input == "/quit" || input == "/exit" {
+                            break true;
+                        }
+                        println!("❌ Connexion en cours de rétablissement, commande ignorée");
+                    }
+                }
+            };
+            if quit {
+                break;
+            }
+
+            match ClientConnection::open(server_addr, self.tls.as_ref()).await {
+                Ok(new_connection) => {
+                    println!("✅ Reconnecté au serveur!");
+                    connection = Some(new_connection);
+                }
+                Err(e) => println!("❌ Connexion impossible: {}", e),
+            }
+        }
+
+        println!("👋 Déconnexion...");
+        Ok(())
+    }
+
+    /// Échange avec le serveur sur une connexion jusqu'à sa fermeture ou la sortie de l'utilisateur
+    async fn run_session(&mut self, mut connection: ClientConnection, rx: &mut mpsc::Receiver<String>) -> SessionEnd {
+        // Se présenter; la connexion (ou la création du compte) suit le HelloAck
+        let hello = Message::hello(features::SUPPORTED.iter().map(|f| f.to_string()).collect());
+        if connection.send(hello).await.is_err() {
+            return SessionEnd::Lost;
+        }
 
-        let mut line = String::new();
         loop {
             tokio::select! {
                 // Messages du serveur
-                result = buf_reader.read_line(&mut line) => {
-                    match result {
-                        Ok(0) => {
-                            println!("\n❌ Connexion fermée par le serveur");
-                            break;
-                        }
-                        Ok(_) => {
-                            let trimmed = line.trim();
-                            if !trimmed.is_empty() {
-                                self.handle_server_message(trimmed).await;
-                            }
-                            line.clear();
-                        }
-                        Err(e) => {
-                            eprintln!("Erreur de lecture du serveur: {}", e);
-                            break;
+                result = connection.next() => {
+                    let Some(msg) = result else {
+                        println!("\n❌ Connexion fermée par le serveur");
+                        return SessionEnd::Lost;
+                    };
+                    // Les transferts de fichiers peuvent appeler plusieurs réponses (morceaux suivants)
+                    let replies = if msg.payload.transfer_id().is_some() {
+                        self.transfers.handle(&msg)
+                    } else {
+                        self.handle_server_message(msg).await.into_iter().collect()
+                    };
+                    for reply in replies {
+                        if connection.send(reply).await.is_err() {
+                            return SessionEnd::Lost;
                         }
                     }
+                    if self.kicked {
+                        connection.close().await;
+                        return SessionEnd::Quit;
+                    }
                 }
                 
                 // Messages de l'utilisateur
                 user_input = rx.recv() => {
                     if let Some(input) = user_input {
+                        // L'utilisateur est actif: les messages affichés sont considérés comme lus
+                        for receipt in self.take_read_receipts() {
+                            if connection.send(receipt).await.is_err() {
+                                return SessionEnd::Lost;
+                            }
+                        }
+                        
                         if input == "/quit" || input == "/exit" {
                             if self.connected {
-                                let disconnect_msg = Message::disconnect(self.username.clone());
-                                let _ = self.send_message_to_writer(&mut writer, &disconnect_msg).await;
+                                let _ = connection.send(Message::disconnect(self.username.clone())).await;
                             }
-                            break;
+                            connection.close().await;
+                            return SessionEnd::Quit;
                         }
                         
                         if let Some(msg) = self.process_user_input(&input).await {
-                            if let Err(e) = self.send_message_to_writer(&mut writer, &msg).await {
+                            self.track_receipts(&msg);
+                            self.remember(&msg);
+                            if let Err(e) = connection.send(msg).await {
                                 eprintln!("Erreur d'envoi: {}", e);
-                                break;
+                                return SessionEnd::Lost;
                             }
                         }
                     }
                 }
             }
         }
-
-        input_task.abort();
-        println!("👋 Déconnexion...");
-        Ok(())
     }
 
-    /// Traite les messages reçus du serveur
-    async fn handle_server_message(&mut self, json: &str) {
-        match Message::from_json(json) {
-            Ok(msg) => {
-                match msg.op_code {
-                    OpCode::ConnectAck => {
-                        self.connected = true;
-                        println!("✅ Connexion établie avec succès!");
-                        println!("💬 Vous pouvez maintenant envoyer des messages");
-                    }
-                    
-                    OpCode::DisconnectAck => {
-                        self.connected = false;
-                        println!("✅ Déconnexion confirmée");
-                    }
-                    
-                    OpCode::MessageReceived => {
-                        if let MessagePayload::Text { content } = msg.payload {
-                            let timestamp = msg.timestamp.format("%H:%M:%S");
-                            match msg.sender {
-                                Some(sender) => {
-                                    println!("[{}] {}: {}", timestamp, sender, content);
-                                }
-                                None => {
-                                    println!("[{}] {}", timestamp, content);
-                                }
-                            }
+    /// Traite les messages reçus du serveur; renvoie la réponse éventuelle à envoyer
+    async fn handle_server_message(&mut self, msg: Message) -> Option<Message> {
+        match msg.op_code {
+            OpCode::HelloAck => {
+                if let MessagePayload::Hello { version, features: negotiated } = msg.payload {
+                    println!("🤝 Serveur en protocole v{}, fonctionnalités: {}", version, negotiated.join(", "));
+                    self.features = negotiated;
+                    if self.framing == Framing::Binary && !self.has_feature(features::BINARY_FRAMING) {
+                        println!("⚠️  Format binaire non pris en charge par le serveur, utilisation du JSON");
+                        self.framing = Framing::Json;
+                    }
+                }
+                // Créer le compte si demandé, sinon se connecter directement
+                return Some(if self.register {
+                    Message::register(self.username.clone(), self.password.clone())
+                } else {
+                    self.connect_message()
+                });
+            }
+            
+            OpCode::RegisterAck => {
+                println!("📝 Compte {} créé", self.username);
+                // Les reconnexions utiliseront le compte existant
+                self.register = false;
+                return Some(self.connect_message());
+            }
+            
+            OpCode::ConnectAck => {
+                self.connected = true;
+                let resumed = match msg.payload {
+                    MessagePayload::Session { resume_token, resumed } => {
+                        self.resume_token = Some(resume_token);
+                        resumed
+                    }
+                    _ => false,
+                };
+                // Le serveur renvoie lui-même les messages manqués pendant la coupure
+                if resumed {
+                    println!("🔁 Session reprise: salons et messages manqués restaurés");
+                    return None;
+                }
+                if let Some(room) = self.current_room.take() {
+                    println!("⚠️  Session non reprise: vous n'êtes plus dans #{}", room);
+                }
+                println!("✅ Connexion établie avec succès!");
+                println!("💬 Vous pouvez maintenant envoyer des messages");
+                // Récupère les derniers messages du chat général
+                if self.has_feature(features::HISTORY) {
+                    return Some(Message::history_request(self.username.clone(), None, Some(RECENT_HISTORY_LIMIT)));
+                }
+            }
+            
+            OpCode::DisconnectAck => {
+                self.connected = false;
+                self.resume_token = None;
+                println!("✅ Déconnexion confirmée");
+            }
+            
+            OpCode::MessageReceived => {
+                if let Some(sender) = &msg.sender {
+                    self.typing.remove(sender);
+                }
+                Self::display_message(&msg);
+                self.remember(&msg);
+                // Accuse réception des messages privés des autres utilisateurs
+                if let (Some(sender), Some(_)) = (&msg.sender, &msg.recipient) {
+                    if sender != SERVER_SENDER && self.has_feature(features::RECEIPTS) {
+                        self.unread.push((msg.id, sender.clone()));
+                        return Some(Message::delivered(self.username.clone(), sender.clone(), msg.id));
+                    }
+                }
+            }
+            
+            OpCode::Delivered | OpCode::Read => {
+                if let MessagePayload::Receipt { message_id } = msg.payload {
+                    let reader = msg.sender.unwrap_or_default();
+                    let preview = if msg.op_code == OpCode::Read {
+                        self.awaiting_receipts.remove(&message_id)
+                    } else {
+                        self.awaiting_receipts.get(&message_id).cloned()
+                    };
+                    if let Some(preview) = preview {
+                        if msg.op_code == OpCode::Read {
+                            println!("✓✓ Lu par {}: «{}»", reader, preview);
+                        } else {
+                            println!("✓ Remis à {}: «{}»", reader, preview);
                         }
                     }
-                    
-                    OpCode::UserList => {
-                        if let MessagePayload::UserList { users } = msg.payload {
-                            println!("👥 Utilisateurs connectés:");
-                            for user in users {
-                                if user == self.username {
-                                    println!("  • {} (vous)", user);
-                                } else {
-                                    println!("  • {}", user);
-                                }
-                            }
+                }
+            }
+            
+            OpCode::HistoryResult => {
+                if let MessagePayload::History { messages } = msg.payload {
+                    if messages.is_empty() {
+                        println!("📜 Aucun message dans l'historique");
+                    } else {
+                        println!("📜 Historique ({} messages):", messages.len());
+                        for message in &messages {
+                            Self::display_message(message);
+                            self.remember(message);
+                        }
+                        println!("📜 Fin de l'historique");
+                    }
+                }
+            }
+            
+            OpCode::EditMessage | OpCode::DeleteMessage => {
+                let editor = msg.sender.clone().unwrap_or_default();
+                let position = msg.payload.corrected_id()
+                    .and_then(|id| self.recent.iter().position(|(recent_id, _, _)| *recent_id == id));
+                // Un administrateur peut corriger le message d'un autre
+                let by = match position {
+                    Some(index) if self.recent[index].1 != editor => format!(" par {}", editor),
+                    _ => String::new(),
+                };
+                match (msg.payload, position) {
+                    (MessagePayload::Edit { content, .. }, Some(index)) => {
+                        let (_, author, text) = &mut self.recent[index];
+                        println!("✏️  Message de {} modifié{}: {}", author, by, content);
+                        *text = content;
+                    }
+                    (MessagePayload::Edit { content, .. }, None) => {
+                        println!("✏️  {} a modifié un message: {}", editor, content);
+                    }
+                    (_, Some(index)) => {
+                        if let Some((_, author, text)) = self.recent.remove(index) {
+                            println!("🗑️  Message de {} supprimé{}: «{}»", author, by, text);
                         }
                     }
-                    
-                    OpCode::Error => {
-                        if let MessagePayload::Error { code, message } = msg.payload {
-                            println!("❌ Erreur {}: {}", code, message);
+                    (_, None) => println!("🗑️  {} a supprimé un message", editor),
+                }
+            }
+            
+            OpCode::UserList => {
+                if let MessagePayload::UserList { users, presence } = msg.payload {
+                    println!("👥 Utilisateurs connectés:");
+                    for user in users {
+                        let you = if user == self.username { " (vous)" } else { "" };
+                        // Un serveur qui ne connaît pas la présence n'envoie que les noms
+                        match presence.iter().find(|p| p.username == user) {
+                            Some(p) => println!("  • {}{} - {}", user, you, describe_presence(p.status, p.text.as_deref())),
+                            None => println!("  • {}{}", user, you),
                         }
                     }
-                    
-                    _ => {
-                        println!("⚠️  Message non géré: {:?}", msg.op_code);
+                }
+            }
+            
+            OpCode::Presence => {
+                if let MessagePayload::Presence { status, text } = msg.payload {
+                    let status = describe_presence(status, text.as_deref());
+                    match msg.sender {
+                        Some(sender) if sender == self.username => println!("💤 Votre statut: {}", status),
+                        Some(sender) => println!("👤 {} est maintenant {}", sender, status),
+                        None => {}
                     }
                 }
             }
-            Err(e) => {
-                eprintln!("Erreur de parsing du message: {}", e);
+            
+            OpCode::Typing => {
+                if let (Some(sender), MessagePayload::Typing { typing }) = (msg.sender, msg.payload) {
+                    if !typing {
+                        self.typing.remove(&sender);
+                    } else if self.typing.get(&sender).is_none_or(|shown| shown.elapsed() >= TYPING_TIMEOUT) {
+                        // Affichée une fois, puis de nouveau si elle est renouvelée après expiration
+                        match &msg.room {
+                            Some(room) => println!("✏️  {} écrit dans #{}...", sender, room),
+                            None if msg.recipient.is_some() => println!("✏️  {} vous écrit...", sender),
+                            None => println!("✏️  {} écrit...", sender),
+                        }
+                        self.typing.insert(sender, Instant::now());
+                    }
+                }
+            }
+            
+            OpCode::RoomJoined => {
+                if let MessagePayload::Room { room } = msg.payload {
+                    println!("🏠 Vous avez rejoint #{} (vos messages y sont envoyés)", room);
+                    self.current_room = Some(room.clone());
+                    // Rejoue les derniers messages du salon
+                    if self.has_feature(features::HISTORY) {
+                        return Some(Message::history_request(self.username.clone(), Some(room), Some(RECENT_HISTORY_LIMIT)));
+                    }
+                }
+            }
+            
+            OpCode::RoomLeft => {
+                if let MessagePayload::Room { room } = msg.payload {
+                    println!("🚪 Vous avez quitté #{}", room);
+                    if self.current_room.as_ref() == Some(&room) {
+                        self.current_room = None;
+                        println!("💬 Retour au chat général");
+                    }
+                }
+            }
+            
+            OpCode::RoomList => {
+                if let MessagePayload::RoomList { rooms } = msg.payload {
+                    if rooms.is_empty() {
+                        println!("🏠 Aucun salon ouvert");
+                    } else {
+                        println!("🏠 Salons:");
+                        for room in rooms {
+                            let current = if self.current_room.as_ref() == Some(&room.name) { " (actuel)" } else { "" };
+                            println!("  • #{} - {} membre(s){}", room.name, room.members, current);
+                        }
+                    }
+                }
+            }
+            
+            OpCode::ModerationAck => {
+                if let MessagePayload::Moderation { username, duration, .. } = msg.payload {
+                    match duration {
+                        Some(seconds) => println!("🛡️  Sanction appliquée à {} pour {} s", username, seconds),
+                        None => println!("🛡️  Sanction appliquée à {}", username),
+                    }
+                }
+            }
+            
+            OpCode::Error => {
+                if let MessagePayload::Error { code, message } = msg.payload {
+                    if code == error_codes::KICKED {
+                        println!("⛔ Vous avez été expulsé du serveur ({})", message);
+                        self.kicked = true;
+                    } else {
+                        println!("❌ Erreur {}: {}", code, message);
+                    }
+                }
+            }
+            
+            _ => {
+                println!("⚠️  Message non géré: {:?}", msg.op_code);
+            }
+        }
+        None
+    }
+
+    /// Retient un message privé envoyé pour afficher ses accusés
+    fn track_receipts(&mut self, msg: &Message) {
+        if let (Some(_), MessagePayload::Text { content }) = (&msg.recipient, &msg.payload) {
+            if self.has_feature(features::RECEIPTS) {
+                let mut preview: String = content.chars().take(RECEIPT_PREVIEW_LEN).collect();
+                if content.chars().count() > RECEIPT_PREVIEW_LEN {
+                    preview.push('…');
+                }
+                self.awaiting_receipts.insert(msg.id, preview);
+            }
+        }
+    }
+
+    /// Retient un message texte affiché ou envoyé, pour pouvoir le corriger ensuite
+    fn remember(&mut self, msg: &Message) {
+        let (Some(sender), MessagePayload::Text { content }) = (&msg.sender, &msg.payload) else {
+            return;
+        };
+        // Les notifications du serveur et les commandes des bots ne sont pas conservées
+        if sender == SERVER_SENDER || content.starts_with('/') || self.recent.iter().any(|(id, _, _)| *id == msg.id) {
+            return;
+        }
+        self.recent.push_back((msg.id, sender.clone(), content.clone()));
+        if self.recent.len() > RECENT_MESSAGES_LIMIT {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Dernier message retenu de `author`
+    fn last_message_of(&self, author: &str) -> Option<usize> {
+        self.recent.iter().rposition(|(_, sender, _)| sender == author)
+    }
+
+    /// Accusés de lecture des messages privés reçus depuis la dernière saisie
+    fn take_read_receipts(&mut self) -> Vec<Message> {
+        self.unread
+            .drain(..)
+            .map(|(message_id, sender)| Message::read(self.username.clone(), sender, message_id))
+            .collect()
+    }
+
+    /// Affiche un message texte reçu en direct ou depuis l'historique
+    fn display_message(msg: &Message) {
+        if let MessagePayload::Text { content } = &msg.payload {
+            let timestamp = msg.timestamp.format("%H:%M:%S");
+            let edited = if msg.edited_at.is_some() { " (modifié)" } else { "" };
+            match &msg.sender {
+                Some(sender) if msg.recipient.is_some() => {
+                    println!("[{}] 💌 {} (privé): {}{}", timestamp, sender, content, edited);
+                }
+                Some(sender) if msg.room.is_some() => {
+                    println!("[{}] #{} {}: {}{}", timestamp, msg.room.as_deref().unwrap_or_default(), sender, content, edited);
+                }
+                Some(sender) => {
+                    println!("[{}] {}: {}{}", timestamp, sender, content, edited);
+                }
+                None => {
+                    println!("[{}] {}{}", timestamp, content, edited);
+                }
             }
         }
     }
 
     /// Traite l'entrée utilisateur et crée les messages appropriés
-    async fn process_user_input(&self, input: &str) -> Option<Message> {
+    async fn process_user_input(&mut self, input: &str) -> Option<Message> {
         if input.starts_with('/') {
             // Commandes spéciales
             match input {
@@ -205,6 +676,222 @@ impl MessageClient {
                         None
                     }
                 }
+                "/rooms" => {
+                    if self.connected {
+                        Some(Message::new(
+                            OpCode::ListRooms,
+                            Some(self.username.clone()),
+                            MessagePayload::Empty
+                        ))
+                    } else {
+                        println!("❌ Vous devez être connecté pour utiliser cette commande");
+                        None
+                    }
+                }
+                _ if input == "/join" || input.starts_with("/join ") || input == "/leave" || input.starts_with("/leave ") => {
+                    if !self.connected {
+                        println!("❌ Vous devez être connecté pour utiliser cette commande");
+                        return None;
+                    }
+                    let (command, argument) = input.split_once(' ').unwrap_or((input, ""));
+                    let room = match argument.trim() {
+                        "" if command == "/leave" => self.current_room.clone(),
+                        "" => None,
+                        room => Some(room.trim_start_matches('#').to_string()),
+                    };
+                    match (command, room) {
+                        ("/join", Some(room)) => Some(Message::join_room(self.username.clone(), room)),
+                        ("/leave", Some(room)) => Some(Message::leave_room(self.username.clone(), room)),
+                        ("/join", None) => {
+                            println!("❌ Usage: /join <salon>");
+                            None
+                        }
+                        _ => {
+                            println!("❌ Vous n'êtes dans aucun salon");
+                            None
+                        }
+                    }
+                }
+                _ if input == "/history" || input.starts_with("/history ") => {
+                    if !self.connected {
+                        println!("❌ Vous devez être connecté pour utiliser cette commande");
+                        return None;
+                    }
+                    if !self.has_feature(features::HISTORY) {
+                        println!("❌ Le serveur ne conserve pas d'historique");
+                        return None;
+                    }
+                    match input["/history".len()..].trim() {
+                        "" => Some(Message::history_request(self.username.clone(), self.current_room.clone(), Some(RECENT_HISTORY_LIMIT))),
+                        count => match count.parse::<usize>() {
+                            Ok(limit) if limit > 0 => {
+                                Some(Message::history_request(self.username.clone(), self.current_room.clone(), Some(limit)))
+                            }
+                            _ => {
+                                println!("❌ Usage: /history [nombre]");
+                                None
+                            }
+                        },
+                    }
+                }
+                _ if input.starts_with("/msg ") => {
+                    if !self.connected {
+                        println!("❌ Vous devez être connecté pour envoyer des messages");
+                        return None;
+                    }
+                    match input["/msg ".len()..].trim().split_once(' ') {
+                        Some((recipient, content)) if !content.trim().is_empty() => {
+                            Some(Message::private_message(
+                                self.username.clone(),
+                                recipient.to_string(),
+                                content.trim().to_string()
+                            ))
+                        }
+                        _ => {
+                            println!("❌ Usage: /msg <utilisateur> <message>");
+                            None
+                        }
+                    }
+                }
+                _ if matches!(input.split(' ').next(), Some("/send")) => {
+                    if !self.connected {
+                        println!("❌ Vous devez être connecté pour envoyer des fichiers");
+                        return None;
+                    }
+                    if !self.has_feature(features::FILES) {
+                        println!("❌ Le serveur ne prend pas en charge les transferts de fichiers");
+                        return None;
+                    }
+                    match input["/send".len()..].trim().split_once(' ') {
+                        Some((recipient, path)) if !path.trim().is_empty() => {
+                            match self.transfers.offer(recipient, Path::new(path.trim())) {
+                                Ok(offer) => {
+                                    println!("📎 Fichier proposé à {}, en attente de son acceptation", recipient);
+                                    Some(offer)
+                                }
+                                Err(e) => {
+                                    println!("❌ {}", e);
+                                    None
+                                }
+                            }
+                        }
+                        _ => {
+                            println!("❌ Usage: /send <utilisateur> <chemin>");
+                            None
+                        }
+                    }
+                }
+                _ if matches!(input.split(' ').next(), Some("/accept" | "/reject")) => {
+                    if !self.connected {
+                        println!("❌ Vous devez être connecté pour utiliser cette commande");
+                        return None;
+                    }
+                    let (command, prefix) = input.split_once(' ').unwrap_or((input, ""));
+                    let prefix = Some(prefix.trim()).filter(|prefix| !prefix.is_empty());
+                    let result = if command == "/accept" {
+                        self.transfers.accept(prefix)
+                    } else {
+                        self.transfers.reject(prefix)
+                    };
+                    match result {
+                        Ok(msg) => Some(msg),
+                        Err(e) => {
+                            println!("❌ {}", e);
+                            None
+                        }
+                    }
+                }
+                _ if matches!(input.split(' ').next(), Some("/edit" | "/delete")) => {
+                    if !self.connected {
+                        println!("❌ Vous devez être connecté pour utiliser cette commande");
+                        return None;
+                    }
+                    if !self.has_feature(features::EDITS) {
+                        println!("❌ Le serveur ne permet pas de corriger les messages");
+                        return None;
+                    }
+                    let (command, argument) = input.split_once(' ').unwrap_or((input, ""));
+                    let argument = argument.trim();
+                    if command == "/edit" {
+                        if argument.is_empty() {
+                            println!("❌ Usage: /edit <nouveau texte>");
+                            return None;
+                        }
+                        let Some(index) = self.last_message_of(&self.username) else {
+                            println!("❌ Aucun message à modifier");
+                            return None;
+                        };
+                        let (id, _, text) = &mut self.recent[index];
+                        *text = argument.to_string();
+                        println!("✏️  Message modifié");
+                        Some(Message::edit_message(self.username.clone(), *id, argument.to_string()))
+                    } else {
+                        // Sans argument, son propre dernier message; sinon celui d'un autre (administrateurs)
+                        let author = if argument.is_empty() { self.username.clone() } else { argument.to_string() };
+                        let Some((id, _, text)) = self.last_message_of(&author).and_then(|index| self.recent.remove(index)) else {
+                            println!("❌ Aucun message de {} à supprimer", author);
+                            return None;
+                        };
+                        println!("🗑️  Message supprimé: «{}»", text);
+                        Some(Message::delete_message(self.username.clone(), id))
+                    }
+                }
+                _ if matches!(input.split(' ').next(), Some("/status")) => {
+                    if !self.connected {
+                        println!("❌ Vous devez être connecté pour utiliser cette commande");
+                        return None;
+                    }
+                    if !self.has_feature(features::PRESENCE) {
+                        println!("❌ Le serveur ne prend pas en charge les statuts");
+                        return None;
+                    }
+                    let args = input["/status".len()..].trim();
+                    let (status, text) = args.split_once(' ').unwrap_or((args, ""));
+                    match parse_status(status) {
+                        Some(status) => {
+                            let text = Some(text.trim().to_string()).filter(|text| !text.is_empty());
+                            println!("💤 Votre statut: {}", describe_presence(status, text.as_deref()));
+                            Some(Message::presence(self.username.clone(), status, text))
+                        }
+                        None => {
+                            println!("❌ Usage: /status <online|away|busy> [texte]");
+                            None
+                        }
+                    }
+                }
+                _ if matches!(input.split(' ').next(), Some("/kick" | "/ban" | "/mute")) => {
+                    if !self.connected {
+                        println!("❌ Vous devez être connecté pour utiliser cette commande");
+                        return None;
+                    }
+                    let (command, args) = input.split_once(' ').unwrap_or((input, ""));
+                    let (target, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+                    let op_code = match command {
+                        "/kick" => OpCode::Kick,
+                        "/ban" => OpCode::Ban,
+                        _ => OpCode::Mute,
+                    };
+                    // Le premier mot après la cible est une durée s'il en a la forme
+                    let (duration, reason) = match rest.trim().split_once(' ').unwrap_or((rest.trim(), "")) {
+                        (first, reason) if op_code != OpCode::Kick && parse_duration(first).is_some() => {
+                            (parse_duration(first), reason.trim())
+                        }
+                        _ => (None, rest.trim()),
+                    };
+                    if target.is_empty() {
+                        println!("❌ Usage: {} <utilisateur> {}[raison]", command, if op_code == OpCode::Kick { "" } else { "[durée] " });
+                        return None;
+                    }
+                    Some(Message::moderation(
+                        op_code,
+                        self.username.clone(),
+                        target.to_string(),
+                        duration,
+                        Some(reason.to_string()).filter(|reason| !reason.is_empty())
+                    ))
+                }
+                // Les autres commandes sont transmises aux bots du serveur
+                _ if self.connected => Some(self.text_message(input)),
                 _ => {
                     println!("❌ Commande inconnue. Tapez /help pour voir les commandes disponibles");
                     None
@@ -217,10 +904,7 @@ impl MessageClient {
                     println!("❌ Le message ne peut pas être vide");
                     None
                 } else {
-                    Some(Message::text_message(
-                        self.username.clone(),
-                        input.to_string()
-                    ))
+                    Some(self.text_message(input))
                 }
             } else {
                 println!("❌ Vous devez être connecté pour envoyer des messages");
@@ -229,27 +913,40 @@ impl MessageClient {
         }
     }
 
+    /// Message texte pour le salon actuel, ou pour le chat général
+    fn text_message(&self, content: &str) -> Message {
+        match &self.current_room {
+            Some(room) => Message::room_message(self.username.clone(), room.clone(), content.to_string()),
+            None => Message::text_message(self.username.clone(), content.to_string()),
+        }
+    }
+
     /// Affiche l'aide
     fn show_help(&self) {
         println!("\n📋 Commandes disponibles:");
         println!("  /help          - Affiche cette aide");
         println!("  /users, /list  - Liste les utilisateurs connectés");
+        println!("  /msg <user> <texte> - Envoie un message privé");
+        println!("  /join <salon>  - Rejoint un salon (le crée s'il n'existe pas)");
+        println!("  /leave [salon] - Quitte un salon (le salon actuel par défaut)");
+        println!("  /rooms         - Liste les salons");
+        println!("  /history [n]   - Affiche les derniers messages du salon actuel ou du chat général");
+        println!("  /edit <texte>  - Modifie votre dernier message");
+        println!("  /delete [user] - Supprime votre dernier message (ou celui d'un autre, administrateurs)");
+        println!("  /status <online|away|busy> [texte] - Change votre statut de présence");
+        println!("  /send <user> <chemin> - Propose un fichier à un utilisateur connecté");
+        println!("  /accept [id]   - Accepte un fichier proposé (le plus ancien par défaut)");
+        println!("  /reject [id]   - Refuse un fichier proposé");
+        println!("  /kick <user> [raison] - Expulse un utilisateur (administrateurs)");
+        println!("  /ban <user|ip> [durée] [raison] - Bannit un utilisateur ou une adresse (administrateurs)");
+        println!("  /mute <user> [durée] [raison] - Empêche un utilisateur d'écrire (administrateurs)");
+        println!("                   durée: 45s, 30m, 2h, 1d; définitive si absente");
         println!("  /disconnect    - Se déconnecte du serveur");
         println!("  /quit, /exit   - Quitte l'application");
-        println!("  <message>      - Envoie un message à tous les utilisateurs");
+        println!("  /<commande>    - Toute autre commande est transmise aux bots du serveur");
+        println!("  <message>      - Envoie un message au salon actuel ou à tous les utilisateurs");
         println!();
     }
-
-    /// Envoie un message au serveur
-    async fn send_message_to_writer(
-        &self,
-        writer: &mut tokio::net::tcp::WriteHalf<'_>,
-        msg: &Message,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let json = format!("{}\n", msg.to_json()?);
-        writer.write_all(json.as_bytes()).await?;
-        Ok(())
-    }
 }
 
 #[cfg(test)]
@@ -258,8 +955,250 @@ mod tests {
 
     #[tokio::test]
     async fn test_client_creation() {
-        let client = MessageClient::new("test_user".to_string());
+        let client = MessageClient::new("test_user".to_string(), "password".to_string());
         assert_eq!(client.username, "test_user");
         assert!(!client.connected);
     }
+
+    #[tokio::test]
+    async fn test_private_message_command() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string());
+        client.connected = true;
+
+        let msg = client.process_user_input("/msg Bob Salut Bob").await.unwrap();
+        assert_eq!(msg.recipient.as_deref(), Some("Bob"));
+        assert!(matches!(msg.payload, MessagePayload::Text { ref content } if content == "Salut Bob"));
+
+        assert!(client.process_user_input("/msg Bob").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bot_command_forwarded() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string());
+        assert!(client.process_user_input("/deploy status").await.is_none());
+
+        client.connected = true;
+        let msg = client.process_user_input("/deploy status").await.unwrap();
+        assert_eq!(msg.op_code, OpCode::SendMessage);
+        assert!(matches!(msg.payload, MessagePayload::Text { ref content } if content == "/deploy status"));
+    }
+
+    #[tokio::test]
+    async fn test_room_commands() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string());
+        client.connected = true;
+
+        let msg = client.process_user_input("/join #rust").await.unwrap();
+        assert_eq!(msg.op_code, OpCode::JoinRoom);
+        assert!(matches!(msg.payload, MessagePayload::Room { ref room } if room == "rust"));
+
+        // Sans salon actuel, /leave n'a rien à quitter
+        assert!(client.process_user_input("/leave").await.is_none());
+
+        client.current_room = Some("rust".to_string());
+        let msg = client.process_user_input("Salut").await.unwrap();
+        assert_eq!(msg.room.as_deref(), Some("rust"));
+
+        let msg = client.process_user_input("/leave").await.unwrap();
+        assert_eq!(msg.op_code, OpCode::LeaveRoom);
+    }
+
+    #[tokio::test]
+    async fn test_hello_ack_starts_login() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string())
+            .with_framing(Framing::Binary);
+
+        // Le serveur ne retient pas le format binaire: le client reste en JSON
+        let ack = Message::hello_ack(vec![features::ROOMS.to_string()]);
+        let reply = client.handle_server_message(ack).await.unwrap();
+        assert_eq!(reply.op_code, OpCode::Connect);
+        assert!(matches!(reply.payload, MessagePayload::Connect { framing: Framing::Json, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_history_fetched_after_connect_and_join() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string());
+        client.features = vec![features::ROOMS.to_string(), features::HISTORY.to_string()];
+
+        let reply = client.handle_server_message(Message::connect_ack()).await.unwrap();
+        assert_eq!(reply.op_code, OpCode::History);
+        assert!(matches!(reply.payload, MessagePayload::HistoryRequest { room: None, .. }));
+
+        let joined = Message::new(OpCode::RoomJoined, None, MessagePayload::Room { room: "rust".to_string() });
+        let reply = client.handle_server_message(joined).await.unwrap();
+        assert!(matches!(reply.payload, MessagePayload::HistoryRequest { room: Some(ref room), .. } if room == "rust"));
+
+        let msg = client.process_user_input("/history 5").await.unwrap();
+        assert!(matches!(msg.payload, MessagePayload::HistoryRequest { limit: Some(5), .. }));
+        assert!(client.process_user_input("/history zéro").await.is_none());
+
+        // Sans la fonctionnalité, rien n'est demandé
+        client.features.clear();
+        assert!(client.handle_server_message(Message::connect_ack()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_receipts() {
+        let mut alice = MessageClient::new("Alice".to_string(), "password".to_string());
+        let mut bob = MessageClient::new("Bob".to_string(), "password".to_string());
+        for client in [&mut alice, &mut bob] {
+            client.connected = true;
+            client.features = vec![features::RECEIPTS.to_string()];
+        }
+
+        let sent = alice.process_user_input("/msg Bob Salut Bob").await.unwrap();
+        alice.track_receipts(&sent);
+
+        // Bob accuse réception dès l'affichage, puis la lecture à sa prochaine saisie
+        let mut received = sent.clone();
+        received.op_code = OpCode::MessageReceived;
+        let delivered = bob.handle_server_message(received).await.unwrap();
+        assert_eq!(delivered.op_code, OpCode::Delivered);
+        assert_eq!(delivered.recipient.as_deref(), Some("Alice"));
+        let read = bob.take_read_receipts();
+        assert!(matches!(read[0].payload, MessagePayload::Receipt { message_id } if message_id == sent.id));
+        assert!(bob.take_read_receipts().is_empty());
+
+        assert!(alice.handle_server_message(delivered).await.is_none());
+        assert!(alice.awaiting_receipts.contains_key(&sent.id));
+        alice.handle_server_message(read[0].clone()).await;
+        assert!(alice.awaiting_receipts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resume_token_reused_on_reconnect() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string());
+        client.features = vec![features::RESUME.to_string()];
+        client.current_room = Some("rust".to_string());
+
+        let token = Uuid::new_v4();
+        assert!(client.handle_server_message(Message::session_ack(token, false)).await.is_none());
+        // Nouvelle session: l'ancien salon est perdu
+        assert!(client.current_room.is_none());
+        assert!(matches!(client.connect_message().payload, MessagePayload::Connect { resume_token: Some(t), .. } if t == token));
+
+        client.current_room = Some("rust".to_string());
+        let reply = client.handle_server_message(Message::session_ack(Uuid::new_v4(), true)).await;
+        assert!(reply.is_none());
+        assert_eq!(client.current_room.as_deref(), Some("rust"));
+    }
+
+    #[tokio::test]
+    async fn test_moderation_commands() {
+        let mut client = MessageClient::new("Admin".to_string(), "password123".to_string());
+        client.connected = true;
+
+        let msg = client.process_user_input("/ban Bob 2h spam répété").await.unwrap();
+        assert_eq!(msg.op_code, OpCode::Ban);
+        assert!(matches!(
+            msg.payload,
+            MessagePayload::Moderation { ref username, duration: Some(7200), reason: Some(ref reason) }
+                if username == "Bob" && reason == "spam répété"
+        ));
+
+        // Sans durée reconnue, tout le reste est le motif
+        let msg = client.process_user_input("/mute Bob trop bavard").await.unwrap();
+        assert!(matches!(msg.payload, MessagePayload::Moderation { duration: None, reason: Some(ref reason), .. } if reason == "trop bavard"));
+        let msg = client.process_user_input("/kick Bob").await.unwrap();
+        assert!(matches!(msg.payload, MessagePayload::Moderation { duration: None, reason: None, .. }));
+
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("1d"), Some(86400));
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("2x"), None);
+
+        // Une expulsion met fin à la session sans reconnexion
+        client.handle_server_message(Message::error(error_codes::KICKED, "Kicked by Admin".to_string())).await;
+        assert!(client.kicked);
+    }
+
+    #[tokio::test]
+    async fn test_presence_and_typing() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string());
+        client.connected = true;
+        assert!(client.process_user_input("/status away").await.is_none());
+
+        client.features = vec![features::PRESENCE.to_string()];
+        let msg = client.process_user_input("/status busy En réunion").await.unwrap();
+        assert_eq!(msg.op_code, OpCode::Presence);
+        assert!(matches!(
+            msg.payload,
+            MessagePayload::Presence { status: PresenceStatus::Busy, text: Some(ref text) } if text == "En réunion"
+        ));
+        assert!(client.process_user_input("/status parti").await.is_none());
+
+        // La saisie d'un utilisateur est retenue jusqu'à son message
+        let typing = Message::typing("Bob".to_string(), Some("Alice".to_string()), None, true);
+        assert!(client.handle_server_message(typing).await.is_none());
+        assert!(client.typing.contains_key("Bob"));
+        let mut received = Message::text_message("Bob".to_string(), "Salut".to_string());
+        received.op_code = OpCode::MessageReceived;
+        client.handle_server_message(received).await;
+        assert!(client.typing.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_edit_and_delete_commands() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string());
+        client.connected = true;
+        client.features = vec![features::EDITS.to_string()];
+        assert!(client.process_user_input("/edit Bonjour").await.is_none());
+
+        let sent = client.process_user_input("Bonjuor").await.unwrap();
+        client.remember(&sent);
+        let edit = client.process_user_input("/edit Bonjour").await.unwrap();
+        assert!(matches!(edit.payload, MessagePayload::Edit { message_id, ref content } if message_id == sent.id && content == "Bonjour"));
+
+        // Une correction reçue met à jour le message retenu
+        let mut received = Message::text_message("Bob".to_string(), "Salut".to_string());
+        received.op_code = OpCode::MessageReceived;
+        client.handle_server_message(received.clone()).await;
+        client.handle_server_message(Message::edit_message("Bob".to_string(), received.id, "Salut à tous".to_string())).await;
+        assert_eq!(client.recent.back().unwrap().2, "Salut à tous");
+        client.handle_server_message(Message::delete_message("Bob".to_string(), received.id)).await;
+        assert_eq!(client.recent.len(), 1);
+
+        let delete = client.process_user_input("/delete").await.unwrap();
+        assert!(matches!(delete.payload, MessagePayload::Delete { message_id } if message_id == sent.id));
+        assert!(client.process_user_input("/delete").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_send_file_command() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string());
+        client.connected = true;
+        client.features = vec![features::FILES.to_string()];
+
+        let path = std::env::temp_dir().join(format!("tp8-send-{}.txt", Uuid::new_v4()));
+        std::fs::write(&path, "Bonjour").unwrap();
+        let msg = client.process_user_input(&format!("/send Bob {}", path.display())).await.unwrap();
+        assert_eq!(msg.op_code, OpCode::FileOffer);
+        assert_eq!(msg.recipient.as_deref(), Some("Bob"));
+        assert!(matches!(msg.payload, MessagePayload::FileOffer { size: 7, .. }));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(client.process_user_input("/send Bob /chemin/inexistant").await.is_none());
+        assert!(client.process_user_input("/send Bob").await.is_none());
+        assert!(client.process_user_input("/accept").await.is_none());
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        assert_eq!(reconnect_delay(1), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(2), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(4), Duration::from_secs(8));
+        assert_eq!(reconnect_delay(6), RECONNECT_MAX_DELAY);
+        assert_eq!(reconnect_delay(u32::MAX), RECONNECT_MAX_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_register_then_connect() {
+        let mut client = MessageClient::new("Alice".to_string(), "password".to_string()).with_registration();
+        assert!(client.register);
+
+        let ack = Message::new(OpCode::RegisterAck, None, MessagePayload::Empty);
+        let reply = client.handle_server_message(ack).await.unwrap();
+        assert_eq!(reply.op_code, OpCode::Connect);
+        assert!(!client.register);
+    }
 }
@@ -0,0 +1,251 @@
+use std::fmt;
+use std::io;
+
+use bytes::{Buf, BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::protocol::Message;
+
+/// Taille maximale d'une trame par défaut (1 Mio)
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Taille de l'en-tête de longueur du format binaire
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Format des trames échangées sur la connexion
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Framing {
+    /// Un message JSON par ligne (format historique, utilisé jusqu'au `ConnectAck`)
+    #[default]
+    Json,
+    /// Longueur sur 4 octets (big-endian) suivie du message encodé en MessagePack
+    Binary,
+}
+
+impl fmt::Display for Framing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Framing::Json => write!(f, "JSON (une ligne par message)"),
+            Framing::Binary => write!(f, "binaire (longueur u32 + MessagePack)"),
+        }
+    }
+}
+
+/// Erreur fatale du codec: le flux ne peut plus être découpé en trames
+#[derive(Debug)]
+pub enum CodecError {
+    Io(io::Error),
+    FrameTooLarge { size: usize, max: usize },
+    Encode(String),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "I/O error: {}", e),
+            CodecError::FrameTooLarge { size, max } => {
+                write!(f, "Frame too large: {} bytes (max {})", size, max)
+            }
+            CodecError::Encode(e) => write!(f, "Encode error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self {
+        CodecError::Io(e)
+    }
+}
+
+/// Codec des messages du protocole, partagé par le serveur et le client
+///
+/// Une trame complète mais illisible (JSON invalide, etc.) est renvoyée comme
+/// `Err(description)` sans interrompre le flux; seules les erreurs de découpage
+/// (`CodecError`) sont fatales.
+#[derive(Debug)]
+pub struct MessageCodec {
+    framing: Framing,
+    max_frame_size: usize,
+    /// Position jusqu'à laquelle une fin de ligne a déjà été cherchée
+    next_index: usize,
+}
+
+impl MessageCodec {
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            next_index: 0,
+        }
+    }
+
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Format des trames en cours
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Change de format pour les trames suivantes (après la négociation); les octets déjà
+    /// reçus mais pas encore découpés seront lus dans le nouveau format
+    pub fn set_framing(&mut self, framing: Framing) {
+        self.framing = framing;
+        self.next_index = 0;
+    }
+
+    fn decode_line(&mut self, src: &mut BytesMut) -> Result<Option<Result<Message, String>>, CodecError> {
+        loop {
+            let Some(offset) = src[self.next_index..].iter().position(|b| *b == b'\n') else {
+                if src.len() > self.max_frame_size {
+                    return Err(CodecError::FrameTooLarge { size: src.len(), max: self.max_frame_size });
+                }
+                self.next_index = src.len();
+                return Ok(None);
+            };
+
+            let end = self.next_index + offset;
+            self.next_index = 0;
+            if end > self.max_frame_size {
+                return Err(CodecError::FrameTooLarge { size: end, max: self.max_frame_size });
+            }
+
+            let line = src.split_to(end + 1);
+            let Ok(text) = std::str::from_utf8(&line[..end]) else {
+                return Ok(Some(Err("Invalid UTF-8 in message".to_string())));
+            };
+            let text = text.trim();
+            // Les lignes vides sont ignorées
+            if !text.is_empty() {
+                return Ok(Some(Message::from_json(text).map_err(|e| format!("JSON parse error: {}", e))));
+            }
+        }
+    }
+
+    fn decode_length_prefixed(&mut self, src: &mut BytesMut) -> Result<Option<Result<Message, String>>, CodecError> {
+        if src.len() < LENGTH_PREFIX_SIZE {
+            return Ok(None);
+        }
+
+        let mut length_bytes = [0u8; LENGTH_PREFIX_SIZE];
+        length_bytes.copy_from_slice(&src[..LENGTH_PREFIX_SIZE]);
+        let length = u32::from_be_bytes(length_bytes) as usize;
+        if length > self.max_frame_size {
+            return Err(CodecError::FrameTooLarge { size: length, max: self.max_frame_size });
+        }
+
+        if src.len() < LENGTH_PREFIX_SIZE + length {
+            src.reserve(LENGTH_PREFIX_SIZE + length - src.len());
+            return Ok(None);
+        }
+
+        src.advance(LENGTH_PREFIX_SIZE);
+        let body = src.split_to(length);
+        Ok(Some(rmp_serde::from_slice(&body).map_err(|e| format!("MessagePack decode error: {}", e))))
+    }
+}
+
+impl Decoder for MessageCodec {
+    type Item = Result<Message, String>;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.framing {
+            Framing::Json => self.decode_line(src),
+            Framing::Binary => self.decode_length_prefixed(src),
+        }
+    }
+}
+
+impl Encoder<&Message> for MessageCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, msg: &Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let body = match self.framing {
+            Framing::Json => msg.to_json().map(String::into_bytes).map_err(|e| CodecError::Encode(e.to_string()))?,
+            Framing::Binary => rmp_serde::to_vec_named(msg).map_err(|e| CodecError::Encode(e.to_string()))?,
+        };
+        if body.len() > self.max_frame_size {
+            return Err(CodecError::FrameTooLarge { size: body.len(), max: self.max_frame_size });
+        }
+
+        match self.framing {
+            Framing::Json => {
+                dst.reserve(body.len() + 1);
+                dst.put_slice(&body);
+                dst.put_u8(b'\n');
+            }
+            Framing::Binary => {
+                dst.reserve(LENGTH_PREFIX_SIZE + body.len());
+                dst.put_u32(body.len() as u32);
+                dst.put_slice(&body);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(codec: &mut MessageCodec, msg: &Message) -> BytesMut {
+        let mut buf = BytesMut::new();
+        codec.encode(msg, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_roundtrip_both_framings() {
+        for framing in [Framing::Json, Framing::Binary] {
+            let mut codec = MessageCodec::new(framing);
+            let msg = Message::text_message("Alice".to_string(), "ligne 1\nligne 2".to_string());
+            let mut buf = encode(&mut codec, &msg);
+
+            let decoded = codec.decode(&mut buf).unwrap().unwrap().unwrap();
+            assert_eq!(decoded.id, msg.id);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_partial_frames() {
+        for framing in [Framing::Json, Framing::Binary] {
+            let mut codec = MessageCodec::new(framing);
+            let msg = Message::text_message("Alice".to_string(), "Salut".to_string());
+            let full = encode(&mut codec, &msg);
+
+            let mut buf = BytesMut::from(&full[..full.len() - 1]);
+            assert!(codec.decode(&mut buf).unwrap().is_none());
+            buf.extend_from_slice(&full[full.len() - 1..]);
+            assert!(codec.decode(&mut buf).unwrap().unwrap().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_invalid_frame_is_recoverable() {
+        let mut codec = MessageCodec::new(Framing::Json);
+        let mut buf = BytesMut::from("pas du json\n\n");
+        buf.extend_from_slice(&encode(&mut codec, &Message::connect_ack()));
+
+        assert!(codec.decode(&mut buf).unwrap().unwrap().is_err());
+        assert!(codec.decode(&mut buf).unwrap().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_frame_too_large() {
+        let mut codec = MessageCodec::new(Framing::Json).with_max_frame_size(16);
+        let mut buf = BytesMut::from(&[b'x'; 32][..]);
+        assert!(matches!(codec.decode(&mut buf), Err(CodecError::FrameTooLarge { .. })));
+
+        let mut codec = MessageCodec::new(Framing::Binary).with_max_frame_size(16);
+        let mut buf = BytesMut::new();
+        buf.put_u32(1 << 30);
+        assert!(matches!(codec.decode(&mut buf), Err(CodecError::FrameTooLarge { .. })));
+    }
+}
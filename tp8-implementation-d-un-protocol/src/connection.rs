@@ -0,0 +1,266 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{SinkExt, Stream, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::codec::Framed;
+
+use crate::codec::{Framing, MessageCodec};
+use crate::protocol::{Message, MessagePayload, OpCode, features};
+use crate::tls::{TlsClientSettings, Transport};
+
+/// Nombre de messages en attente d'envoi avant que `send` ne patiente
+const OUTGOING_CAPACITY: usize = 100;
+
+/// Erreur d'une connexion cliente
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    /// Le serveur a répondu par une erreur du protocole
+    Rejected { code: u16, message: String },
+    /// La connexion est fermée
+    Closed,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "I/O error: {}", e),
+            ClientError::Rejected { code, message } => write!(f, "Error {}: {}", code, message),
+            ClientError::Closed => write!(f, "Connection closed"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+/// Connexion au serveur sans interface, utilisable par un bot, un test ou une interface
+///
+/// Une tâche de fond possède le socket: elle écrit les messages passés à `send`,
+/// répond elle-même aux `Ping` et transmet les autres messages reçus au `Stream`,
+/// qui se termine à la fermeture de la connexion. Le format de trames demandé par
+/// le `Connect` est appliqué dès la réception du `ConnectAck`; les trames illisibles
+/// sont ignorées.
+pub struct ClientConnection {
+    outgoing: mpsc::Sender<Message>,
+    incoming: mpsc::UnboundedReceiver<Message>,
+    task: JoinHandle<()>,
+}
+
+impl ClientConnection {
+    /// Ouvre une connexion TCP vers le serveur, chiffrée si `tls` est fourni
+    pub async fn open(server_addr: &str, tls: Option<&TlsClientSettings>) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(server_addr).await?;
+        let stream: Box<dyn Transport> = match tls {
+            Some(tls) => Box::new(tls.connector.connect(tls.server_name.clone(), stream).await?),
+            None => Box::new(stream),
+        };
+        Ok(Self::from_transport(stream))
+    }
+
+    /// Utilise un flux déjà établi
+    pub fn from_transport(stream: Box<dyn Transport>) -> Self {
+        let (outgoing, outgoing_rx) = mpsc::channel(OUTGOING_CAPACITY);
+        // Réception non bornée: la tâche ne doit jamais cesser d'écrire parce que
+        // le consommateur tarde à lire
+        let (incoming_tx, incoming) = mpsc::unbounded_channel();
+        let connection = Framed::new(stream, MessageCodec::new(Framing::Json));
+        let task = tokio::spawn(Self::run(connection, outgoing_rx, incoming_tx));
+        Self { outgoing, incoming, task }
+    }
+
+    /// Envoie un message au serveur
+    pub async fn send(&self, msg: Message) -> Result<(), ClientError> {
+        self.outgoing.send(msg).await.map_err(|_| ClientError::Closed)
+    }
+
+    /// Se présente avec toutes les fonctionnalités connues, crée le compte si
+    /// `register`, puis se connecte; renvoie les fonctionnalités retenues par le serveur
+    ///
+    /// Les messages qui suivent le `ConnectAck` (liste des utilisateurs, messages
+    /// en attente...) restent disponibles dans le `Stream`.
+    pub async fn login(&mut self, username: &str, password: &str, register: bool) -> Result<Vec<String>, ClientError> {
+        self.send(Message::hello(features::SUPPORTED.iter().map(|f| f.to_string()).collect())).await?;
+        let negotiated = match self.expect(OpCode::HelloAck).await?.payload {
+            MessagePayload::Hello { features, .. } => features,
+            _ => Vec::new(),
+        };
+
+        if register {
+            self.send(Message::register(username.to_string(), password.to_string())).await?;
+            self.expect(OpCode::RegisterAck).await?;
+        }
+        self.send(Message::connect(username.to_string(), password.to_string())).await?;
+        self.expect(OpCode::ConnectAck).await?;
+        Ok(negotiated)
+    }
+
+    /// Ferme la connexion après l'envoi des messages en attente
+    pub async fn close(self) {
+        let Self { outgoing, task, .. } = self;
+        drop(outgoing);
+        let _ = task.await;
+    }
+
+    /// Attend la réponse `expected`; une erreur du serveur est renvoyée telle quelle
+    async fn expect(&mut self, expected: OpCode) -> Result<Message, ClientError> {
+        while let Some(msg) = self.next().await {
+            if msg.op_code == expected {
+                return Ok(msg);
+            }
+            if let MessagePayload::Error { code, message } = msg.payload {
+                return Err(ClientError::Rejected { code, message });
+            }
+        }
+        Err(ClientError::Closed)
+    }
+
+    /// Tâche de fond: relaie les messages entre le socket et les canaux
+    async fn run(
+        mut connection: Framed<Box<dyn Transport>, MessageCodec>,
+        mut outgoing: mpsc::Receiver<Message>,
+        incoming: mpsc::UnboundedSender<Message>,
+    ) {
+        // Format demandé par le dernier Connect, adopté par le serveur après son ConnectAck
+        let mut requested_framing = Framing::Json;
+        // Un changement de format est en attente de réponse: rien n'est envoyé avant de savoir
+        // si les trames suivantes doivent partir dans l'ancien ou le nouveau format
+        let mut awaiting_ack = false;
+
+        loop {
+            tokio::select! {
+                msg = outgoing.recv(), if !awaiting_ack => {
+                    let Some(msg) = msg else { break }; // Connexion abandonnée par son propriétaire
+                    if let MessagePayload::Connect { framing, .. } = &msg.payload {
+                        requested_framing = *framing;
+                        awaiting_ack = *framing != connection.codec().framing();
+                    }
+                    if connection.send(&msg).await.is_err() {
+                        break;
+                    }
+                }
+
+                frame = connection.next() => {
+                    let msg = match frame {
+                        Some(Ok(Ok(msg))) => msg,
+                        Some(Ok(Err(_))) => continue,
+                        None | Some(Err(_)) => break,
+                    };
+                    match msg.op_code {
+                        OpCode::Ping => {
+                            if connection.send(&Message::pong()).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        OpCode::ConnectAck => {
+                            connection.codec_mut().set_framing(requested_framing);
+                            awaiting_ack = false;
+                        }
+                        // Connect refusé: le format ne change pas
+                        OpCode::Error => awaiting_ack = false,
+                        _ => {}
+                    }
+                    if incoming.send(msg).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Stream for ClientConnection {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Message>> {
+        self.incoming.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::protocol::error_codes;
+    use crate::server::MessageServer;
+
+    async fn spawn_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { MessageServer::new().serve(listener).await });
+        addr
+    }
+
+    /// Prochain message vérifiant `predicate`, en ignorant les autres
+    async fn next_matching(connection: &mut ClientConnection, predicate: impl Fn(&Message) -> bool) -> Message {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let msg = connection.next().await.expect("connection closed");
+                if predicate(&msg) {
+                    return msg;
+                }
+            }
+        })
+        .await
+        .expect("message not received")
+    }
+
+    #[tokio::test]
+    async fn test_login_send_and_receive() {
+        let addr = spawn_server().await;
+        let mut alice = ClientConnection::open(&addr, None).await.unwrap();
+        let negotiated = alice.login("Alice", "password123", true).await.unwrap();
+        assert!(negotiated.iter().any(|f| f == features::ROOMS));
+        let mut bob = ClientConnection::open(&addr, None).await.unwrap();
+        bob.login("Bob", "password123", true).await.unwrap();
+
+        alice.send(Message::private_message("Alice".to_string(), "Bob".to_string(), "Salut".to_string())).await.unwrap();
+        let received = next_matching(&mut bob, |msg| msg.recipient.is_some()).await;
+        assert_eq!(received.op_code, OpCode::MessageReceived);
+        assert_eq!(received.sender.as_deref(), Some("Alice"));
+        assert!(matches!(received.payload, MessagePayload::Text { ref content } if content == "Salut"));
+
+        bob.close().await;
+        let mut intruder = ClientConnection::open(&addr, None).await.unwrap();
+        let refused = intruder.login("Alice", "mauvais-mot-de-passe", false).await;
+        assert!(matches!(refused, Err(ClientError::Rejected { code: error_codes::UNAUTHORIZED, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_ping_answered_and_framing_switched() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut client = ClientConnection::open(&addr, None).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = Framed::new(stream, MessageCodec::new(Framing::Json));
+
+        // Le Ping reçoit sa réponse sans apparaître dans le flux
+        server.send(&Message::ping()).await.unwrap();
+        assert_eq!(server.next().await.unwrap().unwrap().unwrap().op_code, OpCode::Pong);
+
+        client.send(Message::connect("Alice".to_string(), "password123".to_string()).with_framing(Framing::Binary)).await.unwrap();
+        assert_eq!(server.next().await.unwrap().unwrap().unwrap().op_code, OpCode::Connect);
+        server.send(&Message::connect_ack()).await.unwrap();
+        server.codec_mut().set_framing(Framing::Binary);
+        server.send(&Message::user_list(Vec::new())).await.unwrap();
+
+        assert_eq!(client.next().await.unwrap().op_code, OpCode::ConnectAck);
+        assert_eq!(client.next().await.unwrap().op_code, OpCode::UserList);
+        drop(server);
+        assert!(client.next().await.is_none());
+    }
+}
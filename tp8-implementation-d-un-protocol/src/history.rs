@@ -0,0 +1,324 @@
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::protocol::{Message, OpCode};
+
+/// Nombre de messages renvoyés par défaut par une requête `History`
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+/// Nombre maximal de messages renvoyés par une requête `History`
+pub const MAX_HISTORY_LIMIT: usize = 200;
+
+/// Nombre de messages conservés par défaut; les plus anciens sont oubliés au-delà
+pub const DEFAULT_MAX_HISTORY_MESSAGES: usize = 10_000;
+
+/// Critères d'une requête d'historique
+#[derive(Debug, Clone, Default)]
+pub struct HistoryQuery {
+    /// Salon demandé (chat général et messages privés si absent)
+    pub room: Option<String>,
+    /// Nombre maximal de messages, les plus récents étant retenus
+    pub limit: Option<usize>,
+    /// Uniquement les messages postérieurs à cette date
+    pub since: Option<DateTime<Utc>>,
+    /// Uniquement les messages reçus après celui-ci
+    pub after: Option<Uuid>,
+}
+
+/// Historique des messages échangés, éventuellement conservé dans un fichier
+/// JSON en ajout seul (un message par ligne)
+///
+/// Les modifications et suppressions sont ajoutées au fichier comme les
+/// messages, et rejouées au chargement. Seuls les `max_messages` derniers
+/// messages sont gardés; le fichier est compacté au chargement.
+pub struct MessageHistory {
+    path: Option<PathBuf>,
+    messages: VecDeque<Message>,
+    max_messages: usize,
+}
+
+impl MessageHistory {
+    /// Crée un historique en mémoire, perdu à l'arrêt du serveur
+    pub fn in_memory(max_messages: usize) -> Self {
+        Self {
+            path: None,
+            messages: VecDeque::new(),
+            max_messages,
+        }
+    }
+
+    /// Charge l'historique depuis `path`; le fichier est créé au premier message
+    ///
+    /// S'il contient des corrections ou plus de `max_messages` messages, il est
+    /// réécrit avec les seuls messages conservés, déjà corrigés.
+    pub fn load(path: impl Into<PathBuf>, max_messages: usize) -> Result<Self, String> {
+        let path = path.into();
+        let recorded = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(index, line)| {
+                    Message::from_json(line).map_err(|e| format!("{}:{}: {}", path.display(), index + 1, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        let recorded_lines = recorded.len();
+        let mut history = Self {
+            path: Some(path),
+            messages: VecDeque::new(),
+            max_messages,
+        };
+        for msg in recorded {
+            history.apply(msg);
+        }
+        if history.messages.len() < recorded_lines {
+            history.compact()?;
+        }
+        Ok(history)
+    }
+
+    /// Nombre de messages conservés
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Indique si aucun message n'a encore été conservé
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Ajoute un message à la fin de l'historique
+    pub fn append(&mut self, msg: Message) -> Result<(), String> {
+        if let Some(path) = &self.path {
+            let line = msg.to_json().map_err(|e| e.to_string())?;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            writeln!(file, "{}", line).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        self.apply(msg);
+        Ok(())
+    }
+
+    // Réécrit le fichier avec les messages conservés, via un fichier temporaire
+    // renommé comme la liste des bannissements
+    fn compact(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut content = String::new();
+        for msg in &self.messages {
+            content.push_str(&msg.to_json().map_err(|e| e.to_string())?);
+            content.push('\n');
+        }
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content).map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+        fs::rename(&tmp_path, path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Message conservé portant cet identifiant
+    pub fn find(&self, id: Uuid) -> Option<&Message> {
+        self.messages.iter().find(|msg| msg.id == id)
+    }
+
+    fn apply(&mut self, msg: Message) {
+        match (&msg.op_code, msg.payload.corrected_id()) {
+            (OpCode::EditMessage, Some(id)) => {
+                if let Some(original) = self.messages.iter_mut().find(|original| original.id == id) {
+                    original.apply_edit(&msg);
+                }
+            }
+            (OpCode::DeleteMessage, Some(id)) => self.messages.retain(|original| original.id != id),
+            _ => {
+                self.messages.push_back(msg);
+                while self.messages.len() > self.max_messages {
+                    self.messages.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Messages visibles par `username` correspondant à la requête, du plus ancien au plus récent
+    ///
+    /// Sans salon, ce sont les messages du chat général et les messages privés
+    /// envoyés ou reçus par l'utilisateur. Un identifiant `after` inconnu est ignoré.
+    pub fn query(&self, username: &str, query: &HistoryQuery) -> Vec<Message> {
+        let start = query
+            .after
+            .and_then(|id| self.messages.iter().position(|msg| msg.id == id))
+            .map_or(0, |index| index + 1);
+        let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT).min(MAX_HISTORY_LIMIT);
+
+        let mut matching: Vec<Message> = self.messages
+            .range(start..)
+            .rev()
+            .filter(|msg| query.since.is_none_or(|since| msg.timestamp > since))
+            .filter(|msg| match &query.room {
+                Some(room) => msg.room.as_ref() == Some(room),
+                None => msg.room.is_none() && msg.recipient.as_ref().is_none_or(|recipient| {
+                    recipient == username || msg.sender.as_deref() == Some(username)
+                }),
+            })
+            .take(limit)
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+
+    /// Messages du chat général et des salons `rooms` reçus après `since` par
+    /// les autres utilisateurs, du plus ancien au plus récent
+    pub fn missed_since(&self, username: &str, rooms: &[String], since: DateTime<Utc>) -> Vec<Message> {
+        let mut missed: Vec<Message> = self.messages
+            .iter()
+            .rev()
+            .take_while(|msg| msg.timestamp > since)
+            .filter(|msg| msg.recipient.is_none() && msg.sender.as_deref() != Some(username))
+            .filter(|msg| msg.room.as_ref().is_none_or(|room| rooms.contains(room)))
+            .take(MAX_HISTORY_LIMIT)
+            .cloned()
+            .collect();
+        missed.reverse();
+        missed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::OpCode;
+
+    fn received(mut msg: Message) -> Message {
+        msg.op_code = OpCode::MessageReceived;
+        msg
+    }
+
+    #[test]
+    fn test_history_visibility_and_limit() {
+        let mut history = MessageHistory::in_memory(DEFAULT_MAX_HISTORY_MESSAGES);
+        history.append(received(Message::text_message("Alice".to_string(), "un".to_string()))).unwrap();
+        history.append(received(Message::private_message("Alice".to_string(), "Bob".to_string(), "secret".to_string()))).unwrap();
+        history.append(received(Message::room_message("Bob".to_string(), "rust".to_string(), "salon".to_string()))).unwrap();
+        history.append(received(Message::text_message("Bob".to_string(), "deux".to_string()))).unwrap();
+
+        // Les messages privés ne sont visibles que par leurs participants
+        assert_eq!(history.query("Carol", &HistoryQuery::default()).len(), 2);
+        assert_eq!(history.query("Bob", &HistoryQuery::default()).len(), 3);
+
+        let room = HistoryQuery { room: Some("rust".to_string()), ..Default::default() };
+        assert_eq!(history.query("Carol", &room).len(), 1);
+
+        // La limite garde les plus récents, dans l'ordre chronologique
+        let last = history.query("Alice", &HistoryQuery { limit: Some(2), ..Default::default() });
+        assert!(matches!(&last[1].payload, crate::protocol::MessagePayload::Text { content } if content == "deux"));
+        assert_eq!(last.len(), 2);
+    }
+
+    #[test]
+    fn test_history_since_and_after() {
+        let mut history = MessageHistory::in_memory(DEFAULT_MAX_HISTORY_MESSAGES);
+        let first = received(Message::text_message("Alice".to_string(), "un".to_string()));
+        let first_id = first.id;
+        let since = first.timestamp;
+        history.append(first).unwrap();
+        let mut second = received(Message::text_message("Bob".to_string(), "deux".to_string()));
+        second.timestamp = since + chrono::Duration::seconds(1);
+        history.append(second).unwrap();
+
+        let after = history.query("Carol", &HistoryQuery { after: Some(first_id), ..Default::default() });
+        assert_eq!(after.len(), 1);
+        let recent = history.query("Carol", &HistoryQuery { since: Some(since), ..Default::default() });
+        assert_eq!(recent.len(), 1);
+        assert_eq!(history.query("Carol", &HistoryQuery { after: Some(Uuid::new_v4()), ..Default::default() }).len(), 2);
+    }
+
+    #[test]
+    fn test_missed_since() {
+        let mut history = MessageHistory::in_memory(DEFAULT_MAX_HISTORY_MESSAGES);
+        history.append(received(Message::text_message("Bob".to_string(), "avant".to_string()))).unwrap();
+        let since = Utc::now();
+        for mut msg in [
+            Message::text_message("Bob".to_string(), "général".to_string()),
+            Message::text_message("Alice".to_string(), "le sien".to_string()),
+            Message::room_message("Bob".to_string(), "rust".to_string(), "salon".to_string()),
+            Message::room_message("Bob".to_string(), "go".to_string(), "autre salon".to_string()),
+            Message::private_message("Bob".to_string(), "Alice".to_string(), "privé".to_string()),
+        ] {
+            msg.timestamp = since + chrono::Duration::milliseconds(1);
+            history.append(received(msg)).unwrap();
+        }
+
+        // Les messages privés passent par la file hors ligne, pas par l'historique
+        let missed = history.missed_since("Alice", &["rust".to_string()], since);
+        assert_eq!(missed.len(), 2);
+        assert_eq!(missed[1].room.as_deref(), Some("rust"));
+    }
+
+    #[test]
+    fn test_history_persisted() {
+        let path = std::env::temp_dir().join(format!("tp8-history-{}.jsonl", Uuid::new_v4()));
+
+        let mut history = MessageHistory::load(&path, DEFAULT_MAX_HISTORY_MESSAGES).unwrap();
+        history.append(received(Message::text_message("Alice".to_string(), "bonjour".to_string()))).unwrap();
+        history.append(received(Message::text_message("Bob".to_string(), "salut".to_string()))).unwrap();
+
+        let reloaded = MessageHistory::load(&path, DEFAULT_MAX_HISTORY_MESSAGES).unwrap();
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.query("Carol", &HistoryQuery::default())[0].sender.as_deref(), Some("Alice"));
+
+        // Les corrections sont rejouées au chargement
+        let first = reloaded.query("Carol", &HistoryQuery::default())[0].id;
+        let second = reloaded.query("Carol", &HistoryQuery::default())[1].id;
+        let mut history = reloaded;
+        history.append(Message::edit_message("Alice".to_string(), first, "bonjour à tous".to_string())).unwrap();
+        history.append(Message::delete_message("Bob".to_string(), second)).unwrap();
+
+        let reloaded = MessageHistory::load(&path, DEFAULT_MAX_HISTORY_MESSAGES).unwrap();
+        assert_eq!(reloaded.len(), 1);
+        let edited = reloaded.find(first).unwrap();
+        assert!(matches!(&edited.payload, crate::protocol::MessagePayload::Text { content } if content == "bonjour à tous"));
+        assert!(edited.edited_at.is_some());
+        assert!(reloaded.find(second).is_none());
+
+        // Le fichier a été compacté: la correction et le message supprimé n'y figurent plus
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_history_capped() {
+        let path = std::env::temp_dir().join(format!("tp8-history-{}.jsonl", Uuid::new_v4()));
+
+        let mut history = MessageHistory::load(&path, 3).unwrap();
+        for content in ["un", "deux", "trois", "quatre", "cinq"] {
+            history.append(received(Message::text_message("Alice".to_string(), content.to_string()))).unwrap();
+        }
+
+        // Les plus anciens sont oubliés
+        assert_eq!(history.len(), 3);
+        let kept = history.query("Bob", &HistoryQuery::default());
+        assert!(matches!(&kept[0].payload, crate::protocol::MessagePayload::Text { content } if content == "trois"));
+
+        // Le fichier garde tout jusqu'au prochain chargement, qui le compacte
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 5);
+        let reloaded = MessageHistory::load(&path, 2).unwrap();
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,19 @@
+//! Protocole de messagerie personnalisé: serveur, client interactif et
+//! connexion sans interface (`connection::ClientConnection`) pour les bots et les tests
+
+pub mod accounts;
+pub mod codec;
+pub mod history;
+pub mod limits;
+pub mod moderation;
+pub mod offline;
+pub mod plugin;
+pub mod protocol;
+pub mod server;
+pub mod client;
+pub mod connection;
+pub mod tls;
+pub mod transfer;
+
+#[cfg(test)]
+mod tests;
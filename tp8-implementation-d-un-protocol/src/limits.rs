@@ -0,0 +1,87 @@
+use std::time::Instant;
+
+/// Nombre maximal de caractères d'un message texte, par défaut
+pub const DEFAULT_MAX_MESSAGE_LENGTH: usize = 4000;
+
+/// Nombre de messages qu'une session peut envoyer d'un coup, par défaut
+pub const DEFAULT_RATE_BURST: u32 = 20;
+
+/// Nombre de messages par seconde accordés à une session sur la durée, par défaut
+pub const DEFAULT_RATE_PER_SECOND: f64 = 5.0;
+
+/// Trames de transfert (morceaux et accusés) qu'une session peut envoyer d'un coup, par défaut
+pub const DEFAULT_TRANSFER_BURST: u32 = 100;
+
+/// Trames de transfert par seconde accordées à une session sur la durée, par défaut
+/// (environ 32 Mo/s avec des morceaux de 32 Kio)
+pub const DEFAULT_TRANSFER_PER_SECOND: f64 = 1000.0;
+
+/// Nombre de dépassements de limites au bout duquel la session est fermée, par défaut
+pub const DEFAULT_MAX_VIOLATIONS: u32 = 5;
+
+/// Seau à jetons: chaque message consomme un jeton, et les jetons se
+/// reconstituent à débit constant jusqu'à la capacité du seau
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Seau plein de `capacity` jetons, reconstitués à raison de `refill_per_second`
+    pub fn new(capacity: u32, refill_per_second: f64) -> Self {
+        Self {
+            capacity: f64::from(capacity),
+            refill_per_second,
+            tokens: f64::from(capacity),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Consomme un jeton s'il en reste un
+    pub fn try_acquire(&mut self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(3, 2.0);
+
+        // La rafale est acceptée jusqu'à la capacité du seau
+        assert!((0..3).all(|_| bucket.try_acquire_at(start)));
+        assert!(!bucket.try_acquire_at(start));
+
+        // Un jeton toutes les 500 ms
+        assert!(!bucket.try_acquire_at(start + Duration::from_millis(400)));
+        assert!(bucket.try_acquire_at(start + Duration::from_millis(600)));
+        assert!(!bucket.try_acquire_at(start + Duration::from_millis(600)));
+
+        // Une longue pause ne remplit pas le seau au-delà de sa capacité
+        let later = start + Duration::from_secs(60);
+        assert!((0..3).all(|_| bucket.try_acquire_at(later)));
+        assert!(!bucket.try_acquire_at(later));
+    }
+}
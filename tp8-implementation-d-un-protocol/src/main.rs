@@ -1,17 +1,26 @@
-mod protocol;
-mod server;
-mod client;
-
-#[cfg(test)]
-mod tests;
-
 use std::env;
 use std::io::{self, Write};
-use server::MessageServer;
-use client::MessageClient;
+use std::path::Path;
+use std::time::Duration;
+use tp8_implementation_d_un_protocol::accounts::AccountStore;
+use tp8_implementation_d_un_protocol::codec::Framing;
+use tp8_implementation_d_un_protocol::history::{DEFAULT_MAX_HISTORY_MESSAGES, MessageHistory};
+use tp8_implementation_d_un_protocol::moderation::BanList;
+use tp8_implementation_d_un_protocol::server::{MessageServer, ServerConfig};
+use tp8_implementation_d_un_protocol::client::{MessageClient, DEFAULT_DOWNLOAD_DIR};
+use tp8_implementation_d_un_protocol::tls::{self, TlsClientSettings};
 
 const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:8080";
 
+/// Fichier des comptes utilisateurs (surchargé par la variable ACCOUNTS_FILE)
+const DEFAULT_ACCOUNTS_FILE: &str = "accounts.json";
+
+/// Fichier de l'historique des messages (surchargé par la variable HISTORY_FILE)
+const DEFAULT_HISTORY_FILE: &str = "history.jsonl";
+
+/// Fichier des bannissements (surchargé par la variable BANS_FILE)
+const DEFAULT_BANS_FILE: &str = "bans.json";
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("🚀 TP 8: Implémentation d'un Protocole Personnalisé");
@@ -46,7 +55,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     run_server(&args[2]).await
                 }
                 "client" => {
-                    run_client(&args[2], DEFAULT_SERVER_ADDR).await
+                    run_client(&args[2], DEFAULT_SERVER_ADDR, false).await
+                }
+                "register" => {
+                    run_client(&args[2], DEFAULT_SERVER_ADDR, true).await
                 }
                 _ => {
                     print_usage();
@@ -57,7 +69,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         4 => {
             match args[1].as_str() {
                 "client" => {
-                    run_client(&args[2], &args[3]).await
+                    run_client(&args[2], &args[3], false).await
+                }
+                "register" => {
+                    run_client(&args[2], &args[3], true).await
                 }
                 _ => {
                     print_usage();
@@ -116,18 +131,152 @@ async fn run_interactive_mode() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 async fn run_server(addr: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let server = MessageServer::new();
+    let accounts_file = env::var("ACCOUNTS_FILE").unwrap_or_else(|_| DEFAULT_ACCOUNTS_FILE.to_string());
+    let history_file = env::var("HISTORY_FILE").unwrap_or_else(|_| DEFAULT_HISTORY_FILE.to_string());
+    let max_history_messages = match env::var("HISTORY_MAX_MESSAGES") {
+        Ok(max) => max.parse()?,
+        Err(_) => DEFAULT_MAX_HISTORY_MESSAGES,
+    };
+    let history = MessageHistory::load(&history_file, max_history_messages)?;
+    let bans_file = env::var("BANS_FILE").unwrap_or_else(|_| DEFAULT_BANS_FILE.to_string());
+    let bans = BanList::load(&bans_file)?;
+    let tls = server_tls()?;
+    let mut config = ServerConfig::default();
+    if let Ok(max_frame_size) = env::var("MAX_FRAME_SIZE") {
+        config.max_frame_size = max_frame_size.parse()?;
+    }
+    if let Ok(limit) = env::var("OFFLINE_QUEUE_LIMIT") {
+        config.offline_queue_limit = limit.parse()?;
+    }
+    if let Ok(ttl) = env::var("OFFLINE_MESSAGE_TTL") {
+        config.offline_message_ttl = chrono::Duration::seconds(ttl.parse()?);
+    }
+    if let Ok(interval) = env::var("PING_INTERVAL") {
+        config.ping_interval = Duration::from_secs(interval.parse()?);
+    }
+    if let Ok(timeout) = env::var("IDLE_TIMEOUT") {
+        config.idle_timeout = Duration::from_secs(timeout.parse()?);
+    }
+    if let Ok(away) = env::var("AWAY_AFTER") {
+        config.away_after = Duration::from_secs(away.parse()?);
+    }
+    if let Ok(window) = env::var("RESUME_WINDOW") {
+        config.resume_window = chrono::Duration::seconds(window.parse()?);
+    }
+    if let Ok(admins) = env::var("ADMINS") {
+        config.admins = admins.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect();
+    }
+    if config.ping_interval.is_zero() || config.idle_timeout.is_zero() || config.away_after.is_zero() {
+        return Err("PING_INTERVAL, IDLE_TIMEOUT et AWAY_AFTER doivent être strictement positifs".into());
+    }
+    if let Ok(length) = env::var("MAX_MESSAGE_LENGTH") {
+        config.max_message_length = length.parse()?;
+    }
+    if let Ok(burst) = env::var("RATE_BURST") {
+        config.rate_burst = burst.parse()?;
+    }
+    if let Ok(rate) = env::var("RATE_LIMIT") {
+        config.rate_per_second = rate.parse()?;
+    }
+    if let Ok(burst) = env::var("TRANSFER_BURST") {
+        config.transfer_burst = burst.parse()?;
+    }
+    if let Ok(rate) = env::var("TRANSFER_RATE_LIMIT") {
+        config.transfer_per_second = rate.parse()?;
+    }
+    if let Ok(violations) = env::var("MAX_VIOLATIONS") {
+        config.max_violations = violations.parse()?;
+    }
+    if config.rate_burst == 0 || config.rate_per_second.is_nan() || config.rate_per_second <= 0.0 || config.max_violations == 0 {
+        return Err("RATE_BURST, RATE_LIMIT et MAX_VIOLATIONS doivent être strictement positifs".into());
+    }
+    if config.transfer_burst == 0 || config.transfer_per_second.is_nan() || config.transfer_per_second <= 0.0 {
+        return Err("TRANSFER_BURST et TRANSFER_RATE_LIMIT doivent être strictement positifs".into());
+    }
     
     println!("🔧 Configuration du serveur:");
     println!("  • Adresse: {}", addr);
-    println!("  • Protocole: TCP");
-    println!("  • Format: JSON");
+    println!("  • Comptes: {} (mots de passe Argon2)", accounts_file);
+    println!("  • Historique: {} ({} messages)", history_file, history.len());
+    println!("  • Protocole: {}", if tls.is_some() { "TCP + TLS" } else { "TCP" });
+    if env::var("TLS_CLIENT_CA").is_ok() {
+        println!("  • Certificat client exigé (TLS mutuel)");
+    }
+    println!("  • Format: JSON, ou binaire (longueur u32 + MessagePack) à la demande du client");
+    println!("  • Taille maximale d'une trame: {} octets", config.max_frame_size);
+    println!(
+        "  • Messages hors ligne: {} par utilisateur, conservés {} h",
+        config.offline_queue_limit,
+        config.offline_message_ttl.num_hours()
+    );
+    println!(
+        "  • Inactivité: Ping après {} s, fermeture après {} s",
+        config.ping_interval.as_secs(),
+        config.idle_timeout.as_secs()
+    );
+    println!("  • Absence automatique après {} s sans action de l'utilisateur", config.away_after.as_secs());
+    println!("  • Reprise de session: {} s après une coupure", config.resume_window.num_seconds());
+    println!(
+        "  • Limites: {} caractères par message, {} messages/s (rafales de {}), fermeture après {} dépassements",
+        config.max_message_length,
+        config.rate_per_second,
+        config.rate_burst,
+        config.max_violations
+    );
+    println!(
+        "  • Transferts: {} trames/s (rafales de {})",
+        config.transfer_per_second,
+        config.transfer_burst
+    );
+    let mut admins: Vec<&String> = config.admins.iter().collect();
+    admins.sort();
+    println!("  • Administrateurs: {:?}", admins);
+    println!("  • Bannissements: {} ({} en cours)", bans_file, bans.active());
     println!("  • Gestion des états: Sessions multiples");
     println!();
     
+    let mut server = MessageServer::new()
+        .with_accounts(AccountStore::load(&accounts_file)?)
+        .with_history(history)
+        .with_bans(bans)
+        .with_config(config);
+    if let Some(acceptor) = tls {
+        server = server.with_tls(acceptor);
+    }
     server.start(addr).await
 }
 
+/// TLS du serveur: TLS_CERT et TLS_KEY (PEM), et TLS_CLIENT_CA pour exiger un certificat client
+fn server_tls() -> Result<Option<tokio_rustls::TlsAcceptor>, Box<dyn std::error::Error>> {
+    let (cert, key) = match (env::var("TLS_CERT"), env::var("TLS_KEY")) {
+        (Ok(cert), Ok(key)) => (cert, key),
+        (Err(_), Err(_)) => return Ok(None),
+        _ => return Err("TLS_CERT et TLS_KEY doivent être définis ensemble".into()),
+    };
+    let client_ca = env::var("TLS_CLIENT_CA").ok();
+    let acceptor = tls::server_acceptor(Path::new(&cert), Path::new(&key), client_ca.as_deref().map(Path::new))?;
+    Ok(Some(acceptor))
+}
+
+/// TLS du client: TLS_CA (autorité du serveur), TLS_CLIENT_CERT et TLS_CLIENT_KEY pour le
+/// TLS mutuel, TLS_SERVER_NAME si le certificat ne porte pas l'hôte de l'adresse
+fn client_tls(server_addr: &str) -> Result<Option<TlsClientSettings>, Box<dyn std::error::Error>> {
+    let Ok(ca) = env::var("TLS_CA") else {
+        return Ok(None);
+    };
+    let identity = match (env::var("TLS_CLIENT_CERT"), env::var("TLS_CLIENT_KEY")) {
+        (Ok(cert), Ok(key)) => Some((cert, key)),
+        (Err(_), Err(_)) => None,
+        _ => return Err("TLS_CLIENT_CERT et TLS_CLIENT_KEY doivent être définis ensemble".into()),
+    };
+    let connector = tls::client_connector(
+        Path::new(&ca),
+        identity.as_ref().map(|(cert, key)| (Path::new(cert), Path::new(key))),
+    )?;
+    let server_name = tls::server_name(&env::var("TLS_SERVER_NAME").unwrap_or_else(|_| server_addr.to_string()))?;
+    Ok(Some(TlsClientSettings { connector, server_name }))
+}
+
 async fn run_client_interactive() -> Result<(), Box<dyn std::error::Error>> {
     print!("Nom d'utilisateur: ");
     io::stdout().flush()?;
@@ -152,18 +301,49 @@ async fn run_client_interactive() -> Result<(), Box<dyn std::error::Error>> {
         server_addr.trim()
     };
     
-    run_client(&username, server_addr).await
+    print!("Créer un nouveau compte ? (o/N): ");
+    io::stdout().flush()?;
+    
+    let mut register = String::new();
+    io::stdin().read_line(&mut register)?;
+    let register = matches!(register.trim(), "o" | "O" | "oui");
+    
+    run_client(&username, server_addr, register).await
 }
 
-async fn run_client(username: &str, server_addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn run_client(username: &str, server_addr: &str, register: bool) -> Result<(), Box<dyn std::error::Error>> {
+    print!("Mot de passe: ");
+    io::stdout().flush()?;
+    
+    let mut password = String::new();
+    io::stdin().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    
+    // Format binaire sur demande: MESSAGE_FRAMING=binary
+    let framing = match env::var("MESSAGE_FRAMING").as_deref() {
+        Ok("binary") => Framing::Binary,
+        _ => Framing::Json,
+    };
+    let tls = client_tls(server_addr)?;
+    let download_dir = env::var("DOWNLOAD_DIR").unwrap_or_else(|_| DEFAULT_DOWNLOAD_DIR.to_string());
+    
     println!("\n🔧 Configuration du client:");
     println!("  • Utilisateur: {}", username);
     println!("  • Serveur: {}", server_addr);
-    println!("  • Protocole: TCP");
-    println!("  • Format: JSON");
+    println!("  • Protocole: {}", if tls.is_some() { "TCP + TLS" } else { "TCP" });
+    println!("  • Format: {}", framing);
+    println!("  • Fichiers reçus: {}", download_dir);
     println!();
     
-    let mut client = MessageClient::new(username.to_string());
+    let mut client = MessageClient::new(username.to_string(), password)
+        .with_framing(framing)
+        .with_download_dir(download_dir);
+    if register {
+        client = client.with_registration();
+    }
+    if let Some(settings) = tls {
+        client = client.with_tls(settings);
+    }
     client.connect_and_run(server_addr).await
 }
 
@@ -174,6 +354,7 @@ fn print_usage() {
     println!("Modes:");
     println!("  server [ADDRESS]              - Lance le serveur (défaut: {})", DEFAULT_SERVER_ADDR);
     println!("  client [USERNAME] [ADDRESS]   - Lance le client");
+    println!("  register [USERNAME] [ADDRESS] - Crée un compte puis lance le client");
     println!("  (sans arguments)              - Mode interactif");
     println!();
     println!("Exemples:");
@@ -181,4 +362,5 @@ fn print_usage() {
     println!("  {} server 0.0.0.0:9999       - Serveur sur port 9999", env::args().next().unwrap());
     println!("  {} client Alice               - Client 'Alice' vers {}", env::args().next().unwrap(), DEFAULT_SERVER_ADDR);
     println!("  {} client Bob 192.168.1.100:8080 - Client 'Bob' vers serveur distant", env::args().next().unwrap());
+    println!("  {} register Carol             - Crée le compte 'Carol' et se connecte", env::args().next().unwrap());
 }
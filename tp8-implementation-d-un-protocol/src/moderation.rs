@@ -0,0 +1,188 @@
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Sanction prononcée par un administrateur
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sanction {
+    /// Administrateur à l'origine de la sanction
+    pub by: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Fin de la sanction (définitive si absente)
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Sanction {
+    /// Sanction de `by`, levée après `duration` secondes si elle est fournie
+    /// (une durée trop grande pour être datée rend la sanction définitive)
+    pub fn new(by: String, reason: Option<String>, duration: Option<u64>) -> Self {
+        let until = duration
+            .and_then(|seconds| chrono::Duration::try_seconds(i64::try_from(seconds).ok()?))
+            .and_then(|duration| Utc::now().checked_add_signed(duration));
+        Self { by, reason, until }
+    }
+
+    /// Indique si la sanction s'applique encore
+    pub fn is_active(&self) -> bool {
+        self.until.is_none_or(|until| Utc::now() < until)
+    }
+}
+
+impl fmt::Display for Sanction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "by {}", self.by)?;
+        if let Some(until) = self.until {
+            write!(f, " until {}", until.format("%Y-%m-%d %H:%M:%S UTC"))?;
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Bannissement d'un nom d'utilisateur, d'une adresse IP ou des deux
+/// (l'adresse ne vise alors que ce nom depuis cette adresse)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub ip: Option<IpAddr>,
+    #[serde(flatten)]
+    pub sanction: Sanction,
+}
+
+/// Liste des bannissements, éventuellement sauvegardée dans un fichier JSON
+pub struct BanList {
+    path: Option<PathBuf>,
+    bans: Vec<Ban>,
+}
+
+impl BanList {
+    /// Crée une liste en mémoire, perdue à l'arrêt du serveur
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            bans: Vec::new(),
+        }
+    }
+
+    /// Charge les bannissements depuis `path`; le fichier est créé au premier bannissement
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let bans = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            bans,
+        })
+    }
+
+    /// Nombre de bannissements en cours
+    pub fn active(&self) -> usize {
+        self.bans.iter().filter(|ban| ban.sanction.is_active()).count()
+    }
+
+    /// Ajoute un bannissement, retire ceux qui ont expiré, puis sauvegarde la liste
+    pub fn add(&mut self, ban: Ban) -> Result<(), String> {
+        let previous = self.bans.clone();
+        self.bans.retain(|ban| ban.sanction.is_active());
+        self.bans.push(ban);
+        if let Err(e) = self.save() {
+            self.bans = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Bannissement en cours visant ce nom d'utilisateur ou cette adresse; une adresse
+    /// associée à un nom ne compte qu'avec ce nom, pour ne pas bloquer ses voisins (NAT)
+    pub fn find(&self, username: Option<&str>, ip: Option<IpAddr>) -> Option<&Ban> {
+        self.bans.iter().filter(|ban| ban.sanction.is_active()).find(|ban| {
+            match (ban.username.as_deref(), ban.ip) {
+                (Some(banned), Some(banned_ip)) => username == Some(banned) && ip == Some(banned_ip),
+                (Some(banned), None) => username == Some(banned),
+                (None, Some(banned_ip)) => ip == Some(banned_ip),
+                (None, None) => false,
+            }
+        })
+    }
+
+    // Écrit dans un fichier temporaire puis le renomme, comme l'annuaire des comptes
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let json = serde_json::to_string_pretty(&self.bans).map_err(|e| e.to_string())?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json).map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+        fs::rename(&tmp_path, path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ban(username: Option<&str>, ip: Option<&str>, duration: Option<u64>) -> Ban {
+        Ban {
+            username: username.map(str::to_string),
+            ip: ip.map(|ip| ip.parse().unwrap()),
+            sanction: Sanction::new("Admin".to_string(), Some("spam".to_string()), duration),
+        }
+    }
+
+    #[test]
+    fn test_ban_by_username_and_ip() {
+        let mut bans = BanList::in_memory();
+        bans.add(ban(Some("Bob"), None, None)).unwrap();
+        bans.add(ban(None, Some("10.0.0.2"), None)).unwrap();
+        bans.add(ban(Some("Dave"), Some("10.0.0.4"), None)).unwrap();
+
+        assert!(bans.find(Some("Bob"), None).is_some());
+        assert!(bans.find(Some("Carol"), Some("10.0.0.2".parse().unwrap())).is_some());
+        assert!(bans.find(Some("Carol"), Some("10.0.0.3".parse().unwrap())).is_none());
+        assert!(bans.find(None, None).is_none());
+
+        // Une adresse associée à un nom ne bloque pas les autres utilisateurs de cette adresse
+        assert!(bans.find(Some("Dave"), Some("10.0.0.4".parse().unwrap())).is_some());
+        assert!(bans.find(Some("Carol"), Some("10.0.0.4".parse().unwrap())).is_none());
+        assert!(bans.find(None, Some("10.0.0.4".parse().unwrap())).is_none());
+
+        // Une sanction expirée ne compte plus
+        let mut expired = ban(Some("Carol"), None, Some(60));
+        expired.sanction.until = Some(Utc::now() - chrono::Duration::seconds(1));
+        bans.add(expired).unwrap();
+        assert!(bans.find(Some("Carol"), None).is_none());
+        assert_eq!(bans.active(), 3);
+    }
+
+    #[test]
+    fn test_bans_persisted() {
+        let path = std::env::temp_dir().join(format!("tp8-bans-{}.json", uuid::Uuid::new_v4()));
+
+        let mut bans = BanList::load(&path).unwrap();
+        bans.add(ban(Some("Bob"), Some("::1"), Some(3600))).unwrap();
+
+        let reloaded = BanList::load(&path).unwrap();
+        let found = reloaded.find(Some("Bob"), Some("::1".parse().unwrap())).unwrap();
+        assert_eq!(found.username.as_deref(), Some("Bob"));
+        assert_eq!(found.sanction.to_string().split(" until ").next(), Some("by Admin"));
+        assert!(found.sanction.to_string().ends_with(": spam"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
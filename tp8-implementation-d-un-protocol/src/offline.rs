@@ -0,0 +1,133 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{Duration, Utc};
+
+use crate::protocol::{Message, OpCode};
+
+/// Nombre maximal de messages en attente par utilisateur, par défaut
+pub const DEFAULT_OFFLINE_QUEUE_LIMIT: usize = 100;
+
+/// Durée de conservation d'un message en attente, par défaut (7 jours)
+pub const DEFAULT_OFFLINE_MESSAGE_TTL: Duration = Duration::days(7);
+
+/// Messages privés adressés à des utilisateurs déconnectés, remis dans
+/// l'ordre d'envoi à leur prochaine connexion
+pub struct OfflineQueue {
+    queues: HashMap<String, VecDeque<Message>>,
+    limit: usize,
+    ttl: Duration,
+}
+
+impl OfflineQueue {
+    pub fn new(limit: usize, ttl: Duration) -> Self {
+        Self {
+            queues: HashMap::new(),
+            limit,
+            ttl,
+        }
+    }
+
+    /// Met un message en attente pour `recipient`; échoue si sa file est pleine
+    pub fn push(&mut self, recipient: &str, msg: Message) -> Result<(), String> {
+        let queue = self.queues.entry(recipient.to_string()).or_default();
+        let expired_before = Utc::now() - self.ttl;
+        queue.retain(|queued| queued.timestamp > expired_before);
+
+        if queue.len() >= self.limit {
+            return Err(format!(
+                "Offline queue of '{}' is full ({} messages)",
+                recipient, self.limit
+            ));
+        }
+        queue.push_back(msg);
+        Ok(())
+    }
+
+    /// Applique une modification ou une suppression au message en attente
+    /// qu'elle vise, s'il n'a pas encore été remis à `recipient`
+    pub fn correct(&mut self, recipient: &str, correction: &Message) {
+        let (Some(queue), Some(id)) = (self.queues.get_mut(recipient), correction.payload.corrected_id()) else {
+            return;
+        };
+        if correction.op_code == OpCode::DeleteMessage {
+            queue.retain(|queued| queued.id != id);
+        } else if let Some(queued) = queue.iter_mut().find(|queued| queued.id == id) {
+            queued.apply_edit(correction);
+        }
+    }
+
+    /// Retire les messages en attente de `username`, sans ceux qui ont expiré
+    pub fn take(&mut self, username: &str) -> Vec<Message> {
+        let expired_before = Utc::now() - self.ttl;
+        self.queues
+            .remove(username)
+            .map(|queue| queue.into_iter().filter(|msg| msg.timestamp > expired_before).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for OfflineQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_OFFLINE_QUEUE_LIMIT, DEFAULT_OFFLINE_MESSAGE_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::MessagePayload;
+
+    fn private(content: &str) -> Message {
+        Message::private_message("Alice".to_string(), "Bob".to_string(), content.to_string())
+    }
+
+    #[test]
+    fn test_offline_queue_order_and_limit() {
+        let mut queue = OfflineQueue::new(2, Duration::hours(1));
+        queue.push("Bob", private("un")).unwrap();
+        queue.push("Bob", private("deux")).unwrap();
+        assert!(queue.push("Bob", private("trois")).is_err());
+
+        let pending = queue.take("Bob");
+        let contents: Vec<_> = pending
+            .iter()
+            .map(|msg| match &msg.payload {
+                MessagePayload::Text { content } => content.as_str(),
+                _ => "",
+            })
+            .collect();
+        assert_eq!(contents, vec!["un", "deux"]);
+        assert!(queue.take("Bob").is_empty());
+    }
+
+    #[test]
+    fn test_offline_queue_expiry() {
+        let mut queue = OfflineQueue::new(1, Duration::hours(1));
+        let mut old = private("ancien");
+        old.timestamp = Utc::now() - Duration::hours(2);
+        queue.push("Bob", old).unwrap();
+
+        // Le message expiré libère sa place dans la file
+        queue.push("Bob", private("récent")).unwrap();
+        assert_eq!(queue.take("Bob").len(), 1);
+    }
+
+    #[test]
+    fn test_offline_queue_corrections() {
+        let mut queue = OfflineQueue::default();
+        let (first, second) = (private("Bonjuor"), private("oups"));
+        let (first_id, second_id) = (first.id, second.id);
+        queue.push("Bob", first).unwrap();
+        queue.push("Bob", second).unwrap();
+
+        queue.correct("Bob", &Message::edit_message("Alice".to_string(), first_id, "Bonjour".to_string()));
+        queue.correct("Bob", &Message::delete_message("Alice".to_string(), second_id));
+        queue.correct("Carol", &Message::delete_message("Alice".to_string(), first_id));
+
+        // Bob ne reçoit que la version corrigée
+        let pending = queue.take("Bob");
+        assert_eq!(pending.len(), 1);
+        assert!(matches!(&pending[0].payload, MessagePayload::Text { content } if content == "Bonjour"));
+        assert!(pending[0].edited_at.is_some());
+    }
+}
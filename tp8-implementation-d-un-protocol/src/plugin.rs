@@ -0,0 +1,267 @@
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use crate::protocol::{Message, MessagePayload, OpCode};
+
+/// Bot hébergé par le serveur: il observe les messages et les connexions, et
+/// répond aux commandes (`/deploy status`) que le serveur ne relaie pas
+///
+/// Les méthodes sont appelées depuis les tâches des clients et ne doivent pas
+/// bloquer; un traitement différé (rappel, appel réseau) passe par `tokio::spawn`
+/// avec une copie du `PluginContext`.
+pub trait Plugin: Send + Sync {
+    /// Nom sous lequel le plugin s'exprime, réservé aux comptes utilisateurs
+    fn name(&self) -> &str;
+
+    /// Commande `/command args` envoyée par un utilisateur; `true` si le plugin l'a traitée
+    fn on_command(&self, _command: &str, _args: &str, _msg: &Message, _ctx: &PluginContext) -> bool {
+        false
+    }
+
+    /// Message relayé par le serveur (chat général, salon ou privé, y compris ceux adressés au plugin)
+    fn on_message(&self, _msg: &Message, _ctx: &PluginContext) {}
+
+    /// Utilisateur connecté
+    fn on_connect(&self, _username: &str, _ctx: &PluginContext) {}
+
+    /// Utilisateur déconnecté
+    fn on_disconnect(&self, _username: &str, _ctx: &PluginContext) {}
+}
+
+/// Moyen d'expression d'un plugin: ses messages sont diffusés avec son nom comme expéditeur
+///
+/// Ils ne sont pas conservés dans l'historique ni mis en attente pour les
+/// utilisateurs déconnectés.
+#[derive(Clone)]
+pub struct PluginContext {
+    name: String,
+    broadcast_tx: broadcast::Sender<Message>,
+}
+
+impl PluginContext {
+    /// Message au chat général
+    pub fn say(&self, content: &str) {
+        self.send(Message::text_message(self.name.clone(), content.to_string()));
+    }
+
+    /// Message aux membres d'un salon
+    pub fn say_in(&self, room: &str, content: &str) {
+        self.send(Message::room_message(self.name.clone(), room.to_string(), content.to_string()));
+    }
+
+    /// Réponse privée à l'auteur de `msg`
+    pub fn reply(&self, msg: &Message, content: &str) {
+        if let Some(sender) = &msg.sender {
+            self.send(Message::private_message(self.name.clone(), sender.clone(), content.to_string()));
+        }
+    }
+
+    /// Diffuse un message, avec le plugin pour expéditeur
+    pub fn send(&self, mut msg: Message) {
+        msg.op_code = OpCode::MessageReceived;
+        msg.sender = Some(self.name.clone());
+        let _ = self.broadcast_tx.send(msg);
+    }
+}
+
+/// Plugins enregistrés au démarrage du serveur
+#[derive(Clone, Default)]
+pub struct Plugins {
+    plugins: Vec<Arc<dyn Plugin>>,
+}
+
+impl Plugins {
+    pub fn register(&mut self, plugin: impl Plugin + 'static) {
+        self.plugins.push(Arc::new(plugin));
+    }
+
+    /// Noms des plugins, dans l'ordre d'enregistrement
+    pub fn names(&self) -> Vec<&str> {
+        self.plugins.iter().map(|plugin| plugin.name()).collect()
+    }
+
+    /// Indique si `name` est celui d'un plugin (sans tenir compte de la casse)
+    pub fn is_plugin(&self, name: &str) -> bool {
+        self.plugins.iter().any(|plugin| plugin.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Propose la commande contenue dans `msg` aux plugins; `None` si ce n'est pas une commande,
+    /// sinon `Some(true)` dès qu'un plugin l'a traitée
+    pub fn command(&self, msg: &Message, broadcast_tx: &broadcast::Sender<Message>) -> Option<bool> {
+        let MessagePayload::Text { content } = &msg.payload else {
+            return None;
+        };
+        let line = content.strip_prefix('/')?;
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        Some(self.plugins.iter().any(|plugin| {
+            plugin.on_command(command, args.trim(), msg, &Self::context(plugin, broadcast_tx))
+        }))
+    }
+
+    pub fn message(&self, msg: &Message, broadcast_tx: &broadcast::Sender<Message>) {
+        for plugin in &self.plugins {
+            plugin.on_message(msg, &Self::context(plugin, broadcast_tx));
+        }
+    }
+
+    pub fn connected(&self, username: &str, broadcast_tx: &broadcast::Sender<Message>) {
+        for plugin in &self.plugins {
+            plugin.on_connect(username, &Self::context(plugin, broadcast_tx));
+        }
+    }
+
+    pub fn disconnected(&self, username: &str, broadcast_tx: &broadcast::Sender<Message>) {
+        for plugin in &self.plugins {
+            plugin.on_disconnect(username, &Self::context(plugin, broadcast_tx));
+        }
+    }
+
+    fn context(plugin: &Arc<dyn Plugin>, broadcast_tx: &broadcast::Sender<Message>) -> PluginContext {
+        PluginContext {
+            name: plugin.name().to_string(),
+            broadcast_tx: broadcast_tx.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::connection::{ClientConnection, ClientError};
+    use crate::protocol::error_codes;
+    use crate::server::MessageServer;
+
+    /// Renvoie le texte des commandes `/echo` et des messages privés reçus, et accueille les arrivants
+    struct EchoBot;
+
+    impl Plugin for EchoBot {
+        fn name(&self) -> &str {
+            "EchoBot"
+        }
+
+        fn on_command(&self, command: &str, args: &str, msg: &Message, ctx: &PluginContext) -> bool {
+            if command != "echo" {
+                return false;
+            }
+            ctx.reply(msg, args);
+            true
+        }
+
+        fn on_message(&self, msg: &Message, ctx: &PluginContext) {
+            if msg.recipient.as_deref() == Some(self.name()) {
+                if let MessagePayload::Text { content } = &msg.payload {
+                    ctx.reply(msg, &format!("écho: {}", content));
+                }
+            }
+        }
+
+        fn on_connect(&self, username: &str, ctx: &PluginContext) {
+            ctx.say(&format!("Bienvenue {}", username));
+        }
+    }
+
+    /// `/remind <millisecondes> <texte>`: rappelle le texte à son auteur après le délai
+    struct ReminderBot;
+
+    impl Plugin for ReminderBot {
+        fn name(&self) -> &str {
+            "Rappel"
+        }
+
+        fn on_command(&self, command: &str, args: &str, msg: &Message, ctx: &PluginContext) -> bool {
+            if command != "remind" {
+                return false;
+            }
+            match args.split_once(' ').and_then(|(delay, text)| Some((delay.parse().ok()?, text))) {
+                Some((delay, text)) => {
+                    let (ctx, msg, text) = (ctx.clone(), msg.clone(), text.to_string());
+                    tokio::spawn(async move {
+                        tokio::time::sleep(Duration::from_millis(delay)).await;
+                        ctx.reply(&msg, &format!("⏰ {}", text));
+                    });
+                }
+                None => ctx.reply(msg, "Usage: /remind <millisecondes> <texte>"),
+            }
+            true
+        }
+    }
+
+    async fn spawn_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = MessageServer::new().with_plugin(EchoBot).with_plugin(ReminderBot);
+        tokio::spawn(async move { server.serve(listener).await });
+        addr
+    }
+
+    async fn login(addr: &str, username: &str) -> ClientConnection {
+        let mut connection = ClientConnection::open(addr, None).await.unwrap();
+        connection.login(username, "password123", true).await.unwrap();
+        connection
+    }
+
+    /// Prochain message envoyé par `sender`, en ignorant les autres
+    async fn next_from(connection: &mut ClientConnection, sender: &str) -> Message {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let msg = connection.next().await.expect("connection closed");
+                if msg.sender.as_deref() == Some(sender) || msg.op_code == OpCode::Error {
+                    return msg;
+                }
+            }
+        })
+        .await
+        .expect("message not received")
+    }
+
+    fn text_of(msg: &Message) -> &str {
+        match &msg.payload {
+            MessagePayload::Text { content } => content,
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bot_commands_and_events() {
+        let addr = spawn_server().await;
+        let mut alice = login(&addr, "Alice").await;
+        assert_eq!(text_of(&next_from(&mut alice, "EchoBot").await), "Bienvenue Alice");
+        let mut bob = login(&addr, "Bob").await;
+        assert_eq!(text_of(&next_from(&mut alice, "EchoBot").await), "Bienvenue Bob");
+
+        // La commande n'est pas relayée: seule la réponse privée arrive
+        alice.send(Message::text_message("Alice".to_string(), "/echo bonjour".to_string())).await.unwrap();
+        let reply = next_from(&mut alice, "EchoBot").await;
+        assert_eq!(reply.recipient.as_deref(), Some("Alice"));
+        assert_eq!(text_of(&reply), "bonjour");
+
+        alice.send(Message::private_message("Alice".to_string(), "EchoBot".to_string(), "ça va ?".to_string())).await.unwrap();
+        assert_eq!(text_of(&next_from(&mut alice, "EchoBot").await), "écho: ça va ?");
+
+        alice.send(Message::text_message("Alice".to_string(), "/remind 50 pause café".to_string())).await.unwrap();
+        assert_eq!(text_of(&next_from(&mut alice, "Rappel").await), "⏰ pause café");
+
+        alice.send(Message::text_message("Alice".to_string(), "/inconnue".to_string())).await.unwrap();
+        let error = next_from(&mut alice, "EchoBot").await;
+        assert!(matches!(error.payload, MessagePayload::Error { code: error_codes::INVALID_MESSAGE, .. }));
+
+        // Bob n'a vu ni les commandes d'Alice ni les réponses qui lui étaient destinées
+        alice.send(Message::text_message("Alice".to_string(), "fin".to_string())).await.unwrap();
+        let next = next_from(&mut bob, "Alice").await;
+        assert_eq!(text_of(&next), "fin");
+    }
+
+    #[tokio::test]
+    async fn test_bot_name_reserved() {
+        let addr = spawn_server().await;
+        let mut connection = ClientConnection::open(&addr, None).await.unwrap();
+        let refused = connection.login("echobot", "password123", true).await;
+        assert!(matches!(refused, Err(ClientError::Rejected { code: error_codes::USER_EXISTS, .. })));
+    }
+}
@@ -1,21 +1,66 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+use crate::codec::Framing;
+
 /// Code d'opération pour définir le type de message
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum OpCode {
     // Messages du client vers le serveur
+    Hello,          // Annonce de la version et des fonctionnalités (premier message)
+    Register,       // Création d'un compte
     Connect,        // Demande de connexion
     Disconnect,     // Demande de déconnexion
     SendMessage,    // Envoi d'un message
     ListUsers,      // Demande la liste des utilisateurs connectés
+    JoinRoom,       // Rejoint un salon (créé s'il n'existe pas)
+    LeaveRoom,      // Quitte un salon
+    ListRooms,      // Demande la liste des salons
+    History,        // Demande les derniers messages échangés
+    
+    // Modération, réservée aux administrateurs
+    Kick,           // Ferme la session d'un utilisateur
+    Ban,            // Bannit un utilisateur (et son adresse IP) et ferme sa session
+    Mute,           // Interdit à un utilisateur d'envoyer des messages
+    
+    // Accusés relayés par le serveur jusqu'à l'expéditeur du message d'origine
+    Delivered,      // Message reçu par le destinataire
+    Read,           // Message lu par le destinataire
+    
+    // Transfert de fichiers, relayé par le serveur entre deux utilisateurs connectés
+    FileOffer,      // Proposition d'envoi d'un fichier
+    FileAccept,     // Acceptation, renvoyée après chaque morceau reçu avec la position atteinte
+    FileReject,     // Refus ou abandon du transfert
+    FileChunk,      // Morceau numéroté du fichier
+    FileComplete,   // Fin du transfert, avec l'empreinte SHA-256 du fichier
+    
+    // Correction d'un message déjà envoyé, par son auteur ou un administrateur
+    EditMessage,    // Remplace le texte du message
+    DeleteMessage,  // Retire le message
+    
+    // Présence, relayée aux clients qui l'ont négociée
+    Presence,       // Statut de l'utilisateur (en ligne, absent, occupé) et texte libre
+    Typing,         // Saisie en cours, éphémère (ni historique ni mise en attente)
+    
+    // Dans les deux sens
+    Ping,           // Vérifie que le pair est toujours là
+    Pong,           // Réponse à un Ping
     
     // Messages du serveur vers le client
+    HelloAck,       // Version du serveur et fonctionnalités retenues
+    RegisterAck,    // Confirmation de création du compte
     ConnectAck,     // Confirmation de connexion
     DisconnectAck,  // Confirmation de déconnexion
     MessageReceived,// Message reçu d'un autre utilisateur
     UserList,       // Liste des utilisateurs connectés
+    RoomJoined,     // Confirmation d'entrée dans un salon
+    RoomLeft,       // Confirmation de sortie d'un salon
+    RoomList,       // Liste des salons
+    HistoryResult,  // Messages de l'historique
+    ModerationAck,  // Confirmation d'une sanction
     Error,          // Message d'erreur
 }
 
@@ -32,28 +77,248 @@ pub struct Message {
     pub sender: Option<String>,
     /// Destinataire du message (optionnel pour les messages broadcast)
     pub recipient: Option<String>,
+    /// Salon auquel le message est destiné (absent pour le chat général)
+    #[serde(default)]
+    pub room: Option<String>,
     /// Corps du message
     pub payload: MessagePayload,
+    /// Date de la dernière modification du texte (absente s'il n'a jamais été modifié)
+    #[serde(default)]
+    pub edited_at: Option<DateTime<Utc>>,
 }
 
 /// Contenu du message selon le type d'opération
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum MessagePayload {
-    Connect { username: String },
+    Hello { version: ProtocolVersion, features: Vec<String> },
+    Register { username: String, password: String },
+    Connect {
+        username: String,
+        #[serde(default)]
+        password: String,
+        /// Format des trames demandé pour la suite de la connexion
+        #[serde(default)]
+        framing: Framing,
+        /// Jeton reçu dans le `ConnectAck` précédent, pour reprendre la session
+        #[serde(default)]
+        resume_token: Option<Uuid>,
+    },
     Disconnect,
     Text { content: String },
-    UserList { users: Vec<String> },
+    UserList {
+        users: Vec<String>,
+        /// Statut de chacun des utilisateurs connectés, dans le même ordre
+        #[serde(default)]
+        presence: Vec<UserPresence>,
+    },
+    Room { room: String },
+    RoomList { rooms: Vec<RoomInfo> },
+    HistoryRequest {
+        /// Salon demandé (chat général et messages privés si absent)
+        #[serde(default)]
+        room: Option<String>,
+        #[serde(default)]
+        limit: Option<usize>,
+        #[serde(default)]
+        since: Option<DateTime<Utc>>,
+        /// Identifiant du dernier message déjà connu du client
+        #[serde(default)]
+        after: Option<Uuid>,
+    },
+    History { messages: Vec<Message> },
+    Receipt { message_id: Uuid },
+    Moderation {
+        /// Utilisateur visé (ou adresse IP pour un `Ban`)
+        username: String,
+        /// Durée de la sanction en secondes (définitive si absente; ignorée par `Kick`)
+        #[serde(default)]
+        duration: Option<u64>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    FileOffer { transfer_id: Uuid, name: String, size: u64 },
+    /// Octets déjà reçus: position de reprise à l'acceptation (multiple de
+    /// `FILE_CHUNK_SIZE`), puis accusé de chaque morceau
+    FileAccept { transfer_id: Uuid, offset: u64 },
+    FileReject {
+        transfer_id: Uuid,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Morceau numéro `seq`, qui commence à l'octet `seq * FILE_CHUNK_SIZE` du fichier
+    FileChunk {
+        transfer_id: Uuid,
+        seq: u64,
+        #[serde(with = "base64_data")]
+        data: Vec<u8>,
+    },
+    /// Empreinte SHA-256 du fichier complet, en hexadécimal
+    FileComplete { transfer_id: Uuid, sha256: String },
+    /// Nouveau texte du message `message_id`
+    Edit { message_id: Uuid, content: String },
+    Delete { message_id: Uuid },
+    Presence {
+        status: PresenceStatus,
+        /// Texte libre affiché à côté du statut
+        #[serde(default)]
+        text: Option<String>,
+    },
+    /// Le `recipient` ou le `room` du message indique à qui s'adresse la saisie
+    Typing { typing: bool },
+    /// Contenu du `ConnectAck` quand la reprise de session a été négociée
+    Session { resume_token: Uuid, resumed: bool },
     Error { code: u16, message: String },
     Empty,
 }
 
+impl MessagePayload {
+    /// Identifiant du transfert de fichier auquel se rapporte le message
+    pub fn transfer_id(&self) -> Option<Uuid> {
+        match self {
+            MessagePayload::FileOffer { transfer_id, .. }
+            | MessagePayload::FileAccept { transfer_id, .. }
+            | MessagePayload::FileReject { transfer_id, .. }
+            | MessagePayload::FileChunk { transfer_id, .. }
+            | MessagePayload::FileComplete { transfer_id, .. } => Some(*transfer_id),
+            _ => None,
+        }
+    }
+
+    /// Identifiant du message modifié ou supprimé
+    pub fn corrected_id(&self) -> Option<Uuid> {
+        match self {
+            MessagePayload::Edit { message_id, .. } | MessagePayload::Delete { message_id } => Some(*message_id),
+            _ => None,
+        }
+    }
+}
+
+/// Données binaires, encodées en base64 pour rester lisibles en JSON
+mod base64_data {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Version du protocole: deux pairs sont compatibles s'ils partagent la version majeure
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProtocolVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ProtocolVersion {
+    pub fn is_compatible_with(&self, other: &ProtocolVersion) -> bool {
+        self.major == other.major
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Version implémentée par ce serveur et ce client
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion { major: 2, minor: 0 };
+
+/// Fonctionnalités optionnelles annoncées dans `Hello`; les noms inconnus sont ignorés
+pub mod features {
+    /// Trames binaires (longueur + MessagePack) après le `ConnectAck`
+    pub const BINARY_FRAMING: &str = "framing.binary";
+    /// Salons (`JoinRoom`, `LeaveRoom`, `ListRooms`, messages de salon)
+    pub const ROOMS: &str = "rooms";
+    /// Historique persistant des messages (`History`)
+    pub const HISTORY: &str = "history";
+    /// Accusés de réception et de lecture (`Delivered`, `Read`)
+    pub const RECEIPTS: &str = "receipts";
+    /// `Ping` du serveur sur une connexion silencieuse, auquel le client répond par `Pong`
+    pub const HEARTBEAT: &str = "heartbeat";
+    /// Jeton de reprise dans le `ConnectAck`, pour retrouver ses salons après une coupure
+    pub const RESUME: &str = "resume";
+    /// Transfert de fichiers entre utilisateurs (`FileOffer`, `FileChunk`...)
+    pub const FILES: &str = "files";
+    /// Statuts de présence et indications de saisie
+    pub const PRESENCE: &str = "presence";
+    /// Modification et suppression des messages envoyés
+    pub const EDITS: &str = "edits";
+
+    /// Fonctionnalités prises en charge par cette implémentation
+    pub const SUPPORTED: &[&str] = &[BINARY_FRAMING, ROOMS, HISTORY, RECEIPTS, HEARTBEAT, RESUME, FILES, PRESENCE, EDITS];
+}
+
+/// Statut de présence d'un utilisateur connecté
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PresenceStatus {
+    #[default]
+    Online,
+    Away,
+    Busy,
+}
+
+/// Présence d'un utilisateur dans une `UserList`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserPresence {
+    pub username: String,
+    pub status: PresenceStatus,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+/// Description d'un salon dans une `RoomList`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoomInfo {
+    pub name: String,
+    pub members: usize,
+}
+
+/// Expéditeur des notifications du serveur, réservé: aucun compte ne peut porter ce nom
+pub const SERVER_SENDER: &str = "Serveur";
+
+/// Longueur minimale d'un mot de passe à la création du compte
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Longueur maximale d'un nom de salon
+pub const MAX_ROOM_NAME_LEN: usize = 32;
+
+/// Taille des morceaux d'un transfert de fichier (seul le dernier peut être plus petit)
+pub const FILE_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Longueur maximale d'un nom de fichier transféré
+pub const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Longueur maximale du texte accompagnant un statut de présence
+pub const MAX_STATUS_TEXT_LEN: usize = 64;
+
+/// Durée au bout de laquelle une indication de saisie non renouvelée est caduque
+pub const TYPING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 /// Codes d'erreur du protocole
 pub mod error_codes {
     pub const INVALID_MESSAGE: u16 = 400;
     pub const UNAUTHORIZED: u16 = 401;
+    pub const FORBIDDEN: u16 = 403;
+    pub const USER_NOT_FOUND: u16 = 404;
     pub const USER_EXISTS: u16 = 409;
+    pub const IDLE_TIMEOUT: u16 = 408;
+    /// Session fermée par un administrateur (expulsion ou bannissement)
+    pub const KICKED: u16 = 410;
+    pub const FRAME_TOO_LARGE: u16 = 413;
+    pub const UPGRADE_REQUIRED: u16 = 426;
+    /// Message trop long ou envoyé trop vite; la récidive ferme la session
+    pub const LIMIT_EXCEEDED: u16 = 429;
     pub const INTERNAL_ERROR: u16 = 500;
+    pub const QUEUE_FULL: u16 = 507;
 }
 
 impl Message {
@@ -65,7 +330,9 @@ impl Message {
             timestamp: Utc::now(),
             sender,
             recipient: None,
+            room: None,
             payload,
+            edited_at: None,
         }
     }
 
@@ -81,15 +348,58 @@ impl Message {
         msg
     }
 
+    /// Crée l'annonce de version et de fonctionnalités du client
+    pub fn hello(features: Vec<String>) -> Self {
+        Self::new(
+            OpCode::Hello,
+            None,
+            MessagePayload::Hello { version: PROTOCOL_VERSION, features },
+        )
+    }
+
+    /// Crée la réponse du serveur avec les fonctionnalités retenues
+    pub fn hello_ack(features: Vec<String>) -> Self {
+        Self::new(
+            OpCode::HelloAck,
+            None,
+            MessagePayload::Hello { version: PROTOCOL_VERSION, features },
+        )
+    }
+
+    /// Crée une demande de création de compte
+    pub fn register(username: String, password: String) -> Self {
+        Self::new(
+            OpCode::Register,
+            Some(username.clone()),
+            MessagePayload::Register { username, password },
+        )
+    }
+
     /// Crée un message de connexion
-    pub fn connect(username: String) -> Self {
+    pub fn connect(username: String, password: String) -> Self {
         Self::new(
             OpCode::Connect,
             Some(username.clone()),
-            MessagePayload::Connect { username },
+            MessagePayload::Connect { username, password, framing: Framing::Json, resume_token: None },
         )
     }
 
+    /// Demande un format de trames pour la suite de la connexion (message `Connect` uniquement)
+    pub fn with_framing(mut self, requested: Framing) -> Self {
+        if let MessagePayload::Connect { framing, .. } = &mut self.payload {
+            *framing = requested;
+        }
+        self
+    }
+
+    /// Demande la reprise de la session associée au jeton (message `Connect` uniquement)
+    pub fn with_resume_token(mut self, token: Uuid) -> Self {
+        if let MessagePayload::Connect { resume_token, .. } = &mut self.payload {
+            *resume_token = Some(token);
+        }
+        self
+    }
+
     /// Crée un message de déconnexion
     pub fn disconnect(username: String) -> Self {
         Self::new(
@@ -108,6 +418,138 @@ impl Message {
         )
     }
 
+    /// Crée un message privé destiné à un seul utilisateur
+    pub fn private_message(sender: String, recipient: String, content: String) -> Self {
+        Self::new_with_recipient(
+            OpCode::SendMessage,
+            Some(sender),
+            recipient,
+            MessagePayload::Text { content },
+        )
+    }
+
+    /// Crée un message texte destiné aux membres d'un salon
+    pub fn room_message(sender: String, room: String, content: String) -> Self {
+        let mut msg = Self::text_message(sender, content);
+        msg.room = Some(room);
+        msg
+    }
+
+    /// Crée une demande d'entrée dans un salon
+    pub fn join_room(sender: String, room: String) -> Self {
+        Self::new(OpCode::JoinRoom, Some(sender), MessagePayload::Room { room })
+    }
+
+    /// Crée une demande de sortie d'un salon
+    pub fn leave_room(sender: String, room: String) -> Self {
+        Self::new(OpCode::LeaveRoom, Some(sender), MessagePayload::Room { room })
+    }
+
+    /// Crée une liste de salons
+    pub fn room_list(rooms: Vec<RoomInfo>) -> Self {
+        Self::new(OpCode::RoomList, None, MessagePayload::RoomList { rooms })
+    }
+
+    /// Crée une demande des derniers messages du chat général ou d'un salon
+    pub fn history_request(sender: String, room: Option<String>, limit: Option<usize>) -> Self {
+        Self::new(
+            OpCode::History,
+            Some(sender),
+            MessagePayload::HistoryRequest { room, limit, since: None, after: None },
+        )
+    }
+
+    /// Crée la réponse à une demande d'historique
+    pub fn history(messages: Vec<Message>) -> Self {
+        Self::new(OpCode::HistoryResult, None, MessagePayload::History { messages })
+    }
+
+    /// Crée un accusé de réception du message `message_id`, adressé à son expéditeur
+    pub fn delivered(sender: String, original_sender: String, message_id: Uuid) -> Self {
+        Self::new_with_recipient(OpCode::Delivered, Some(sender), original_sender, MessagePayload::Receipt { message_id })
+    }
+
+    /// Crée un accusé de lecture du message `message_id`, adressé à son expéditeur
+    pub fn read(sender: String, original_sender: String, message_id: Uuid) -> Self {
+        Self::new_with_recipient(OpCode::Read, Some(sender), original_sender, MessagePayload::Receipt { message_id })
+    }
+
+    /// Crée la modification du message `message_id`
+    pub fn edit_message(sender: String, message_id: Uuid, content: String) -> Self {
+        Self::new(OpCode::EditMessage, Some(sender), MessagePayload::Edit { message_id, content })
+    }
+
+    /// Crée la suppression du message `message_id`
+    pub fn delete_message(sender: String, message_id: Uuid) -> Self {
+        Self::new(OpCode::DeleteMessage, Some(sender), MessagePayload::Delete { message_id })
+    }
+
+    /// Remplace le texte de ce message par celui d'une modification `EditMessage`
+    pub fn apply_edit(&mut self, edit: &Message) {
+        if let (MessagePayload::Text { content }, MessagePayload::Edit { content: new_content, .. }) = (&mut self.payload, &edit.payload) {
+            *content = new_content.clone();
+            self.edited_at = Some(edit.timestamp);
+        }
+    }
+
+    /// Crée un changement de statut de présence
+    pub fn presence(sender: String, status: PresenceStatus, text: Option<String>) -> Self {
+        Self::new(OpCode::Presence, Some(sender), MessagePayload::Presence { status, text })
+    }
+
+    /// Crée une indication de saisie, adressée à `recipient`, au salon `room` ou au chat général
+    pub fn typing(sender: String, recipient: Option<String>, room: Option<String>, typing: bool) -> Self {
+        let mut msg = Self::new(OpCode::Typing, Some(sender), MessagePayload::Typing { typing });
+        msg.recipient = recipient;
+        msg.room = room;
+        msg
+    }
+
+    /// Crée la proposition d'envoi d'un fichier à `recipient`
+    pub fn file_offer(sender: String, recipient: String, transfer_id: Uuid, name: String, size: u64) -> Self {
+        Self::new_with_recipient(OpCode::FileOffer, Some(sender), recipient, MessagePayload::FileOffer { transfer_id, name, size })
+    }
+
+    /// Crée l'acceptation d'un transfert (ou l'accusé d'un morceau), à reprendre à `offset`
+    pub fn file_accept(sender: String, recipient: String, transfer_id: Uuid, offset: u64) -> Self {
+        Self::new_with_recipient(OpCode::FileAccept, Some(sender), recipient, MessagePayload::FileAccept { transfer_id, offset })
+    }
+
+    /// Crée le refus ou l'abandon d'un transfert
+    pub fn file_reject(sender: String, recipient: String, transfer_id: Uuid, reason: Option<String>) -> Self {
+        Self::new_with_recipient(OpCode::FileReject, Some(sender), recipient, MessagePayload::FileReject { transfer_id, reason })
+    }
+
+    /// Crée le morceau numéro `seq` d'un transfert
+    pub fn file_chunk(sender: String, recipient: String, transfer_id: Uuid, seq: u64, data: Vec<u8>) -> Self {
+        Self::new_with_recipient(OpCode::FileChunk, Some(sender), recipient, MessagePayload::FileChunk { transfer_id, seq, data })
+    }
+
+    /// Crée la fin d'un transfert, avec l'empreinte SHA-256 du fichier
+    pub fn file_complete(sender: String, recipient: String, transfer_id: Uuid, sha256: String) -> Self {
+        Self::new_with_recipient(OpCode::FileComplete, Some(sender), recipient, MessagePayload::FileComplete { transfer_id, sha256 })
+    }
+
+    /// Crée une sanction (`Kick`, `Ban` ou `Mute`) contre `username`
+    pub fn moderation(op_code: OpCode, sender: String, username: String, duration: Option<u64>, reason: Option<String>) -> Self {
+        Self::new(op_code, Some(sender), MessagePayload::Moderation { username, duration, reason })
+    }
+
+    /// Crée la confirmation d'une sanction, qui en reprend le contenu
+    pub fn moderation_ack(payload: MessagePayload) -> Self {
+        Self::new(OpCode::ModerationAck, None, payload)
+    }
+
+    /// Crée une sonde de présence du pair
+    pub fn ping() -> Self {
+        Self::new(OpCode::Ping, None, MessagePayload::Empty)
+    }
+
+    /// Crée la réponse à un `Ping`
+    pub fn pong() -> Self {
+        Self::new(OpCode::Pong, None, MessagePayload::Empty)
+    }
+
     /// Crée un message d'erreur
     pub fn error(code: u16, message: String) -> Self {
         Self::new(
@@ -122,9 +564,15 @@ impl Message {
         Self::new(OpCode::ConnectAck, None, MessagePayload::Empty)
     }
 
+    /// Crée un accusé de connexion portant le jeton de reprise de la session
+    pub fn session_ack(resume_token: Uuid, resumed: bool) -> Self {
+        Self::new(OpCode::ConnectAck, None, MessagePayload::Session { resume_token, resumed })
+    }
+
     /// Crée une liste d'utilisateurs
-    pub fn user_list(users: Vec<String>) -> Self {
-        Self::new(OpCode::UserList, None, MessagePayload::UserList { users })
+    pub fn user_list(presence: Vec<UserPresence>) -> Self {
+        let users = presence.iter().map(|user| user.username.clone()).collect();
+        Self::new(OpCode::UserList, None, MessagePayload::UserList { users, presence })
     }
 
     /// Sérialise le message en JSON
@@ -140,11 +588,32 @@ impl Message {
     /// Valide la cohérence du message
     pub fn validate(&self) -> Result<(), String> {
         match &self.op_code {
+            OpCode::Hello if !matches!(self.payload, MessagePayload::Hello { .. }) => {
+                return Err("Invalid payload for Hello message".to_string());
+            },
+            OpCode::Register => {
+                if let MessagePayload::Register { username, password } = &self.payload {
+                    if username.is_empty() {
+                        return Err("Username cannot be empty".to_string());
+                    }
+                    if username.eq_ignore_ascii_case(SERVER_SENDER) {
+                        return Err(format!("Username '{}' is reserved", username));
+                    }
+                    if password.chars().count() < MIN_PASSWORD_LEN {
+                        return Err(format!("Password must be at least {} characters", MIN_PASSWORD_LEN));
+                    }
+                } else {
+                    return Err("Invalid payload for Register message".to_string());
+                }
+            },
             OpCode::Connect => {
-                if let MessagePayload::Connect { username } = &self.payload {
+                if let MessagePayload::Connect { username, password, .. } = &self.payload {
                     if username.is_empty() {
                         return Err("Username cannot be empty".to_string());
                     }
+                    if password.is_empty() {
+                        return Err("Password cannot be empty".to_string());
+                    }
                 } else {
                     return Err("Invalid payload for Connect message".to_string());
                 }
@@ -160,6 +629,110 @@ impl Message {
                 if self.sender.is_none() {
                     return Err("SendMessage must have a sender".to_string());
                 }
+                if self.recipient.as_ref().is_some_and(|r| r.is_empty()) {
+                    return Err("Recipient cannot be empty".to_string());
+                }
+                if self.recipient.is_some() && self.room.is_some() {
+                    return Err("A message cannot have both a recipient and a room".to_string());
+                }
+                if let Some(room) = &self.room {
+                    validate_room_name(room)?;
+                }
+            },
+            OpCode::JoinRoom | OpCode::LeaveRoom => {
+                if let MessagePayload::Room { room } = &self.payload {
+                    validate_room_name(room)?;
+                } else {
+                    return Err("Invalid payload for room operation".to_string());
+                }
+            },
+            OpCode::Delivered | OpCode::Read => {
+                if !matches!(self.payload, MessagePayload::Receipt { .. }) {
+                    return Err("Invalid payload for receipt".to_string());
+                }
+                if self.recipient.as_ref().is_none_or(|r| r.is_empty()) {
+                    return Err("A receipt must be addressed to the original sender".to_string());
+                }
+            },
+            OpCode::Kick | OpCode::Ban | OpCode::Mute => {
+                if let MessagePayload::Moderation { username, duration, .. } = &self.payload {
+                    if username.is_empty() {
+                        return Err("Username cannot be empty".to_string());
+                    }
+                    if *duration == Some(0) {
+                        return Err("Sanction duration must be positive".to_string());
+                    }
+                } else {
+                    return Err("Invalid payload for moderation message".to_string());
+                }
+            },
+            OpCode::FileOffer | OpCode::FileAccept | OpCode::FileReject | OpCode::FileChunk | OpCode::FileComplete => {
+                if self.recipient.as_ref().is_none_or(|r| r.is_empty()) {
+                    return Err("A file transfer message must have a recipient".to_string());
+                }
+                match (&self.op_code, &self.payload) {
+                    (OpCode::FileOffer, MessagePayload::FileOffer { name, .. }) => validate_file_name(name)?,
+                    (OpCode::FileAccept, MessagePayload::FileAccept { .. }) => {}
+                    (OpCode::FileChunk, MessagePayload::FileChunk { data, .. }) => {
+                        if data.is_empty() || data.len() > FILE_CHUNK_SIZE {
+                            return Err(format!("Chunk must contain 1 to {} bytes", FILE_CHUNK_SIZE));
+                        }
+                    }
+                    (OpCode::FileComplete, MessagePayload::FileComplete { sha256, .. }) => {
+                        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                            return Err("Checksum must be a hexadecimal SHA-256 digest".to_string());
+                        }
+                    }
+                    (OpCode::FileReject, MessagePayload::FileReject { .. }) => {}
+                    _ => return Err("Invalid payload for file transfer message".to_string()),
+                }
+            },
+            OpCode::EditMessage => {
+                if let MessagePayload::Edit { content, .. } = &self.payload {
+                    if content.is_empty() {
+                        return Err("Message content cannot be empty".to_string());
+                    }
+                } else {
+                    return Err("Invalid payload for EditMessage".to_string());
+                }
+            },
+            OpCode::DeleteMessage if !matches!(self.payload, MessagePayload::Delete { .. }) => {
+                return Err("Invalid payload for DeleteMessage".to_string());
+            },
+            OpCode::Presence => {
+                if let MessagePayload::Presence { text: Some(text), .. } = &self.payload {
+                    if text.chars().count() > MAX_STATUS_TEXT_LEN {
+                        return Err(format!("Status text cannot exceed {} characters", MAX_STATUS_TEXT_LEN));
+                    }
+                    if text.chars().any(char::is_control) {
+                        return Err("Status text cannot contain control characters".to_string());
+                    }
+                } else if !matches!(self.payload, MessagePayload::Presence { .. }) {
+                    return Err("Invalid payload for Presence message".to_string());
+                }
+            },
+            OpCode::Typing => {
+                if !matches!(self.payload, MessagePayload::Typing { .. }) {
+                    return Err("Invalid payload for Typing message".to_string());
+                }
+                if self.recipient.is_some() && self.room.is_some() {
+                    return Err("A typing notification cannot have both a recipient and a room".to_string());
+                }
+                if let Some(room) = &self.room {
+                    validate_room_name(room)?;
+                }
+            },
+            OpCode::History => {
+                if let MessagePayload::HistoryRequest { room, limit, .. } = &self.payload {
+                    if let Some(room) = room {
+                        validate_room_name(room)?;
+                    }
+                    if *limit == Some(0) {
+                        return Err("History limit must be positive".to_string());
+                    }
+                } else {
+                    return Err("Invalid payload for History message".to_string());
+                }
             },
             _ => {}
         }
@@ -167,13 +740,41 @@ impl Message {
     }
 }
 
+/// Un nom de salon est non vide, sans espace et limité à `MAX_ROOM_NAME_LEN` caractères
+fn validate_room_name(room: &str) -> Result<(), String> {
+    if room.is_empty() {
+        return Err("Room name cannot be empty".to_string());
+    }
+    if room.chars().count() > MAX_ROOM_NAME_LEN {
+        return Err(format!("Room name cannot exceed {} characters", MAX_ROOM_NAME_LEN));
+    }
+    if room.chars().any(char::is_whitespace) {
+        return Err("Room name cannot contain whitespace".to_string());
+    }
+    Ok(())
+}
+
+/// Un nom de fichier est un simple nom, sans chemin, limité à `MAX_FILE_NAME_LEN` caractères
+fn validate_file_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err("Invalid file name".to_string());
+    }
+    if name.chars().count() > MAX_FILE_NAME_LEN {
+        return Err(format!("File name cannot exceed {} characters", MAX_FILE_NAME_LEN));
+    }
+    if name.chars().any(|c| c == '/' || c == '\\' || c.is_control()) {
+        return Err("File name cannot contain a path".to_string());
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_message_serialization() {
-        let msg = Message::connect("test_user".to_string());
+        let msg = Message::connect("test_user".to_string(), "password".to_string());
         let json = msg.to_json().unwrap();
         let deserialized = Message::from_json(&json).unwrap();
         assert_eq!(msg.op_code, deserialized.op_code);
@@ -181,10 +782,176 @@ mod tests {
 
     #[test]
     fn test_message_validation() {
-        let valid_msg = Message::connect("user".to_string());
+        let valid_msg = Message::connect("user".to_string(), "password".to_string());
         assert!(valid_msg.validate().is_ok());
 
-        let invalid_msg = Message::connect("".to_string());
+        let invalid_msg = Message::connect("".to_string(), "password".to_string());
+        assert!(invalid_msg.validate().is_err());
+
+        let no_password = Message::connect("user".to_string(), "".to_string());
+        assert!(no_password.validate().is_err());
+
+        let short_password = Message::register("user".to_string(), "court".to_string());
+        assert!(short_password.validate().is_err());
+
+        let reserved = Message::register(SERVER_SENDER.to_string(), "password".to_string());
+        assert!(reserved.validate().is_err());
+    }
+
+    #[test]
+    fn test_private_message() {
+        let msg = Message::private_message("Alice".to_string(), "Bob".to_string(), "Salut".to_string());
+        assert_eq!(msg.recipient.as_deref(), Some("Bob"));
+        assert!(msg.validate().is_ok());
+
+        let invalid_msg = Message::private_message("Alice".to_string(), "".to_string(), "Salut".to_string());
         assert!(invalid_msg.validate().is_err());
     }
+
+    #[test]
+    fn test_room_validation() {
+        assert!(Message::join_room("Alice".to_string(), "rust".to_string()).validate().is_ok());
+        assert!(Message::join_room("Alice".to_string(), "".to_string()).validate().is_err());
+        assert!(Message::join_room("Alice".to_string(), "deux mots".to_string()).validate().is_err());
+        assert!(Message::leave_room("Alice".to_string(), "x".repeat(MAX_ROOM_NAME_LEN + 1)).validate().is_err());
+
+        let msg = Message::room_message("Alice".to_string(), "rust".to_string(), "Salut".to_string());
+        assert!(msg.validate().is_ok());
+
+        let mut invalid_msg = msg.clone();
+        invalid_msg.recipient = Some("Bob".to_string());
+        assert!(invalid_msg.validate().is_err());
+    }
+
+    #[test]
+    fn test_history_request_validation() {
+        let msg = Message::history_request("Alice".to_string(), Some("rust".to_string()), Some(20));
+        assert!(msg.validate().is_ok());
+        let json = msg.to_json().unwrap();
+        assert!(matches!(
+            Message::from_json(&json).unwrap().payload,
+            MessagePayload::HistoryRequest { limit: Some(20), since: None, .. }
+        ));
+
+        assert!(Message::history_request("Alice".to_string(), None, Some(0)).validate().is_err());
+        assert!(Message::history_request("Alice".to_string(), Some("".to_string()), None).validate().is_err());
+    }
+
+    #[test]
+    fn test_receipt_validation() {
+        let original = Message::private_message("Alice".to_string(), "Bob".to_string(), "Salut".to_string());
+        let receipt = Message::delivered("Bob".to_string(), "Alice".to_string(), original.id);
+        assert!(receipt.validate().is_ok());
+        assert!(matches!(receipt.payload, MessagePayload::Receipt { message_id } if message_id == original.id));
+
+        let mut unaddressed = Message::read("Bob".to_string(), "Alice".to_string(), original.id);
+        unaddressed.recipient = None;
+        assert!(unaddressed.validate().is_err());
+    }
+
+    #[test]
+    fn test_moderation_validation() {
+        let ban = Message::moderation(OpCode::Ban, "Admin".to_string(), "Bob".to_string(), Some(3600), Some("spam".to_string()));
+        assert!(ban.validate().is_ok());
+        let json = ban.to_json().unwrap();
+        assert!(matches!(
+            Message::from_json(&json).unwrap().payload,
+            MessagePayload::Moderation { duration: Some(3600), reason: Some(_), .. }
+        ));
+
+        assert!(Message::moderation(OpCode::Mute, "Admin".to_string(), "Bob".to_string(), Some(0), None).validate().is_err());
+        assert!(Message::moderation(OpCode::Kick, "Admin".to_string(), String::new(), None, None).validate().is_err());
+        assert!(Message::new(OpCode::Kick, Some("Admin".to_string()), MessagePayload::Empty).validate().is_err());
+    }
+
+    #[test]
+    fn test_file_transfer_validation() {
+        let id = Uuid::new_v4();
+        let chunk = Message::file_chunk("Alice".to_string(), "Bob".to_string(), id, 3, vec![0, 255, 42]);
+        assert!(chunk.validate().is_ok());
+        // Les données sont encodées en base64 dans le JSON
+        let json = chunk.to_json().unwrap();
+        assert!(json.contains("\"data\":\"AP8q\""));
+        match Message::from_json(&json).unwrap().payload {
+            MessagePayload::FileChunk { transfer_id, seq: 3, data } => {
+                assert_eq!(transfer_id, id);
+                assert_eq!(data, vec![0, 255, 42]);
+            }
+            other => panic!("expected chunk, got {:?}", other),
+        }
+
+        let offer = |name: &str| Message::file_offer("Alice".to_string(), "Bob".to_string(), id, name.to_string(), 10);
+        assert!(offer("rapport.pdf").validate().is_ok());
+        assert!(offer("../.ssh/id_rsa").validate().is_err());
+        assert!(offer("..").validate().is_err());
+
+        let mut no_recipient = offer("rapport.pdf");
+        no_recipient.recipient = None;
+        assert!(no_recipient.validate().is_err());
+        assert!(Message::new_with_recipient(OpCode::FileAccept, None, "Alice".to_string(), MessagePayload::Empty).validate().is_err());
+        assert!(Message::file_chunk("Alice".to_string(), "Bob".to_string(), id, 0, vec![0; FILE_CHUNK_SIZE + 1]).validate().is_err());
+        assert!(Message::file_complete("Alice".to_string(), "Bob".to_string(), id, "abc".to_string()).validate().is_err());
+    }
+
+    #[test]
+    fn test_presence_validation() {
+        let away = Message::presence("Alice".to_string(), PresenceStatus::Away, Some("Réunion".to_string()));
+        assert!(away.validate().is_ok());
+        assert!(Message::presence("Alice".to_string(), PresenceStatus::Busy, Some("x".repeat(MAX_STATUS_TEXT_LEN + 1))).validate().is_err());
+        assert!(Message::presence("Alice".to_string(), PresenceStatus::Online, Some("a\nb".to_string())).validate().is_err());
+        assert!(Message::new(OpCode::Presence, None, MessagePayload::Empty).validate().is_err());
+
+        assert!(Message::typing("Alice".to_string(), Some("Bob".to_string()), None, true).validate().is_ok());
+        assert!(Message::typing("Alice".to_string(), Some("Bob".to_string()), Some("rust".to_string()), true).validate().is_err());
+
+        // Une liste d'utilisateurs sans statuts (serveur antérieur) reste lisible
+        let json = r#"{"type":"UserList","users":["Alice"]}"#;
+        match serde_json::from_str::<MessagePayload>(json).unwrap() {
+            MessagePayload::UserList { users, presence } => {
+                assert_eq!(users, vec!["Alice"]);
+                assert!(presence.is_empty());
+            }
+            other => panic!("expected user list, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_edit_validation() {
+        let mut original = Message::text_message("Alice".to_string(), "Bonjuor".to_string());
+        let edit = Message::edit_message("Alice".to_string(), original.id, "Bonjour".to_string());
+        assert!(edit.validate().is_ok());
+        assert_eq!(edit.payload.corrected_id(), Some(original.id));
+
+        original.apply_edit(&edit);
+        assert!(matches!(original.payload, MessagePayload::Text { ref content } if content == "Bonjour"));
+        assert_eq!(original.edited_at, Some(edit.timestamp));
+
+        assert!(Message::edit_message("Alice".to_string(), original.id, String::new()).validate().is_err());
+        assert!(Message::delete_message("Alice".to_string(), original.id).validate().is_ok());
+        assert!(Message::new(OpCode::DeleteMessage, None, MessagePayload::Empty).validate().is_err());
+    }
+
+    #[test]
+    fn test_protocol_version_compatibility() {
+        let hello = Message::hello(vec![features::ROOMS.to_string()]);
+        assert!(hello.validate().is_ok());
+
+        let minor_bump = ProtocolVersion { major: PROTOCOL_VERSION.major, minor: PROTOCOL_VERSION.minor + 1 };
+        assert!(minor_bump.is_compatible_with(&PROTOCOL_VERSION));
+
+        let old = ProtocolVersion { major: 1, minor: 0 };
+        assert!(!old.is_compatible_with(&PROTOCOL_VERSION));
+        assert_eq!(PROTOCOL_VERSION.to_string(), "2.0");
+    }
+
+    #[test]
+    fn test_message_without_room_field() {
+        // Les clients antérieurs aux salons n'envoient pas le champ `room`
+        let msg = Message::text_message("Alice".to_string(), "Salut".to_string());
+        let mut json: serde_json::Value = serde_json::from_str(&msg.to_json().unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("room");
+
+        let deserialized = Message::from_json(&json.to_string()).unwrap();
+        assert_eq!(deserialized.room, None);
+    }
 }
@@ -1,11 +1,41 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{Mutex, broadcast};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::{Duration, Instant};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, Notify, broadcast, mpsc};
+use tokio::time::MissedTickBehavior;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::codec::Framed;
 use uuid::Uuid;
 
-use crate::protocol::{Message, OpCode, MessagePayload, error_codes};
+use crate::accounts::{self, AccountStore};
+use crate::codec::{CodecError, Framing, MessageCodec, DEFAULT_MAX_FRAME_SIZE};
+use crate::history::{DEFAULT_MAX_HISTORY_MESSAGES, HistoryQuery, MessageHistory};
+use crate::limits::{
+    TokenBucket, DEFAULT_MAX_MESSAGE_LENGTH, DEFAULT_MAX_VIOLATIONS, DEFAULT_RATE_BURST, DEFAULT_RATE_PER_SECOND,
+    DEFAULT_TRANSFER_BURST, DEFAULT_TRANSFER_PER_SECOND,
+};
+use crate::moderation::{Ban, BanList, Sanction};
+use crate::offline::{OfflineQueue, DEFAULT_OFFLINE_MESSAGE_TTL, DEFAULT_OFFLINE_QUEUE_LIMIT};
+use crate::plugin::{Plugin, Plugins};
+use crate::protocol::{
+    Message, OpCode, MessagePayload, PresenceStatus, RoomInfo, UserPresence, PROTOCOL_VERSION, SERVER_SENDER,
+    error_codes, features,
+};
+use crate::tls::Transport;
+
+/// Trames de transfert en attente d'écriture par connexion: quelques fenêtres de morceaux
+const TRANSFER_CHANNEL_CAPACITY: usize = 64;
+
+/// Rôle d'un utilisateur connecté
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    /// Peut expulser, bannir et rendre muets les autres utilisateurs
+    Admin,
+}
 
 /// État d'un client connecté
 #[derive(Debug, Clone)]
@@ -13,6 +43,39 @@ pub struct ClientSession {
     pub id: Uuid,
     pub username: String,
     pub connected_at: chrono::DateTime<chrono::Utc>,
+    /// Jeton permettant de reprendre la session après une coupure
+    pub resume_token: Uuid,
+    pub role: Role,
+    /// Adresse du client, retenue pour un éventuel bannissement
+    pub ip: Option<IpAddr>,
+    /// Fonctionnalités négociées par le client
+    pub features: Vec<String>,
+    pub status: PresenceStatus,
+    pub status_text: Option<String>,
+    /// Passé absent par le serveur après une inactivité, et non par l'utilisateur
+    pub auto_away: bool,
+    /// Canal propre à la connexion, par lequel lui sont relayées les trames de transfert
+    pub transfers: Option<mpsc::Sender<Message>>,
+    /// Prévient la connexion que sa session a été reprise par une autre
+    pub takeover: Option<Arc<Notify>>,
+}
+
+/// Transfert de fichier en cours entre deux utilisateurs connectés
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub sender: String,
+    pub recipient: String,
+    /// Le destinataire a accepté: les morceaux peuvent lui être relayés
+    pub accepted: bool,
+}
+
+/// Session interrompue par une coupure, en attente de reprise
+#[derive(Debug, Clone)]
+pub struct SuspendedSession {
+    pub username: String,
+    /// Salons dont l'utilisateur était membre
+    pub rooms: Vec<String>,
+    pub disconnected_at: chrono::DateTime<chrono::Utc>,
 }
 
 /// État global du serveur
@@ -21,6 +84,14 @@ pub struct ServerState {
     pub sessions: HashMap<Uuid, ClientSession>,
     /// Mapping nom d'utilisateur -> ID de session
     pub username_to_session: HashMap<String, Uuid>,
+    /// Salons (nom du salon -> membres)
+    pub rooms: HashMap<String, HashSet<String>>,
+    /// Sessions interrompues (jeton de reprise -> état à restaurer)
+    pub suspended: HashMap<Uuid, SuspendedSession>,
+    /// Utilisateurs réduits au silence (nom -> sanction)
+    pub mutes: HashMap<String, Sanction>,
+    /// Transferts de fichiers en cours (identifiant -> participants)
+    pub transfers: HashMap<Uuid, Transfer>,
 }
 
 impl ServerState {
@@ -28,6 +99,10 @@ impl ServerState {
         Self {
             sessions: HashMap::new(),
             username_to_session: HashMap::new(),
+            rooms: HashMap::new(),
+            suspended: HashMap::new(),
+            mutes: HashMap::new(),
+            transfers: HashMap::new(),
         }
     }
 
@@ -42,6 +117,15 @@ impl ServerState {
             id: session_id,
             username: username.clone(),
             connected_at: chrono::Utc::now(),
+            resume_token: Uuid::new_v4(),
+            role: Role::User,
+            ip: None,
+            features: Vec::new(),
+            status: PresenceStatus::Online,
+            status_text: None,
+            auto_away: false,
+            transfers: None,
+            takeover: None,
         };
 
         self.sessions.insert(session_id, session);
@@ -51,25 +135,401 @@ impl ServerState {
     }
 
     /// Supprime une session
-    pub fn remove_session(&mut self, session_id: &Uuid) -> Option<String> {
-        if let Some(session) = self.sessions.remove(session_id) {
-            self.username_to_session.remove(&session.username);
-            Some(session.username)
-        } else {
-            None
+    pub fn remove_session(&mut self, session_id: &Uuid) -> Option<ClientSession> {
+        let session = self.sessions.remove(session_id)?;
+        self.username_to_session.remove(&session.username);
+        self.leave_all_rooms(&session.username);
+        Some(session)
+    }
+
+    /// Supprime une session interrompue par une coupure en gardant de quoi la reprendre
+    pub fn suspend_session(&mut self, session_id: &Uuid) -> Option<ClientSession> {
+        let rooms = self.rooms_of(&self.sessions.get(session_id)?.username);
+        let session = self.remove_session(session_id)?;
+        self.suspended.insert(session.resume_token, SuspendedSession {
+            username: session.username.clone(),
+            rooms,
+            disconnected_at: chrono::Utc::now(),
+        });
+        Some(session)
+    }
+
+    /// Reprend la session de `username` associée au jeton, si elle date de moins de `window`
+    ///
+    /// Une session encore active avec ce jeton (connexion à moitié ouverte que le
+    /// serveur n'a pas encore détectée) est remplacée, et sa connexion prévenue
+    /// qu'elle doit se fermer.
+    pub fn resume_session(&mut self, token: Uuid, username: &str, window: chrono::Duration) -> Option<SuspendedSession> {
+        let now = chrono::Utc::now();
+        self.suspended.retain(|_, suspended| now - suspended.disconnected_at < window);
+
+        if self.suspended.get(&token).is_some_and(|suspended| suspended.username == username) {
+            return self.suspended.remove(&token);
+        }
+
+        let session_id = *self.username_to_session.get(username)?;
+        if self.sessions.get(&session_id)?.resume_token != token {
+            return None;
+        }
+        let rooms = self.rooms_of(username);
+        if let Some(takeover) = self.remove_session(&session_id).and_then(|session| session.takeover) {
+            takeover.notify_one();
+        }
+        Some(SuspendedSession {
+            username: username.to_string(),
+            rooms,
+            disconnected_at: now,
+        })
+    }
+
+    /// Session active d'un utilisateur
+    pub fn session_of(&self, username: &str) -> Option<&ClientSession> {
+        self.sessions.get(self.username_to_session.get(username)?)
+    }
+
+    fn session_of_mut(&mut self, username: &str) -> Option<&mut ClientSession> {
+        self.sessions.get_mut(self.username_to_session.get(username)?)
+    }
+
+    /// Change le statut d'un utilisateur connecté; renvoie sa nouvelle présence
+    /// si elle a changé
+    pub fn set_presence(&mut self, username: &str, status: PresenceStatus, text: Option<String>) -> Option<UserPresence> {
+        let session = self.session_of_mut(username)?;
+        session.auto_away = false;
+        if session.status == status && session.status_text == text {
+            return None;
+        }
+        session.status = status;
+        session.status_text = text;
+        Some(Self::presence_of(session))
+    }
+
+    /// Passe absent un utilisateur en ligne resté inactif
+    pub fn mark_away(&mut self, username: &str) -> Option<UserPresence> {
+        let session = self.session_of_mut(username)?;
+        if session.status != PresenceStatus::Online {
+            return None;
+        }
+        session.status = PresenceStatus::Away;
+        session.auto_away = true;
+        Some(Self::presence_of(session))
+    }
+
+    /// Repasse en ligne un utilisateur passé absent pour inactivité
+    pub fn mark_active(&mut self, username: &str) -> Option<UserPresence> {
+        let session = self.session_of_mut(username)?;
+        if !session.auto_away {
+            return None;
+        }
+        session.status = PresenceStatus::Online;
+        session.auto_away = false;
+        Some(Self::presence_of(session))
+    }
+
+    fn presence_of(session: &ClientSession) -> UserPresence {
+        UserPresence {
+            username: session.username.clone(),
+            status: session.status,
+            text: session.status_text.clone(),
+        }
+    }
+
+    /// Interdit à un utilisateur d'envoyer des messages
+    pub fn mute(&mut self, username: &str, sanction: Sanction) {
+        self.mutes.insert(username.to_string(), sanction);
+    }
+
+    /// Sanction en cours empêchant un utilisateur d'envoyer des messages
+    pub fn muted(&mut self, username: &str) -> Option<&Sanction> {
+        self.mutes.retain(|_, sanction| sanction.is_active());
+        self.mutes.get(username)
+    }
+
+    /// Vérifie qu'un message de transfert de fichier de `sender` à `recipient`
+    /// peut être relayé, et met à jour l'état du transfert
+    ///
+    /// Seul le destinataire accepte, seul l'expéditeur envoie les morceaux et la
+    /// fin (après acceptation), et chacun des deux peut abandonner.
+    pub fn route_transfer(&mut self, op_code: &OpCode, transfer_id: Uuid, sender: &str, recipient: &str) -> Result<(), (u16, String)> {
+        if *op_code == OpCode::FileOffer {
+            let Some(session) = self.session_of(recipient) else {
+                return Err((error_codes::USER_NOT_FOUND, format!("User '{}' is not connected", recipient)));
+            };
+            if !session.features.iter().any(|f| f == features::FILES) {
+                return Err((error_codes::INVALID_MESSAGE, format!("User '{}' does not accept files", recipient)));
+            }
+            if self.transfers.get(&transfer_id).is_some_and(|transfer| transfer.sender != sender) {
+                return Err((error_codes::FORBIDDEN, format!("Transfer {} belongs to another user", transfer_id)));
+            }
+            self.transfers.insert(transfer_id, Transfer {
+                sender: sender.to_string(),
+                recipient: recipient.to_string(),
+                accepted: false,
+            });
+            return Ok(());
+        }
+
+        let Some(transfer) = self.transfers.get_mut(&transfer_id) else {
+            return Err((error_codes::INVALID_MESSAGE, format!("Unknown transfer {}", transfer_id)));
+        };
+        let from_sender = transfer.sender == sender && transfer.recipient == recipient;
+        let from_recipient = transfer.recipient == sender && transfer.sender == recipient;
+        match op_code {
+            OpCode::FileAccept if from_recipient => transfer.accepted = true,
+            OpCode::FileReject if from_sender || from_recipient => {
+                self.transfers.remove(&transfer_id);
+            }
+            OpCode::FileChunk | OpCode::FileComplete if from_sender && !transfer.accepted => {
+                return Err((error_codes::FORBIDDEN, format!("Transfer {} was not accepted", transfer_id)));
+            }
+            OpCode::FileChunk if from_sender => {}
+            OpCode::FileComplete if from_sender => {
+                self.transfers.remove(&transfer_id);
+            }
+            _ => return Err((error_codes::FORBIDDEN, format!("Not allowed for transfer {}", transfer_id))),
         }
+        Ok(())
+    }
+
+    /// Indique si la trame prolonge un transfert en cours de `username`: morceau
+    /// d'un transfert accepté dont il est l'expéditeur, ou accusé du destinataire
+    pub fn in_transfer(&self, op_code: &OpCode, transfer_id: Uuid, username: &str) -> bool {
+        self.transfers.get(&transfer_id).is_some_and(|transfer| match op_code {
+            OpCode::FileChunk => transfer.accepted && transfer.sender == username,
+            OpCode::FileAccept => transfer.recipient == username,
+            _ => false,
+        })
+    }
+
+    /// Abandonne les transferts d'un utilisateur qui se déconnecte; renvoie
+    /// l'identifiant et l'autre participant de chacun
+    pub fn abort_transfers(&mut self, username: &str) -> Vec<(Uuid, String)> {
+        let mut aborted = Vec::new();
+        self.transfers.retain(|id, transfer| {
+            let peer = if transfer.sender == username {
+                &transfer.recipient
+            } else if transfer.recipient == username {
+                &transfer.sender
+            } else {
+                return true;
+            };
+            aborted.push((*id, peer.clone()));
+            false
+        });
+        aborted
+    }
+
+    /// Indique si un utilisateur est actuellement connecté
+    pub fn is_connected(&self, username: &str) -> bool {
+        self.username_to_session.contains_key(username)
     }
 
     /// Obtient la liste des utilisateurs connectés
     pub fn get_connected_users(&self) -> Vec<String> {
         self.sessions.values().map(|s| s.username.clone()).collect()
     }
+
+    /// Présence des utilisateurs connectés, triés par nom
+    pub fn get_presences(&self) -> Vec<UserPresence> {
+        let mut presences: Vec<UserPresence> = self.sessions.values().map(Self::presence_of).collect();
+        presences.sort_by(|a, b| a.username.cmp(&b.username));
+        presences
+    }
+
+    /// Ajoute un utilisateur à un salon, créé au besoin; `false` s'il en était déjà membre
+    pub fn join_room(&mut self, room: &str, username: &str) -> bool {
+        self.rooms
+            .entry(room.to_string())
+            .or_default()
+            .insert(username.to_string())
+    }
+
+    /// Retire un utilisateur d'un salon, supprimé lorsqu'il devient vide;
+    /// `false` s'il n'en était pas membre
+    pub fn leave_room(&mut self, room: &str, username: &str) -> bool {
+        let Some(members) = self.rooms.get_mut(room) else {
+            return false;
+        };
+        let removed = members.remove(username);
+        if members.is_empty() {
+            self.rooms.remove(room);
+        }
+        removed
+    }
+
+    /// Retire un utilisateur de tous ses salons
+    pub fn leave_all_rooms(&mut self, username: &str) {
+        for members in self.rooms.values_mut() {
+            members.remove(username);
+        }
+        self.rooms.retain(|_, members| !members.is_empty());
+    }
+
+    /// Indique si un utilisateur est membre d'un salon
+    pub fn is_member(&self, room: &str, username: &str) -> bool {
+        self.rooms.get(room).is_some_and(|members| members.contains(username))
+    }
+
+    /// Salons dont l'utilisateur est membre, triés par nom
+    pub fn rooms_of(&self, username: &str) -> Vec<String> {
+        let mut rooms: Vec<String> = self.rooms
+            .iter()
+            .filter(|(_, members)| members.contains(username))
+            .map(|(room, _)| room.clone())
+            .collect();
+        rooms.sort();
+        rooms
+    }
+
+    /// Obtient la liste des salons, triée par nom
+    pub fn get_rooms(&self) -> Vec<RoomInfo> {
+        let mut rooms: Vec<RoomInfo> = self.rooms
+            .iter()
+            .map(|(name, members)| RoomInfo { name: name.clone(), members: members.len() })
+            .collect();
+        rooms.sort_by(|a, b| a.name.cmp(&b.name));
+        rooms
+    }
+}
+
+impl Default for ServerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Connexion d'un client, découpée en messages par le codec du protocole
+type Connection = Framed<Box<dyn Transport>, MessageCodec>;
+
+/// Paramètres du serveur
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Taille maximale d'une trame reçue; au-delà la connexion est fermée
+    pub max_frame_size: usize,
+    /// Nombre maximal de messages en attente pour un utilisateur déconnecté
+    pub offline_queue_limit: usize,
+    /// Durée au-delà de laquelle un message en attente n'est plus remis
+    pub offline_message_ttl: chrono::Duration,
+    /// Silence au bout duquel le serveur envoie un `Ping` (clients ayant négocié `heartbeat`)
+    pub ping_interval: Duration,
+    /// Silence au bout duquel la session est fermée et le nom d'utilisateur libéré
+    pub idle_timeout: Duration,
+    /// Délai pendant lequel une session coupée peut être reprise avec son jeton
+    pub resume_window: chrono::Duration,
+    /// Utilisateurs ayant le rôle d'administrateur
+    pub admins: HashSet<String>,
+    /// Nombre maximal de caractères d'un message texte
+    pub max_message_length: usize,
+    /// Messages qu'une session peut envoyer d'un coup
+    pub rate_burst: u32,
+    /// Messages par seconde accordés à une session sur la durée
+    pub rate_per_second: f64,
+    /// Trames d'un transfert en cours (morceaux et accusés) qu'une session peut envoyer d'un coup
+    pub transfer_burst: u32,
+    /// Trames d'un transfert en cours par seconde accordées à une session sur la durée
+    pub transfer_per_second: f64,
+    /// Dépassements de limites tolérés avant la fermeture de la session
+    pub max_violations: u32,
+    /// Inactivité de l'utilisateur au bout de laquelle il passe absent
+    pub away_after: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            offline_queue_limit: DEFAULT_OFFLINE_QUEUE_LIMIT,
+            offline_message_ttl: DEFAULT_OFFLINE_MESSAGE_TTL,
+            ping_interval: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(90),
+            resume_window: chrono::Duration::minutes(2),
+            admins: HashSet::new(),
+            max_message_length: DEFAULT_MAX_MESSAGE_LENGTH,
+            rate_burst: DEFAULT_RATE_BURST,
+            rate_per_second: DEFAULT_RATE_PER_SECOND,
+            transfer_burst: DEFAULT_TRANSFER_BURST,
+            transfer_per_second: DEFAULT_TRANSFER_PER_SECOND,
+            max_violations: DEFAULT_MAX_VIOLATIONS,
+            away_after: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Décision prise à chaque vérification de l'activité d'une connexion
+#[derive(Debug, PartialEq)]
+enum Heartbeat {
+    /// Le client s'est manifesté récemment
+    Alive,
+    /// Le client est silencieux: on vérifie qu'il est toujours là
+    Ping,
+    /// Le client est resté silencieux trop longtemps: la session est fermée
+    Expired,
+}
+
+impl ServerConfig {
+    /// Vérifie la longueur d'un message (ou de sa modification) et consomme un jeton du débit de la session
+    fn check_limits(&self, msg: &Message, bucket: &mut TokenBucket) -> Result<(), String> {
+        if let MessagePayload::Text { content } | MessagePayload::Edit { content, .. } = &msg.payload {
+            let length = content.chars().count();
+            if length > self.max_message_length {
+                return Err(format!("Message too long: {} characters (max {})", length, self.max_message_length));
+            }
+        }
+        if !bucket.try_acquire() {
+            return Err(format!("Rate limit exceeded ({} messages per second)", self.rate_per_second));
+        }
+        Ok(())
+    }
+
+    /// Consomme un jeton du débit des transferts de la session
+    fn check_transfer_rate(&self, bucket: &mut TokenBucket) -> Result<(), String> {
+        if !bucket.try_acquire() {
+            return Err(format!("Transfer rate limit exceeded ({} frames per second)", self.transfer_per_second));
+        }
+        Ok(())
+    }
+
+    fn heartbeat(&self, idle: Duration, heartbeat_negotiated: bool) -> Heartbeat {
+        if idle >= self.idle_timeout {
+            Heartbeat::Expired
+        } else if idle >= self.ping_interval && heartbeat_negotiated {
+            Heartbeat::Ping
+        } else {
+            Heartbeat::Alive
+        }
+    }
+}
+
+/// Ressources partagées par toutes les connexions, et adresse du client
+#[derive(Clone)]
+struct ServerContext {
+    state: Arc<Mutex<ServerState>>,
+    accounts: Arc<Mutex<AccountStore>>,
+    history: Arc<Mutex<MessageHistory>>,
+    offline: Arc<Mutex<OfflineQueue>>,
+    broadcast_tx: broadcast::Sender<Message>,
+    config: Arc<ServerConfig>,
+    plugins: Arc<Plugins>,
+    bans: Arc<Mutex<BanList>>,
+    peer_ip: IpAddr,
+    /// Entrée du canal des transferts de cette connexion, confiée à sa session
+    transfers_tx: mpsc::Sender<Message>,
+    /// Signal de reprise de la session de cette connexion, confié à sa session
+    takeover: Arc<Notify>,
 }
 
 /// Serveur de messagerie
 pub struct MessageServer {
     state: Arc<Mutex<ServerState>>,
+    accounts: Arc<Mutex<AccountStore>>,
+    history: Arc<Mutex<MessageHistory>>,
+    offline: Arc<Mutex<OfflineQueue>>,
+    bans: Arc<Mutex<BanList>>,
     broadcast_tx: broadcast::Sender<Message>,
+    config: Arc<ServerConfig>,
+    /// Accepteur TLS; sans lui les connexions sont en clair
+    tls: Option<TlsAcceptor>,
+    plugins: Plugins,
 }
 
 impl MessageServer {
@@ -79,27 +539,111 @@ impl MessageServer {
         
         Self {
             state: Arc::new(Mutex::new(ServerState::new())),
+            accounts: Arc::new(Mutex::new(AccountStore::in_memory())),
+            history: Arc::new(Mutex::new(MessageHistory::in_memory(DEFAULT_MAX_HISTORY_MESSAGES))),
+            offline: Arc::new(Mutex::new(OfflineQueue::default())),
+            bans: Arc::new(Mutex::new(BanList::in_memory())),
             broadcast_tx,
+            config: Arc::new(ServerConfig::default()),
+            tls: None,
+            plugins: Plugins::default(),
         }
     }
 
+    /// Remplace les paramètres par défaut du serveur
+    pub fn with_config(mut self, config: ServerConfig) -> Self {
+        self.offline = Arc::new(Mutex::new(OfflineQueue::new(
+            config.offline_queue_limit,
+            config.offline_message_ttl,
+        )));
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// Utilise l'annuaire de comptes fourni (en mémoire par défaut)
+    pub fn with_accounts(mut self, accounts: AccountStore) -> Self {
+        self.accounts = Arc::new(Mutex::new(accounts));
+        self
+    }
+
+    /// Utilise l'historique fourni (en mémoire par défaut)
+    pub fn with_history(mut self, history: MessageHistory) -> Self {
+        self.history = Arc::new(Mutex::new(history));
+        self
+    }
+
+    /// Utilise la liste de bannissements fournie (en mémoire par défaut)
+    pub fn with_bans(mut self, bans: BanList) -> Self {
+        self.bans = Arc::new(Mutex::new(bans));
+        self
+    }
+
+    /// Chiffre les connexions avec TLS (voir `tls::server_acceptor`)
+    pub fn with_tls(mut self, acceptor: TlsAcceptor) -> Self {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    /// Enregistre un plugin (bot), appelé pour chaque message et chaque connexion
+    pub fn with_plugin(mut self, plugin: impl Plugin + 'static) -> Self {
+        self.plugins.register(plugin);
+        self
+    }
+
     /// Lance le serveur sur l'adresse spécifiée
     pub async fn start(&self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
         let listener = TcpListener::bind(addr).await?;
         println!("🚀 Serveur de messagerie démarré sur {}", addr);
-        println!("📋 Protocole de messagerie personnalisé v1.0");
+        println!("📋 Protocole de messagerie personnalisé v{}", PROTOCOL_VERSION);
+        if self.tls.is_some() {
+            println!("🔒 Connexions chiffrées par TLS");
+        }
+        if !self.plugins.names().is_empty() {
+            println!("🤖 Plugins: {}", self.plugins.names().join(", "));
+        }
         println!("🔄 En attente de connexions...\n");
 
+        self.serve(listener).await?;
+        Ok(())
+    }
+
+    /// Accepte les connexions sur un socket déjà ouvert (port éphémère des tests, par exemple)
+    pub async fn serve(&self, listener: TcpListener) -> std::io::Result<()> {
+        let plugins = Arc::new(self.plugins.clone());
         loop {
             let (stream, addr) = listener.accept().await?;
             println!("🔗 Nouvelle connexion depuis: {}", addr);
 
-            let state = Arc::clone(&self.state);
-            let broadcast_tx = self.broadcast_tx.clone();
+            let (transfers_tx, transfers_rx) = mpsc::channel(TRANSFER_CHANNEL_CAPACITY);
+            let context = ServerContext {
+                state: Arc::clone(&self.state),
+                accounts: Arc::clone(&self.accounts),
+                history: Arc::clone(&self.history),
+                offline: Arc::clone(&self.offline),
+                broadcast_tx: self.broadcast_tx.clone(),
+                config: Arc::clone(&self.config),
+                plugins: Arc::clone(&plugins),
+                bans: Arc::clone(&self.bans),
+                peer_ip: addr.ip(),
+                transfers_tx,
+                takeover: Arc::new(Notify::new()),
+            };
             let broadcast_rx = self.broadcast_tx.subscribe();
+            let tls = self.tls.clone();
 
             tokio::spawn(async move {
-                if let Err(e) = Self::handle_client(stream, state, broadcast_tx, broadcast_rx).await {
+                // La poignée de main TLS se fait dans la tâche du client pour ne pas bloquer les autres
+                let stream: Box<dyn Transport> = match tls {
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => Box::new(stream),
+                        Err(e) => {
+                            eprintln!("❌ Échec de la négociation TLS avec {}: {}", addr, e);
+                            return;
+                        }
+                    },
+                    None => Box::new(stream),
+                };
+                if let Err(e) = Self::handle_client(stream, context, broadcast_rx, transfers_rx).await {
                     eprintln!("❌ Erreur avec le client {}: {}", addr, e);
                 }
             });
@@ -108,57 +652,126 @@ impl MessageServer {
 
     /// Gère un client connecté
     async fn handle_client(
-        mut stream: TcpStream,
-        state: Arc<Mutex<ServerState>>,
-        broadcast_tx: broadcast::Sender<Message>,
+        stream: Box<dyn Transport>,
+        context: ServerContext,
         mut broadcast_rx: broadcast::Receiver<Message>,
+        mut transfers_rx: mpsc::Receiver<Message>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let (reader, mut writer) = stream.split();
-        let mut buf_reader = BufReader::new(reader);
+        let ServerContext { state, broadcast_tx, config, plugins, bans, peer_ip, takeover, .. } = &context;
+        let mut connection = Framed::new(
+            stream,
+            MessageCodec::new(Framing::Json).with_max_frame_size(config.max_frame_size)
+        );
+
+        // Une adresse bannie est refusée avant tout échange
+        let ip_ban = bans.lock().await.find(None, Some(*peer_ip)).map(|ban| ban.sanction.to_string());
+        if let Some(sanction) = ip_ban {
+            println!("⛔ Connexion refusée depuis l'adresse bannie {}", peer_ip);
+            let error_msg = Message::error(error_codes::FORBIDDEN, format!("Address banned {}", sanction));
+            let _ = Self::send_message_to_writer(&mut connection, &error_msg).await;
+            return Ok(());
+        }
+
         let mut session_id: Option<Uuid> = None;
         let mut username: Option<String> = None;
+        // Fonctionnalités négociées par Hello (None tant que le client ne s'est pas présenté)
+        let mut features: Option<Vec<String>> = None;
+        // Toute trame reçue, y compris un Pong, prouve que le client est là
+        let mut last_activity = Instant::now();
+        // Dernière action de l'utilisateur lui-même, pour le passer absent
+        let mut last_action = Instant::now();
+        let mut heartbeat = tokio::time::interval(config.ping_interval.min(config.idle_timeout).min(config.away_after));
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // Expulsé par un administrateur ou pour abus: la session ne pourra pas être reprise
+        let mut kicked = false;
+        let mut rate_limiter = TokenBucket::new(config.rate_burst, config.rate_per_second);
+        let mut transfer_limiter = TokenBucket::new(config.transfer_burst, config.transfer_per_second);
+        let mut violations = 0;
 
-        let mut line = String::new();
         loop {
             tokio::select! {
                 // Lecture des messages du client
-                result = buf_reader.read_line(&mut line) => {
+                result = connection.next() => {
+                    last_activity = Instant::now();
                     match result {
-                        Ok(0) => break, // Connexion fermée
-                        Ok(_) => {
-                            let trimmed = line.trim();
-                            if !trimmed.is_empty() {
-                                match Message::from_json(trimmed) {
-                                    Ok(msg) => {
-                                        if let Err(e) = msg.validate() {
-                                            let error_msg = Message::error(
-                                                error_codes::INVALID_MESSAGE,
-                                                format!("Invalid message: {}", e)
-                                            );
-                                            Self::send_message_to_writer(&mut writer, &error_msg).await?;
-                                        } else {
-                                            Self::process_message(
-                                                msg,
-                                                &mut session_id,
-                                                &mut username,
-                                                &state,
-                                                &broadcast_tx,
-                                                &mut writer
-                                            ).await?;
+                        None => break, // Connexion fermée
+                        Some(Ok(Ok(msg))) => {
+                            // Les Pong répondent au serveur et ne comptent pas dans le débit; les morceaux
+                            // d'un transfert en cours et leurs accusés ont leur propre débit, plus large, et
+                            // toute autre trame de transfert compte comme un message
+                            let in_transfer = match (&msg.op_code, msg.payload.transfer_id(), username.as_deref()) {
+                                (OpCode::FileChunk | OpCode::FileAccept, Some(transfer_id), Some(current_username)) => {
+                                    state.lock().await.in_transfer(&msg.op_code, transfer_id, current_username)
+                                }
+                                _ => false,
+                            };
+                            let checked = match msg.op_code {
+                                OpCode::Pong => Ok(()),
+                                _ if in_transfer => config.check_transfer_rate(&mut transfer_limiter),
+                                _ => config.check_limits(&msg, &mut rate_limiter),
+                            };
+                            if let Err(e) = checked {
+                                violations += 1;
+                                if violations >= config.max_violations {
+                                    println!(
+                                        "🚫 {} déconnecté après {} dépassements de limites",
+                                        username.as_deref().unwrap_or("Client anonyme"),
+                                        violations
+                                    );
+                                    let error_msg = Message::error(
+                                        error_codes::LIMIT_EXCEEDED,
+                                        format!("{}; disconnected after {} violations", e, violations)
+                                    );
+                                    let _ = Self::send_message_to_writer(&mut connection, &error_msg).await;
+                                    kicked = true;
+                                    break;
+                                }
+                                let error_msg = Message::error(error_codes::LIMIT_EXCEEDED, e);
+                                Self::send_message_to_writer(&mut connection, &error_msg).await?;
+                            } else if let Err(e) = msg.validate() {
+                                let error_msg = Message::error(
+                                    error_codes::INVALID_MESSAGE,
+                                    format!("Invalid message: {}", e)
+                                );
+                                Self::send_message_to_writer(&mut connection, &error_msg).await?;
+                            } else {
+                                if is_user_action(&msg.op_code) {
+                                    last_action = Instant::now();
+                                    // Revenu après une absence automatique (sauf s'il choisit lui-même un statut)
+                                    let back = match username {
+                                        Some(ref current_username) if msg.op_code != OpCode::Presence => {
+                                            state.lock().await.mark_active(current_username)
                                         }
-                                    }
-                                    Err(e) => {
-                                        let error_msg = Message::error(
-                                            error_codes::INVALID_MESSAGE,
-                                            format!("JSON parse error: {}", e)
-                                        );
-                                        Self::send_message_to_writer(&mut writer, &error_msg).await?;
+                                        _ => None,
+                                    };
+                                    if let Some(presence) = back {
+                                        Self::announce_presence(presence, &features, broadcast_tx, &mut connection).await?;
                                     }
                                 }
+                                Self::process_message(
+                                    msg,
+                                    &mut session_id,
+                                    &mut username,
+                                    &mut features,
+                                    &context,
+                                    &mut connection
+                                ).await?;
                             }
-                            line.clear();
                         }
-                        Err(e) => {
+                        Some(Ok(Err(e))) => {
+                            let error_msg = Message::error(error_codes::INVALID_MESSAGE, e);
+                            Self::send_message_to_writer(&mut connection, &error_msg).await?;
+                        }
+                        Some(Err(CodecError::FrameTooLarge { size, max })) => {
+                            // Le flux ne peut plus être découpé: on prévient puis on ferme
+                            let error_msg = Message::error(
+                                error_codes::FRAME_TOO_LARGE,
+                                format!("Frame too large: {} bytes (max {})", size, max)
+                            );
+                            let _ = Self::send_message_to_writer(&mut connection, &error_msg).await;
+                            break;
+                        }
+                        Some(Err(e)) => {
                             eprintln!("Erreur de lecture: {}", e);
                             break;
                         }
@@ -176,32 +789,163 @@ impl MessageServer {
                                 }
                             }
 
-                            let json = format!("{}\n", msg.to_json().unwrap_or_default());
-                            if let Err(_) = writer.write_all(json.as_bytes()).await {
+                            // Les messages privés ne sont remis qu'à leur destinataire
+                            if let Some(ref recipient) = msg.recipient {
+                                if username.as_ref() != Some(recipient) {
+                                    continue;
+                                }
+                            }
+
+                            // Les accusés ne sont remis qu'aux clients qui les ont négociés
+                            if matches!(msg.op_code, OpCode::Delivered | OpCode::Read)
+                                && !has_feature(&features, features::RECEIPTS) {
+                                continue;
+                            }
+
+                            // Les transferts de fichiers aussi
+                            if msg.payload.transfer_id().is_some() && !has_feature(&features, features::FILES) {
+                                continue;
+                            }
+
+                            // Ainsi que les statuts et les indications de saisie
+                            if matches!(msg.op_code, OpCode::Presence | OpCode::Typing)
+                                && !has_feature(&features, features::PRESENCE) {
+                                continue;
+                            }
+
+                            // Et les corrections de messages
+                            if msg.payload.corrected_id().is_some() && !has_feature(&features, features::EDITS) {
+                                continue;
+                            }
+
+                            // Les messages d'un salon ne sont remis qu'à ses membres,
+                            // et jamais aux clients qui n'ont pas négocié les salons
+                            if let Some(ref room) = msg.room {
+                                if !has_feature(&features, features::ROOMS) {
+                                    continue;
+                                }
+                                let is_member = match username {
+                                    Some(ref current_username) => state.lock().await.is_member(room, current_username),
+                                    None => false,
+                                };
+                                if !is_member {
+                                    continue;
+                                }
+                            }
+
+                            if Self::send_message_to_writer(&mut connection, &msg).await.is_err() {
+                                break;
+                            }
+
+                            // Une expulsion adressée à ce client ferme sa connexion
+                            if msg.recipient.is_some()
+                                && matches!(msg.payload, MessagePayload::Error { code: error_codes::KICKED, .. }) {
+                                kicked = true;
+                                break;
+                            }
+                        }
+                        // Client trop lent: les messages manqués sont perdus, la connexion reste ouverte
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            println!(
+                                "⚠️  {} a manqué {} message(s) diffusé(s)",
+                                username.as_deref().unwrap_or("Client anonyme"),
+                                skipped
+                            );
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+                
+                // Trames de transfert adressées à ce client, hors du canal de diffusion
+                Some(msg) = transfers_rx.recv() => {
+                    if Self::send_message_to_writer(&mut connection, &msg).await.is_err() {
+                        break;
+                    }
+                }
+                
+                // Session reprise par une autre connexion: celle-ci n'a plus lieu d'être
+                _ = takeover.notified() => {
+                    println!(
+                        "🔁 Connexion de {} remplacée par une reprise de session",
+                        username.as_deref().unwrap_or("Client anonyme")
+                    );
+                    let error_msg = Message::error(
+                        error_codes::KICKED,
+                        "Session resumed from another connection".to_string()
+                    );
+                    let _ = Self::send_message_to_writer(&mut connection, &error_msg).await;
+                    break;
+                }
+                
+                // Vérification périodique de l'activité du client
+                _ = heartbeat.tick() => {
+                    let idle = last_activity.elapsed();
+                    match config.heartbeat(idle, has_feature(&features, features::HEARTBEAT)) {
+                        Heartbeat::Alive => {}
+                        Heartbeat::Ping => {
+                            if Self::send_message_to_writer(&mut connection, &Message::ping()).await.is_err() {
                                 break;
                             }
                         }
-                        Err(_) => break, // Canal fermé
+                        Heartbeat::Expired => {
+                            println!(
+                                "⏱️  {} inactif depuis {} s, fermeture de la session",
+                                username.as_deref().unwrap_or("Client anonyme"),
+                                idle.as_secs()
+                            );
+                            let error_msg = Message::error(
+                                error_codes::IDLE_TIMEOUT,
+                                format!("Idle timeout ({} s without traffic)", config.idle_timeout.as_secs())
+                            );
+                            let _ = Self::send_message_to_writer(&mut connection, &error_msg).await;
+                            break;
+                        }
+                    }
+
+                    // Utilisateur connecté mais inactif: il passe absent
+                    if let Some(ref current_username) = username {
+                        if last_action.elapsed() >= config.away_after {
+                            let away = state.lock().await.mark_away(current_username);
+                            if let Some(presence) = away {
+                                println!("💤 {} est absent (inactif depuis {} s)", current_username, last_action.elapsed().as_secs());
+                                if Self::announce_presence(presence, &features, broadcast_tx, &mut connection).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
                     }
                 }
             }
         }
 
-        // Nettoyage lors de la déconnexion
+        // Nettoyage lors de la déconnexion; la session reste reprenable si le client
+        // a négocié la reprise et n'a pas été expulsé
         if let Some(id) = session_id {
             let mut state_guard = state.lock().await;
-            if let Some(disconnected_user) = state_guard.remove_session(&id) {
-                println!("👋 Utilisateur déconnecté: {}", disconnected_user);
+            let removed = if has_feature(&features, features::RESUME) && !kicked {
+                state_guard.suspend_session(&id)
+            } else {
+                state_guard.remove_session(&id)
+            };
+            if let Some(session) = removed {
+                Self::abort_transfers(&mut state_guard, &session.username, broadcast_tx);
+                println!(
+                    "👋 Utilisateur déconnecté: {} (session {}, connecté depuis {})",
+                    session.username,
+                    session.id,
+                    session.connected_at.format("%H:%M:%S")
+                );
                 
                 // Notifier les autres utilisateurs
                 let disconnect_msg = Message::new(
                     OpCode::MessageReceived,
-                    Some("Serveur".to_string()),
+                    Some(SERVER_SENDER.to_string()),
                     MessagePayload::Text {
-                        content: format!("{} a quitté le chat", disconnected_user)
+                        content: format!("{} a quitté le chat", session.username)
                     }
                 );
                 let _ = broadcast_tx.send(disconnect_msg);
+                plugins.disconnected(&session.username, broadcast_tx);
             }
         }
 
@@ -213,41 +957,231 @@ impl MessageServer {
         msg: Message,
         session_id: &mut Option<Uuid>,
         username: &mut Option<String>,
-        state: &Arc<Mutex<ServerState>>,
-        broadcast_tx: &broadcast::Sender<Message>,
-        writer: &mut tokio::net::tcp::WriteHalf<'_>,
+        negotiated_features: &mut Option<Vec<String>>,
+        context: &ServerContext,
+        writer: &mut Connection,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        let ServerContext { state, accounts, history, offline, broadcast_tx, config, plugins, bans, peer_ip, transfers_tx, takeover } = context;
+
+        // Un client 1.x ne connaît pas Hello et commence par Register ou Connect:
+        // il est accepté avec les seules fonctionnalités de base
+        if negotiated_features.is_none() && matches!(msg.op_code, OpCode::Register | OpCode::Connect) {
+            println!("🤝 Client sans Hello: protocole v1.x, fonctionnalités de base");
+            *negotiated_features = Some(Vec::new());
+        }
+
+        // Toute autre opération doit être précédée de la négociation Hello
+        if negotiated_features.is_none() && msg.op_code != OpCode::Hello {
+            let error_msg = Message::error(
+                error_codes::UPGRADE_REQUIRED,
+                format!("Hello required before {:?} (protocol v{})", msg.op_code, PROTOCOL_VERSION)
+            );
+            Self::send_message_to_writer(writer, &error_msg).await?;
+            return Ok(());
+        }
+
         match msg.op_code {
+            OpCode::Hello => {
+                if let MessagePayload::Hello { version, features: client_features } = msg.payload {
+                    if negotiated_features.is_some() {
+                        let error_msg = Message::error(
+                            error_codes::INVALID_MESSAGE,
+                            "Hello already received".to_string()
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                    } else if !version.is_compatible_with(&PROTOCOL_VERSION) {
+                        println!("⛔ Client en protocole v{} refusé (serveur v{})", version, PROTOCOL_VERSION);
+                        let error_msg = Message::error(
+                            error_codes::UPGRADE_REQUIRED,
+                            format!("Unsupported protocol version {} (server speaks {})", version, PROTOCOL_VERSION)
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                    } else {
+                        // Seules les fonctionnalités connues des deux côtés sont retenues
+                        let common: Vec<String> = client_features
+                            .into_iter()
+                            .filter(|feature| features::SUPPORTED.contains(&feature.as_str()))
+                            .collect();
+                        println!("🤝 Client en protocole v{}, fonctionnalités: {:?}", version, common);
+                        Self::send_message_to_writer(writer, &Message::hello_ack(common.clone())).await?;
+                        *negotiated_features = Some(common);
+                    }
+                }
+            }
+
+
+            OpCode::Register => {
+                if let MessagePayload::Register { username: new_username, password } = msg.payload {
+                    if accounts.lock().await.exists(&new_username) {
+                        let error_msg = Message::error(
+                            error_codes::USER_EXISTS,
+                            format!("Account '{}' already exists", new_username)
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+                    if plugins.is_plugin(&new_username) {
+                        let error_msg = Message::error(
+                            error_codes::USER_EXISTS,
+                            format!("Name '{}' is reserved for a bot", new_username)
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+
+                    // Le hash Argon2 est coûteux: hors du runtime asynchrone
+                    let hashed = tokio::task::spawn_blocking(move || accounts::hash_password(&password)).await?;
+                    let result = match hashed {
+                        Ok(password_hash) => accounts.lock().await.insert(new_username.clone(), password_hash),
+                        Err(e) => Err(e),
+                    };
+
+                    match result {
+                        Ok(()) => {
+                            println!("📝 Nouveau compte créé: {}", new_username);
+                            let ack = Message::new(OpCode::RegisterAck, None, MessagePayload::Empty);
+                            Self::send_message_to_writer(writer, &ack).await?;
+                        }
+                        Err(e) => {
+                            eprintln!("❌ Impossible de créer le compte {}: {}", new_username, e);
+                            let error_msg = Message::error(
+                                error_codes::INTERNAL_ERROR,
+                                "Could not create account".to_string()
+                            );
+                            Self::send_message_to_writer(writer, &error_msg).await?;
+                        }
+                    }
+                }
+            }
+
+            OpCode::Connect if username.is_some() => {
+                let error_msg = Message::error(
+                    error_codes::INVALID_MESSAGE,
+                    "Already connected".to_string()
+                );
+                Self::send_message_to_writer(writer, &error_msg).await?;
+            }
+
             OpCode::Connect => {
-                if let MessagePayload::Connect { username: new_username } = msg.payload {
+                if let MessagePayload::Connect { username: new_username, password, framing, resume_token } = msg.payload {
+                    if framing == Framing::Binary && !has_feature(negotiated_features, features::BINARY_FRAMING) {
+                        let error_msg = Message::error(
+                            error_codes::INVALID_MESSAGE,
+                            "Binary framing was not negotiated".to_string()
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+
+                    let password_hash = accounts.lock().await.password_hash(&new_username);
+                    let authenticated = match password_hash {
+                        Some(hash) => tokio::task::spawn_blocking(move || {
+                            accounts::verify_password(&password, &hash)
+                        }).await?,
+                        None => false,
+                    };
+                    if !authenticated {
+                        println!("🔒 Échec d'authentification pour {}", new_username);
+                        let error_msg = Message::error(
+                            error_codes::UNAUTHORIZED,
+                            "Invalid username or password".to_string()
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+
+                    let ban = bans.lock().await.find(Some(&new_username), Some(*peer_ip)).map(|ban| ban.sanction.to_string());
+                    if let Some(sanction) = ban {
+                        println!("⛔ Connexion refusée pour {}, banni", new_username);
+                        let error_msg = Message::error(error_codes::FORBIDDEN, format!("Banned {}", sanction));
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+
                     let mut state_guard = state.lock().await;
+                    let resumed = match resume_token {
+                        Some(token) if has_feature(negotiated_features, features::RESUME) => {
+                            state_guard.resume_session(token, &new_username, config.resume_window)
+                        }
+                        _ => None,
+                    };
                     match state_guard.add_session(new_username.clone()) {
                         Ok(new_session_id) => {
                             *session_id = Some(new_session_id);
                             *username = Some(new_username.clone());
+                            if let Some(session) = state_guard.sessions.get_mut(&new_session_id) {
+                                session.ip = Some(*peer_ip);
+                                session.features = negotiated_features.clone().unwrap_or_default();
+                                session.transfers = Some(transfers_tx.clone());
+                                session.takeover = Some(Arc::clone(takeover));
+                                if config.admins.contains(&new_username) {
+                                    session.role = Role::Admin;
+                                }
+                            }
+                            
+                            // Reprise: l'utilisateur retrouve ses salons
+                            if let Some(ref suspended) = resumed {
+                                for room in &suspended.rooms {
+                                    state_guard.join_room(room, &new_username);
+                                }
+                                println!("🔁 Session de {} reprise ({} salon(s))", new_username, suspended.rooms.len());
+                            } else {
+                                println!("✅ Nouvel utilisateur connecté: {}", new_username);
+                            }
+                            
+                            // Les trames de connexion sont préparées sous le verrou de l'état, puis
+                            // écrites après l'avoir relâché: un client lent ne bloque pas les autres
+                            let ack = if has_feature(negotiated_features, features::RESUME) {
+                                Message::session_ack(state_guard.sessions[&new_session_id].resume_token, resumed.is_some())
+                            } else {
+                                Message::connect_ack()
+                            };
+                            let user_list = Message::user_list(state_guard.get_presences());
+                            
+                            // Messages reçus pendant l'absence, dans l'ordre d'envoi; retirés sous le
+                            // verrou pour qu'aucun message privé ne soit mis en attente entre-temps
+                            let pending = offline.lock().await.take(&new_username);
                             
-                            println!("✅ Nouvel utilisateur connecté: {}", new_username);
+                            // Puis ce qui a été dit pendant la coupure dans le chat général et ses salons
+                            let missed = match resumed {
+                                Some(ref suspended) => history.lock().await.missed_since(
+                                    &new_username,
+                                    &suspended.rooms,
+                                    suspended.disconnected_at
+                                ),
+                                None => Vec::new(),
+                            };
+                            drop(state_guard);
                             
-                            // Confirmation de connexion
-                            let ack = Message::connect_ack();
+                            // Confirmation de connexion, dernière trame au format JSON
                             Self::send_message_to_writer(writer, &ack).await?;
+                            if framing != Framing::Json {
+                                println!("📦 {} utilise le format {}", new_username, framing);
+                                writer.codec_mut().set_framing(framing);
+                            }
                             
-                            // Envoie la liste des utilisateurs
-                            let users = state_guard.get_connected_users();
-                            let user_list = Message::user_list(users);
                             Self::send_message_to_writer(writer, &user_list).await?;
                             
+                            if !pending.is_empty() {
+                                println!("📬 {} message(s) en attente remis à {}", pending.len(), new_username);
+                            }
+                            for queued_msg in pending.iter().chain(&missed) {
+                                Self::send_message_to_writer(writer, queued_msg).await?;
+                            }
+                            
                             // Notifie les autres utilisateurs
                             let join_msg = Message::new(
                                 OpCode::MessageReceived,
-                                Some("Serveur".to_string()),
+                                Some(SERVER_SENDER.to_string()),
                                 MessagePayload::Text {
                                     content: format!("{} a rejoint le chat", new_username)
                                 }
                             );
                             let _ = broadcast_tx.send(join_msg);
+                            plugins.connected(&new_username, broadcast_tx);
                         }
                         Err(e) => {
+                            drop(state_guard);
                             let error_msg = Message::error(error_codes::USER_EXISTS, e);
                             Self::send_message_to_writer(writer, &error_msg).await?;
                         }
@@ -256,9 +1190,12 @@ impl MessageServer {
             }
             
             OpCode::Disconnect => {
-                if let Some(id) = session_id {
+                // La connexion reste ouverte mais n'est plus associée à un utilisateur
+                *username = None;
+                if let Some(id) = session_id.take() {
                     let mut state_guard = state.lock().await;
-                    if let Some(disconnected_user) = state_guard.remove_session(id) {
+                    if let Some(session) = state_guard.remove_session(&id) {
+                        Self::abort_transfers(&mut state_guard, &session.username, broadcast_tx);
                         let ack = Message::new(
                             OpCode::DisconnectAck,
                             None,
@@ -269,45 +1206,660 @@ impl MessageServer {
                         // Notifier les autres
                         let disconnect_msg = Message::new(
                             OpCode::MessageReceived,
-                            Some("Serveur".to_string()),
+                            Some(SERVER_SENDER.to_string()),
                             MessagePayload::Text {
-                                content: format!("{} a quitté le chat", disconnected_user)
+                                content: format!("{} a quitté le chat", session.username)
                             }
                         );
                         let _ = broadcast_tx.send(disconnect_msg);
+                        plugins.disconnected(&session.username, broadcast_tx);
                     }
                 }
             }
             
             OpCode::SendMessage => {
-                if session_id.is_some() {
-                    // Diffuser le message à tous les clients connectés
-                    let broadcast_msg = Message::new(
-                        OpCode::MessageReceived,
-                        msg.sender,
-                        msg.payload
-                    );
-                    let _ = broadcast_tx.send(broadcast_msg);
-                } else {
+                let Some(current_username) = username.clone() else {
                     let error_msg = Message::error(
                         error_codes::UNAUTHORIZED,
                         "Not connected".to_string()
                     );
                     Self::send_message_to_writer(writer, &error_msg).await?;
-                }
-            }
-            
-            OpCode::ListUsers => {
-                if session_id.is_some() {
-                    let state_guard = state.lock().await;
-                    let users = state_guard.get_connected_users();
-                    let user_list = Message::user_list(users);
-                    Self::send_message_to_writer(writer, &user_list).await?;
-                } else {
-                    let error_msg = Message::error(
-                        error_codes::UNAUTHORIZED,
-                        "Not connected".to_string()
-                    );
+                    return Ok(());
+                };
+
+                // L'expéditeur est celui de la session, jamais celui annoncé par le client
+                let mut msg = msg;
+                if let Err(e) = Self::stamp_sender(&mut msg, &current_username) {
+                    println!("🚫 Tentative d'usurpation par {}: {}", current_username, e);
+                    let error_msg = Message::error(error_codes::FORBIDDEN, e);
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+
+                let mute = state.lock().await.muted(&current_username).map(|sanction| sanction.to_string());
+                if let Some(sanction) = mute {
+                    let error_msg = Message::error(error_codes::FORBIDDEN, format!("You are muted {}", sanction));
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+
+                // Les commandes sont destinées aux plugins et ne sont jamais relayées
+                match plugins.command(&msg, broadcast_tx) {
+                    Some(true) => return Ok(()),
+                    Some(false) => {
+                        let error_msg = Message::error(
+                            error_codes::INVALID_MESSAGE,
+                            "Unknown command".to_string()
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+                    None => {}
+                }
+
+                // Le message relayé garde l'identifiant choisi par l'expéditeur,
+                // pour que les accusés de réception puissent y faire référence:
+                // il ne doit pas déjà désigner un message conservé
+                if history.lock().await.find(msg.id).is_some() {
+                    let error_msg = Message::error(
+                        error_codes::INVALID_MESSAGE,
+                        format!("Message id {} is already in use", msg.id)
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+
+                if let Some(room) = msg.room.clone() {
+                    // Message de salon: l'expéditeur doit en être membre
+                    if state.lock().await.is_member(&room, &current_username) {
+                        let mut room_msg = Message::new(
+                            OpCode::MessageReceived,
+                            msg.sender,
+                            msg.payload
+                        );
+                        room_msg.id = msg.id;
+                        room_msg.room = Some(room);
+                        Self::record(history, &room_msg).await;
+                        let _ = broadcast_tx.send(room_msg.clone());
+                        plugins.message(&room_msg, broadcast_tx);
+                    } else {
+                        let error_msg = Message::error(
+                            error_codes::FORBIDDEN,
+                            format!("You are not a member of room '{}'", room)
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                    }
+                } else {
+                    match msg.recipient {
+                        // Message privé: remis directement, ou mis en attente si le
+                        // destinataire a un compte mais n'est pas connecté
+                        Some(recipient) => {
+                            let mut private_msg = Message::new_with_recipient(
+                                OpCode::MessageReceived,
+                                msg.sender,
+                                recipient.clone(),
+                                msg.payload
+                            );
+                            private_msg.id = msg.id;
+                            let has_account = accounts.lock().await.exists(&recipient);
+
+                            // Le verrou de l'état est gardé pendant la mise en attente: le
+                            // destinataire ne peut pas se connecter entre les deux
+                            let state_guard = state.lock().await;
+                            let reply = if state_guard.is_connected(&recipient) {
+                                Self::record(history, &private_msg).await;
+                                let _ = broadcast_tx.send(private_msg.clone());
+                                plugins.message(&private_msg, broadcast_tx);
+                                None
+                            } else if plugins.is_plugin(&recipient) {
+                                // Adressé à un bot: seuls les plugins le reçoivent
+                                Self::record(history, &private_msg).await;
+                                plugins.message(&private_msg, broadcast_tx);
+                                None
+                            } else if has_account {
+                                match offline.lock().await.push(&recipient, private_msg.clone()) {
+                                    Ok(()) => {
+                                        Self::record(history, &private_msg).await;
+                                        plugins.message(&private_msg, broadcast_tx);
+                                        Some(Message::new_with_recipient(
+                                            OpCode::MessageReceived,
+                                            Some(SERVER_SENDER.to_string()),
+                                            current_username.clone(),
+                                            MessagePayload::Text {
+                                                content: format!("{} est hors ligne, le message lui sera remis à sa prochaine connexion", recipient)
+                                            }
+                                        ))
+                                    }
+                                    Err(e) => Some(Message::error(error_codes::QUEUE_FULL, e)),
+                                }
+                            } else {
+                                Some(Message::error(
+                                    error_codes::USER_NOT_FOUND,
+                                    format!("User '{}' does not exist", recipient)
+                                ))
+                            };
+                            drop(state_guard);
+
+                            if let Some(reply) = reply {
+                                Self::send_message_to_writer(writer, &reply).await?;
+                            }
+                        }
+                        // Diffuser le message à tous les clients connectés
+                        None => {
+                            let mut broadcast_msg = Message::new(
+                                OpCode::MessageReceived,
+                                msg.sender,
+                                msg.payload
+                            );
+                            broadcast_msg.id = msg.id;
+                            Self::record(history, &broadcast_msg).await;
+                            let _ = broadcast_tx.send(broadcast_msg.clone());
+                            plugins.message(&broadcast_msg, broadcast_tx);
+                        }
+                    }
+                }
+            }
+            
+            OpCode::Ping => {
+                Self::send_message_to_writer(writer, &Message::pong()).await?;
+            }
+
+            // L'activité a déjà été prise en compte à la réception
+            OpCode::Pong => {}
+
+            OpCode::Delivered | OpCode::Read if !has_feature(negotiated_features, features::RECEIPTS) => {
+                let error_msg = Message::error(
+                    error_codes::INVALID_MESSAGE,
+                    "Receipts were not negotiated".to_string()
+                );
+                Self::send_message_to_writer(writer, &error_msg).await?;
+            }
+
+            OpCode::Delivered | OpCode::Read => {
+                let Some(current_username) = username.clone() else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+
+                let mut receipt = msg;
+                if let Err(e) = Self::stamp_sender(&mut receipt, &current_username) {
+                    let error_msg = Message::error(error_codes::FORBIDDEN, e);
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+
+                // L'historique fait foi: seul le destinataire d'un message conservé
+                // (un membre de son salon, ou tout autre que l'auteur pour le chat
+                // général) peut en accuser réception ou lecture
+                let MessagePayload::Receipt { message_id } = receipt.payload else {
+                    let error_msg = Message::error(
+                        error_codes::INVALID_MESSAGE,
+                        "Receipt payload expected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+                let original = history.lock().await.find(message_id).cloned();
+                let Some(original) = original else {
+                    let error_msg = Message::error(
+                        error_codes::USER_NOT_FOUND,
+                        format!("Message {} not found", message_id)
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+                let received = match original.room {
+                    Some(ref room) => state.lock().await.is_member(room, &current_username),
+                    None => match original.recipient.as_deref() {
+                        Some(recipient) => recipient == current_username,
+                        None => original.sender.as_deref() != Some(current_username.as_str()),
+                    },
+                };
+                let Some(original_sender) = original.sender.filter(|_| received) else {
+                    let error_msg = Message::error(
+                        error_codes::FORBIDDEN,
+                        format!("Message {} was not addressed to you", message_id)
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+
+                // Remis comme un message privé à l'expéditeur d'origine, quel que soit le
+                // destinataire indiqué par le client; perdu s'il s'est déconnecté
+                receipt.recipient = Some(original_sender);
+                let _ = broadcast_tx.send(receipt);
+            }
+
+            OpCode::JoinRoom | OpCode::LeaveRoom | OpCode::ListRooms
+                if !has_feature(negotiated_features, features::ROOMS) => {
+                let error_msg = Message::error(
+                    error_codes::INVALID_MESSAGE,
+                    "Rooms were not negotiated".to_string()
+                );
+                Self::send_message_to_writer(writer, &error_msg).await?;
+            }
+
+            OpCode::JoinRoom | OpCode::LeaveRoom => {
+                if let (Some(current_username), MessagePayload::Room { room }) = (username.as_ref(), msg.payload) {
+                    let joining = msg.op_code == OpCode::JoinRoom;
+                    let changed = {
+                        let mut state_guard = state.lock().await;
+                        if joining {
+                            state_guard.join_room(&room, current_username)
+                        } else {
+                            state_guard.leave_room(&room, current_username)
+                        }
+                    };
+
+                    if !joining && !changed {
+                        let error_msg = Message::error(
+                            error_codes::FORBIDDEN,
+                            format!("You are not a member of room '{}'", room)
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+
+                    let ack = Message::new(
+                        if joining { OpCode::RoomJoined } else { OpCode::RoomLeft },
+                        None,
+                        MessagePayload::Room { room: room.clone() }
+                    );
+                    Self::send_message_to_writer(writer, &ack).await?;
+
+                    // Notifie les membres du salon
+                    if changed {
+                        println!("🏠 {} a {} #{}", current_username, if joining { "rejoint" } else { "quitté" }, room);
+                        let mut notice = Message::new(
+                            OpCode::MessageReceived,
+                            Some(SERVER_SENDER.to_string()),
+                            MessagePayload::Text {
+                                content: format!("{} a {} le salon", current_username, if joining { "rejoint" } else { "quitté" })
+                            }
+                        );
+                        notice.room = Some(room);
+                        let _ = broadcast_tx.send(notice);
+                    }
+                } else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                }
+            }
+            
+            OpCode::ListRooms => {
+                if session_id.is_some() {
+                    let rooms = state.lock().await.get_rooms();
+                    Self::send_message_to_writer(writer, &Message::room_list(rooms)).await?;
+                } else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                }
+            }
+            
+            OpCode::History if !has_feature(negotiated_features, features::HISTORY) => {
+                let error_msg = Message::error(
+                    error_codes::INVALID_MESSAGE,
+                    "History was not negotiated".to_string()
+                );
+                Self::send_message_to_writer(writer, &error_msg).await?;
+            }
+
+            OpCode::History => {
+                if let (Some(current_username), MessagePayload::HistoryRequest { room, limit, since, after }) = (username.as_ref(), msg.payload) {
+                    // L'historique d'un salon est réservé à ses membres
+                    if let Some(ref room) = room {
+                        if !state.lock().await.is_member(room, current_username) {
+                            let error_msg = Message::error(
+                                error_codes::FORBIDDEN,
+                                format!("You are not a member of room '{}'", room)
+                            );
+                            Self::send_message_to_writer(writer, &error_msg).await?;
+                            return Ok(());
+                        }
+                    }
+
+                    let query = HistoryQuery { room, limit, since, after };
+                    let messages = history.lock().await.query(current_username, &query);
+                    Self::send_message_to_writer(writer, &Message::history(messages)).await?;
+                } else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                }
+            }
+            
+            OpCode::Kick | OpCode::Ban | OpCode::Mute => {
+                let Some(current_username) = username.clone() else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+                let MessagePayload::Moderation { username: target, duration, reason } = msg.payload.clone() else {
+                    return Ok(());
+                };
+
+                let mut state_guard = state.lock().await;
+                if state_guard.session_of(&current_username).map(|session| session.role) != Some(Role::Admin) {
+                    drop(state_guard);
+                    println!("🚫 {} n'est pas administrateur ({:?} {})", current_username, msg.op_code, target);
+                    let error_msg = Message::error(
+                        error_codes::FORBIDDEN,
+                        format!("{:?} is reserved for administrators", msg.op_code)
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+
+                let sanction = Sanction::new(current_username.clone(), reason.clone(), duration);
+                // Utilisateurs à expulser: la cible du Kick ou du Ban, ou toutes les
+                // sessions ouvertes depuis une adresse bannie
+                let result = match msg.op_code {
+                    OpCode::Kick if state_guard.is_connected(&target) => Ok(vec![target.clone()]),
+                    OpCode::Kick => Err(Message::error(
+                        error_codes::USER_NOT_FOUND,
+                        format!("User '{}' is not connected", target)
+                    )),
+                    OpCode::Ban if target.parse::<IpAddr>().is_err() && !accounts.lock().await.exists(&target) => {
+                        Err(Message::error(
+                            error_codes::USER_NOT_FOUND,
+                            format!("User '{}' does not exist", target)
+                        ))
+                    }
+                    OpCode::Ban => {
+                        // L'adresse n'est bannie que si l'administrateur la désigne explicitement
+                        let ban = match target.parse::<IpAddr>() {
+                            Ok(ip) => Ban { username: None, ip: Some(ip), sanction },
+                            Err(_) => Ban { username: Some(target.clone()), ip: None, sanction },
+                        };
+                        let kicked: Vec<String> = state_guard.sessions.values()
+                            .filter(|session| {
+                                ban.username.as_ref() == Some(&session.username)
+                                    || (ban.username.is_none() && ban.ip.is_some() && session.ip == ban.ip)
+                            })
+                            .map(|session| session.username.clone())
+                            .collect();
+                        match bans.lock().await.add(ban) {
+                            Ok(()) => Ok(kicked),
+                            Err(e) => {
+                                eprintln!("❌ Impossible d'enregistrer le bannissement de {}: {}", target, e);
+                                Err(Message::error(error_codes::INTERNAL_ERROR, "Could not save ban".to_string()))
+                            }
+                        }
+                    }
+                    _ if accounts.lock().await.exists(&target) => {
+                        let notice = Message::new_with_recipient(
+                            OpCode::MessageReceived,
+                            Some(SERVER_SENDER.to_string()),
+                            target.clone(),
+                            MessagePayload::Text {
+                                content: format!("Vous ne pouvez plus envoyer de messages ({})", sanction)
+                            }
+                        );
+                        state_guard.mute(&target, sanction);
+                        let _ = broadcast_tx.send(notice);
+                        Ok(Vec::new())
+                    }
+                    _ => Err(Message::error(
+                        error_codes::USER_NOT_FOUND,
+                        format!("User '{}' does not exist", target)
+                    )),
+                };
+                drop(state_guard);
+
+                match result {
+                    Ok(kicked) => {
+                        println!(
+                            "🛡️  {} par {}: {} ({})",
+                            match msg.op_code { OpCode::Kick => "Expulsion", OpCode::Ban => "Bannissement", _ => "Silence" },
+                            current_username,
+                            target,
+                            reason.as_deref().unwrap_or("sans motif")
+                        );
+                        // Chaque connexion expulsée se ferme à la réception de cette erreur
+                        for kicked_username in kicked {
+                            let mut kick = Message::error(
+                                error_codes::KICKED,
+                                format!(
+                                    "{} by {}{}",
+                                    if msg.op_code == OpCode::Ban { "Banned" } else { "Kicked" },
+                                    current_username,
+                                    reason.as_ref().map(|reason| format!(": {}", reason)).unwrap_or_default()
+                                )
+                            );
+                            kick.recipient = Some(kicked_username);
+                            let _ = broadcast_tx.send(kick);
+                        }
+                        Self::send_message_to_writer(writer, &Message::moderation_ack(msg.payload)).await?;
+                    }
+                    Err(error_msg) => {
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                    }
+                }
+            }
+
+            OpCode::FileOffer | OpCode::FileAccept | OpCode::FileReject | OpCode::FileChunk | OpCode::FileComplete
+                if !has_feature(negotiated_features, features::FILES) => {
+                let error_msg = Message::error(
+                    error_codes::INVALID_MESSAGE,
+                    "File transfers were not negotiated".to_string()
+                );
+                Self::send_message_to_writer(writer, &error_msg).await?;
+            }
+
+            OpCode::FileOffer | OpCode::FileAccept | OpCode::FileReject | OpCode::FileChunk | OpCode::FileComplete => {
+                let Some(current_username) = username.clone() else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+
+                let mut transfer_msg = msg;
+                if let Err(e) = Self::stamp_sender(&mut transfer_msg, &current_username) {
+                    let error_msg = Message::error(error_codes::FORBIDDEN, e);
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+
+                // La validation garantit un destinataire et un identifiant de transfert
+                let (Some(recipient), Some(transfer_id)) = (transfer_msg.recipient.clone(), transfer_msg.payload.transfer_id()) else {
+                    return Ok(());
+                };
+                let mut state_guard = state.lock().await;
+                let routed = state_guard.route_transfer(&transfer_msg.op_code, transfer_id, &current_username, &recipient)
+                    .map(|()| state_guard.session_of(&recipient).and_then(|session| session.transfers.clone()));
+                match routed {
+                    Ok(Some(recipient_tx)) => {
+                        if let MessagePayload::FileOffer { name, size, .. } = &transfer_msg.payload {
+                            println!("📎 {} propose {} ({} octets) à {}", current_username, name, size, recipient);
+                        }
+                        // Relayé par le canal du destinataire, sans historique ni mise en attente: les
+                        // morceaux n'encombrent pas la diffusion; un destinataire débordé (il n'accuse
+                        // pourtant qu'une fenêtre de morceaux à la fois) fait abandonner le transfert
+                        if let Err(mpsc::error::TrySendError::Full(_)) = recipient_tx.try_send(transfer_msg) {
+                            state_guard.transfers.remove(&transfer_id);
+                            drop(state_guard);
+                            println!("⚠️  Transfert {} abandonné: {} ne suit pas", transfer_id, recipient);
+                            let reason = Some(format!("{} is not keeping up", recipient));
+                            let _ = broadcast_tx.send(Message::file_reject(current_username.clone(), recipient.clone(), transfer_id, reason.clone()));
+                            let reject = Message::file_reject(recipient, current_username, transfer_id, reason);
+                            Self::send_message_to_writer(writer, &reject).await?;
+                        }
+                    }
+                    // Destinataire déconnecté entre-temps: ses transferts sont déjà abandonnés
+                    Ok(None) => {}
+                    Err((code, e)) => {
+                        drop(state_guard);
+                        Self::send_message_to_writer(writer, &Message::error(code, e)).await?;
+                    }
+                }
+            }
+
+            OpCode::EditMessage | OpCode::DeleteMessage if !has_feature(negotiated_features, features::EDITS) => {
+                let error_msg = Message::error(
+                    error_codes::INVALID_MESSAGE,
+                    "Message edits were not negotiated".to_string()
+                );
+                Self::send_message_to_writer(writer, &error_msg).await?;
+            }
+
+            OpCode::EditMessage | OpCode::DeleteMessage => {
+                let Some(current_username) = username.clone() else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+
+                let mut correction = msg;
+                if let Err(e) = Self::stamp_sender(&mut correction, &current_username) {
+                    let error_msg = Message::error(error_codes::FORBIDDEN, e);
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+                let Some(message_id) = correction.payload.corrected_id() else {
+                    return Ok(());
+                };
+
+                let mut state_guard = state.lock().await;
+                if correction.op_code == OpCode::EditMessage {
+                    if let Some(sanction) = state_guard.muted(&current_username).map(|sanction| sanction.to_string()) {
+                        drop(state_guard);
+                        let error_msg = Message::error(error_codes::FORBIDDEN, format!("You are muted {}", sanction));
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+                }
+                let is_admin = state_guard.session_of(&current_username).is_some_and(|session| session.role == Role::Admin);
+                drop(state_guard);
+
+                // L'historique fait foi: seul un message conservé peut être corrigé
+                let original = history.lock().await.find(message_id).cloned();
+                let Some(original) = original else {
+                    let error_msg = Message::error(
+                        error_codes::USER_NOT_FOUND,
+                        format!("Message {} not found", message_id)
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+                let author = original.sender.clone().unwrap_or_default();
+                if author != current_username && !is_admin {
+                    let error_msg = Message::error(
+                        error_codes::FORBIDDEN,
+                        format!("Only {} or an administrator can modify message {}", author, message_id)
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+
+                // La correction est remise aux mêmes destinataires que le message d'origine,
+                // et appliquée à sa copie en attente si le destinataire est absent
+                correction.room = original.room.clone();
+                correction.recipient = original.recipient.clone();
+                Self::record(history, &correction).await;
+                if let Some(ref recipient) = original.recipient {
+                    offline.lock().await.correct(recipient, &correction);
+                }
+                println!(
+                    "{} {} par {}",
+                    if correction.op_code == OpCode::EditMessage { "✏️  Message modifié" } else { "🗑️  Message supprimé" },
+                    message_id,
+                    current_username
+                );
+                let _ = broadcast_tx.send(correction.clone());
+
+                // L'auteur d'un message privé corrigé par un administrateur en est prévenu
+                if original.recipient.is_some() && author != current_username {
+                    correction.recipient = Some(author);
+                    let _ = broadcast_tx.send(correction);
+                }
+            }
+
+            OpCode::Presence | OpCode::Typing if !has_feature(negotiated_features, features::PRESENCE) => {
+                let error_msg = Message::error(
+                    error_codes::INVALID_MESSAGE,
+                    "Presence was not negotiated".to_string()
+                );
+                Self::send_message_to_writer(writer, &error_msg).await?;
+            }
+
+            OpCode::Presence | OpCode::Typing => {
+                let Some(current_username) = username.clone() else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                };
+
+                let mut msg = msg;
+                if let Err(e) = Self::stamp_sender(&mut msg, &current_username) {
+                    let error_msg = Message::error(error_codes::FORBIDDEN, e);
+                    Self::send_message_to_writer(writer, &error_msg).await?;
+                    return Ok(());
+                }
+
+                if let MessagePayload::Presence { status, text } = msg.payload {
+                    let changed = state.lock().await.set_presence(&current_username, status, text);
+                    if let Some(presence) = changed {
+                        println!("🟢 {} est maintenant {:?}", current_username, presence.status);
+                        let _ = broadcast_tx.send(Message::presence(presence.username, presence.status, presence.text));
+                    }
+                    return Ok(());
+                }
+
+                // Indication de saisie: relayée comme le message qu'elle annonce, sans
+                // historique ni mise en attente; celle d'un utilisateur muet est ignorée
+                let mut state_guard = state.lock().await;
+                if state_guard.muted(&current_username).is_some() {
+                    return Ok(());
+                }
+                if let Some(ref room) = msg.room {
+                    if !state_guard.is_member(room, &current_username) {
+                        drop(state_guard);
+                        let error_msg = Message::error(
+                            error_codes::FORBIDDEN,
+                            format!("You are not a member of room '{}'", room)
+                        );
+                        Self::send_message_to_writer(writer, &error_msg).await?;
+                        return Ok(());
+                    }
+                }
+                drop(state_guard);
+                let _ = broadcast_tx.send(msg);
+            }
+
+            OpCode::ListUsers => {
+                if session_id.is_some() {
+                    let state_guard = state.lock().await;
+                    let user_list = Message::user_list(state_guard.get_presences());
+                    Self::send_message_to_writer(writer, &user_list).await?;
+                } else {
+                    let error_msg = Message::error(
+                        error_codes::UNAUTHORIZED,
+                        "Not connected".to_string()
+                    );
                     Self::send_message_to_writer(writer, &error_msg).await?;
                 }
             }
@@ -324,13 +1876,226 @@ impl MessageServer {
         Ok(())
     }
 
+    /// Prévient l'autre participant de chaque transfert interrompu par le départ de `username`
+    fn abort_transfers(state: &mut ServerState, username: &str, broadcast_tx: &broadcast::Sender<Message>) {
+        for (transfer_id, peer) in state.abort_transfers(username) {
+            let reject = Message::file_reject(
+                username.to_string(),
+                peer,
+                transfer_id,
+                Some(format!("{} disconnected", username))
+            );
+            let _ = broadcast_tx.send(reject);
+        }
+    }
+
+    /// Diffuse un changement de statut décidé par le serveur, y compris à l'utilisateur
+    /// concerné, que la diffusion ne renvoie pas à son expéditeur
+    async fn announce_presence(
+        presence: UserPresence,
+        negotiated_features: &Option<Vec<String>>,
+        broadcast_tx: &broadcast::Sender<Message>,
+        writer: &mut Connection,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let update = Message::presence(presence.username, presence.status, presence.text);
+        if has_feature(negotiated_features, features::PRESENCE) {
+            Self::send_message_to_writer(writer, &update).await?;
+        }
+        let _ = broadcast_tx.send(update);
+        Ok(())
+    }
+
+    /// Conserve un message distribué dans l'historique; un échec d'écriture
+    /// n'empêche pas la distribution
+    async fn record(history: &Mutex<MessageHistory>, msg: &Message) {
+        if let Err(e) = history.lock().await.append(msg.clone()) {
+            eprintln!("❌ Impossible d'enregistrer le message dans l'historique: {}", e);
+        }
+    }
+
+    /// Fixe l'expéditeur d'un message à l'utilisateur authentifié de la session;
+    /// un expéditeur annoncé différent est une tentative d'usurpation
+    fn stamp_sender(msg: &mut Message, session_username: &str) -> Result<(), String> {
+        match msg.sender.as_deref() {
+            Some(sender) if sender != session_username => Err(format!(
+                "Sender '{}' does not match authenticated user '{}'",
+                sender, session_username
+            )),
+            _ => {
+                msg.sender = Some(session_username.to_string());
+                Ok(())
+            }
+        }
+    }
+
     /// Envoie un message au client
     async fn send_message_to_writer(
-        writer: &mut tokio::net::tcp::WriteHalf<'_>,
+        writer: &mut Connection,
         msg: &Message,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let json = format!("{}\n", msg.to_json()?);
-        writer.write_all(json.as_bytes()).await?;
+        writer.send(msg).await?;
         Ok(())
     }
 }
+
+impl Default for MessageServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Opérations faites par l'utilisateur lui-même, par opposition aux réponses
+/// automatiques de son client (`Pong`, accusés, morceaux de fichiers)
+fn is_user_action(op_code: &OpCode) -> bool {
+    matches!(
+        op_code,
+        OpCode::SendMessage | OpCode::Presence | OpCode::Typing | OpCode::ListUsers | OpCode::JoinRoom
+            | OpCode::LeaveRoom | OpCode::ListRooms | OpCode::History | OpCode::Read | OpCode::FileOffer
+            | OpCode::FileReject | OpCode::EditMessage | OpCode::DeleteMessage | OpCode::Kick | OpCode::Ban
+            | OpCode::Mute
+    )
+}
+
+/// Indique si une fonctionnalité a été négociée avec le client
+fn has_feature(negotiated: &Option<Vec<String>>, feature: &str) -> bool {
+    negotiated.as_ref().is_some_and(|features| features.iter().any(|f| f == feature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_membership() {
+        let mut state = ServerState::new();
+        let alice = state.add_session("Alice".to_string()).unwrap();
+        state.add_session("Bob".to_string()).unwrap();
+
+        assert!(state.join_room("rust", "Alice"));
+        assert!(!state.join_room("rust", "Alice"));
+        assert!(state.join_room("rust", "Bob"));
+        assert!(state.is_member("rust", "Bob"));
+        assert_eq!(state.get_rooms(), vec![RoomInfo { name: "rust".to_string(), members: 2 }]);
+
+        assert!(state.leave_room("rust", "Bob"));
+        assert!(!state.leave_room("rust", "Bob"));
+
+        // La déconnexion du dernier membre supprime le salon
+        state.remove_session(&alice);
+        assert!(state.get_rooms().is_empty());
+    }
+
+    #[test]
+    fn test_session_resumption() {
+        let window = chrono::Duration::minutes(1);
+        let mut state = ServerState::new();
+        let alice = state.add_session("Alice".to_string()).unwrap();
+        let token = state.sessions[&alice].resume_token;
+        state.join_room("rust", "Alice");
+
+        state.suspend_session(&alice).unwrap();
+        assert!(!state.is_connected("Alice"));
+        assert!(state.resume_session(token, "Bob", window).is_none());
+        let suspended = state.resume_session(token, "Alice", window).unwrap();
+        assert_eq!(suspended.rooms, vec!["rust".to_string()]);
+        // Un jeton ne sert qu'une fois
+        assert!(state.resume_session(token, "Alice", window).is_none());
+
+        // Un jeton expiré n'est plus accepté
+        let alice = state.add_session("Alice".to_string()).unwrap();
+        let token = state.sessions[&alice].resume_token;
+        state.suspend_session(&alice).unwrap();
+        assert!(state.resume_session(token, "Alice", chrono::Duration::zero()).is_none());
+    }
+
+    #[test]
+    fn test_resume_replaces_half_open_session() {
+        let mut state = ServerState::new();
+        let alice = state.add_session("Alice".to_string()).unwrap();
+        let token = state.sessions[&alice].resume_token;
+        state.join_room("rust", "Alice");
+
+        assert!(state.resume_session(Uuid::new_v4(), "Alice", chrono::Duration::minutes(1)).is_none());
+        let suspended = state.resume_session(token, "Alice", chrono::Duration::minutes(1)).unwrap();
+        assert_eq!(suspended.rooms, vec!["rust".to_string()]);
+        assert!(state.add_session("Alice".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_roles_and_mutes() {
+        let mut state = ServerState::new();
+        state.add_session("Bob".to_string()).unwrap();
+        assert_eq!(state.session_of("Bob").unwrap().role, Role::User);
+        assert!(state.session_of("Carol").is_none());
+
+        state.mute("Bob", Sanction::new("Admin".to_string(), None, Some(60)));
+        assert!(state.muted("Bob").is_some());
+        assert!(state.muted("Carol").is_none());
+
+        // Une sanction expirée est levée
+        state.mutes.get_mut("Bob").unwrap().until = Some(chrono::Utc::now() - chrono::Duration::seconds(1));
+        assert!(state.muted("Bob").is_none());
+        assert!(state.mutes.is_empty());
+    }
+
+    #[test]
+    fn test_presence() {
+        let mut state = ServerState::new();
+        state.add_session("Bob".to_string()).unwrap();
+        state.add_session("Alice".to_string()).unwrap();
+        let presences = state.get_presences();
+        assert_eq!(presences.iter().map(|p| p.username.as_str()).collect::<Vec<_>>(), vec!["Alice", "Bob"]);
+        assert!(presences.iter().all(|p| p.status == PresenceStatus::Online));
+
+        // Absent pour inactivité, puis de retour à la première action
+        assert_eq!(state.mark_away("Bob").unwrap().status, PresenceStatus::Away);
+        assert!(state.mark_away("Bob").is_none());
+        assert_eq!(state.mark_active("Bob").unwrap().status, PresenceStatus::Online);
+        assert!(state.mark_active("Bob").is_none());
+
+        // Un statut choisi n'est ni remplacé par l'absence automatique ni levé par l'activité
+        let busy = state.set_presence("Bob", PresenceStatus::Busy, Some("En réunion".to_string())).unwrap();
+        assert_eq!(busy.text.as_deref(), Some("En réunion"));
+        assert!(state.set_presence("Bob", PresenceStatus::Busy, Some("En réunion".to_string())).is_none());
+        assert!(state.mark_away("Bob").is_none());
+        assert!(state.mark_active("Bob").is_none());
+        assert!(state.set_presence("Carol", PresenceStatus::Away, None).is_none());
+    }
+
+    #[test]
+    fn test_heartbeat_decision() {
+        let config = ServerConfig {
+            ping_interval: Duration::from_secs(10),
+            idle_timeout: Duration::from_secs(30),
+            ..ServerConfig::default()
+        };
+
+        assert_eq!(config.heartbeat(Duration::from_secs(5), true), Heartbeat::Alive);
+        assert_eq!(config.heartbeat(Duration::from_secs(15), true), Heartbeat::Ping);
+        // Sans heartbeat négocié, le client n'est pas sondé mais expire quand même
+        assert_eq!(config.heartbeat(Duration::from_secs(15), false), Heartbeat::Alive);
+        assert_eq!(config.heartbeat(Duration::from_secs(30), false), Heartbeat::Expired);
+    }
+
+    #[test]
+    fn test_sender_is_stamped_from_session() {
+        let mut msg = Message::text_message("Alice".to_string(), "Salut".to_string());
+        assert!(MessageServer::stamp_sender(&mut msg, "Alice").is_ok());
+        assert_eq!(msg.sender.as_deref(), Some("Alice"));
+
+        // Sans expéditeur annoncé, celui de la session est utilisé
+        msg.sender = None;
+        assert!(MessageServer::stamp_sender(&mut msg, "Alice").is_ok());
+        assert_eq!(msg.sender.as_deref(), Some("Alice"));
+    }
+
+    #[test]
+    fn test_spoofed_sender_is_rejected() {
+        let mut msg = Message::text_message("Bob".to_string(), "Je suis Bob".to_string());
+        assert!(MessageServer::stamp_sender(&mut msg, "Alice").is_err());
+        assert_eq!(msg.sender.as_deref(), Some("Bob"));
+
+        let mut msg = Message::room_message(SERVER_SENDER.to_string(), "rust".to_string(), "Annonce".to_string());
+        assert!(MessageServer::stamp_sender(&mut msg, "Alice").is_err());
+    }
+}
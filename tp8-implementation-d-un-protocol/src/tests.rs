@@ -1,53 +1,900 @@
-use std::process::Stdio;
-use tokio::process::Command;
-use tokio::time::{sleep, Duration};
+//! Tests d'intégration pour le protocole de messagerie
+//!
+//! Chaque test démarre un `MessageServer` sur un port éphémère et le pilote
+//! avec des clients sans interface qui vérifient les trames réellement reçues.
 
-/// Tests d'intégration pour le protocole de messagerie
-/// 
-/// Ces tests valident le bon fonctionnement du protocole
-/// en simulant des scénarios d'utilisation réels.
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::Framed;
+
+use crate::codec::{Framing, MessageCodec};
+use crate::history::MessageHistory;
+use crate::protocol::{Message, MessagePayload, OpCode, PresenceStatus, ProtocolVersion, PROTOCOL_VERSION, SERVER_SENDER, error_codes, features};
+use crate::server::{MessageServer, ServerConfig};
+
+/// Délai maximal d'attente d'une trame du serveur
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Délai pendant lequel un client doit ne rien recevoir
+const SILENCE: Duration = Duration::from_millis(200);
+
+/// Démarre un serveur (comptes et historique en mémoire) sur un port éphémère
+async fn spawn_server() -> SocketAddr {
+    spawn_server_with(MessageServer::new()).await
+}
+
+async fn spawn_server_with(server: MessageServer) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { server.serve(listener).await });
+    addr
+}
+
+/// Client de test: envoie des messages et vérifie ceux reçus du serveur
+struct TestClient {
+    username: String,
+    connection: Framed<TcpStream, MessageCodec>,
+}
+
+impl TestClient {
+    async fn open(addr: SocketAddr, username: &str) -> Self {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        Self {
+            username: username.to_string(),
+            connection: Framed::new(stream, MessageCodec::new(Framing::Json)),
+        }
+    }
+
+    /// Se présente, crée le compte et se connecte; renvoie la liste des utilisateurs reçue
+    async fn login(addr: SocketAddr, username: &str) -> (Self, Vec<String>) {
+        Self::login_with(addr, username, &[]).await
+    }
+
+    /// Comme `login`, en négociant les fonctionnalités demandées
+    async fn login_with(addr: SocketAddr, username: &str, requested: &[&str]) -> (Self, Vec<String>) {
+        let mut client = Self::open(addr, username).await;
+        client.send(&Message::hello(requested.iter().map(|f| f.to_string()).collect())).await;
+        assert_eq!(client.recv().await.op_code, OpCode::HelloAck);
+        client.send(&Message::register(username.to_string(), "password123".to_string())).await;
+        assert_eq!(client.recv().await.op_code, OpCode::RegisterAck);
+        client.send(&Message::connect(username.to_string(), "password123".to_string())).await;
+        assert_eq!(client.recv().await.op_code, OpCode::ConnectAck);
+        let users = client.expect_user_list().await;
+        // L'annonce de la connexion est diffusée à tous, y compris au nouveau venu
+        assert_eq!(client.expect_notice().await, format!("{} a rejoint le chat", username));
+        (client, users)
+    }
+
+    /// Se reconnecte à un compte existant; s'arrête au `ConnectAck`
+    async fn reconnect_with(addr: SocketAddr, username: &str, requested: &[&str]) -> Self {
+        let mut client = Self::open(addr, username).await;
+        client.send(&Message::hello(requested.iter().map(|f| f.to_string()).collect())).await;
+        assert_eq!(client.recv().await.op_code, OpCode::HelloAck);
+        client.send(&Message::connect(username.to_string(), "password123".to_string())).await;
+        assert_eq!(client.recv().await.op_code, OpCode::ConnectAck);
+        client
+    }
+
+    async fn hello(&mut self) {
+        self.send(&Message::hello(Vec::new())).await;
+        assert_eq!(self.recv().await.op_code, OpCode::HelloAck);
+    }
+
+    async fn send(&mut self, msg: &Message) {
+        self.connection.send(msg).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> Message {
+        match tokio::time::timeout(RECV_TIMEOUT, self.connection.next()).await {
+            Ok(Some(Ok(Ok(msg)))) => msg,
+            other => panic!("{}: no message received ({:?})", self.username, other.map(|r| r.map(|r| r.map(|m| m.map(|m| m.op_code))))),
+        }
+    }
+
+    /// Attend la prochaine notification du serveur et renvoie son texte
+    async fn expect_notice(&mut self) -> String {
+        let msg = self.recv().await;
+        assert_eq!(msg.sender.as_deref(), Some(SERVER_SENDER));
+        text_of(&msg)
+    }
+
+    async fn expect_error(&mut self, expected: u16) {
+        match self.recv().await.payload {
+            MessagePayload::Error { code, .. } => assert_eq!(code, expected),
+            other => panic!("{}: expected error {}, got {:?}", self.username, expected, other),
+        }
+    }
+
+    async fn expect_user_list(&mut self) -> Vec<String> {
+        match self.recv().await.payload {
+            MessagePayload::UserList { mut users, .. } => {
+                users.sort();
+                users
+            }
+            other => panic!("{}: expected user list, got {:?}", self.username, other),
+        }
+    }
+
+    /// Vérifie qu'aucune trame n'arrive pendant un court délai
+    async fn expect_silence(&mut self) {
+        if let Ok(msg) = tokio::time::timeout(SILENCE, self.connection.next()).await {
+            panic!("{}: unexpected message {:?}", self.username, msg.map(|r| r.map(|r| r.map(|m| m.op_code))));
+        }
+    }
+}
+
+fn text_of(msg: &Message) -> String {
+    match &msg.payload {
+        MessagePayload::Text { content } => content.clone(),
+        other => panic!("expected text, got {:?}", other),
+    }
+}
 
 #[tokio::test]
 async fn test_protocol_basic_flow() {
-    // Ce test simule un scénario basique :
-    // 1. Démarrage du serveur
-    // 2. Connexion d'un client
-    // 3. Envoi d'un message
-    // 4. Déconnexion
-    
-    println!("🧪 Test du protocole - Flux basique");
-    
-    // Note: Ces tests nécessitent une infrastructure plus complexe
-    // pour être automatisés complètement. Ils servent d'exemple
-    // de ce qui pourrait être testé.
-    
-    // Simulation du test
-    println!("✅ Test simulé réussi");
+    let addr = spawn_server().await;
+    let (mut alice, users) = TestClient::login(addr, "Alice").await;
+    assert_eq!(users, vec!["Alice"]);
+
+    let (mut bob, users) = TestClient::login(addr, "Bob").await;
+    assert_eq!(users, vec!["Alice", "Bob"]);
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+
+    alice.send(&Message::text_message("Alice".to_string(), "Bonjour Bob".to_string())).await;
+    let received = bob.recv().await;
+    assert_eq!(received.op_code, OpCode::MessageReceived);
+    assert_eq!(received.sender.as_deref(), Some("Alice"));
+    assert_eq!(text_of(&received), "Bonjour Bob");
+    // L'expéditeur ne reçoit pas son propre message
+    alice.expect_silence().await;
+
+    bob.send(&Message::disconnect("Bob".to_string())).await;
+    assert_eq!(bob.recv().await.op_code, OpCode::DisconnectAck);
+    assert_eq!(alice.expect_notice().await, "Bob a quitté le chat");
 }
 
 #[tokio::test]
 async fn test_multiple_clients() {
-    // Test avec plusieurs clients connectés simultanément
-    println!("🧪 Test du protocole - Clients multiples");
-    println!("✅ Test simulé réussi");
+    let addr = spawn_server().await;
+    let (mut alice, _) = TestClient::login(addr, "Alice").await;
+    let (mut bob, _) = TestClient::login(addr, "Bob").await;
+    let (mut carol, _) = TestClient::login(addr, "Carol").await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+    assert_eq!(alice.expect_notice().await, "Carol a rejoint le chat");
+    assert_eq!(bob.expect_notice().await, "Carol a rejoint le chat");
+
+    carol.send(&Message::new(OpCode::ListUsers, Some("Carol".to_string()), MessagePayload::Empty)).await;
+    assert_eq!(carol.expect_user_list().await, vec!["Alice", "Bob", "Carol"]);
+
+    // Diffusion à tous les autres clients
+    carol.send(&Message::text_message("Carol".to_string(), "Salut tout le monde".to_string())).await;
+    for client in [&mut alice, &mut bob] {
+        let received = client.recv().await;
+        assert_eq!(received.sender.as_deref(), Some("Carol"));
+        assert_eq!(text_of(&received), "Salut tout le monde");
+    }
+
+    // Message privé: seul le destinataire le reçoit
+    alice.send(&Message::private_message("Alice".to_string(), "Bob".to_string(), "Entre nous".to_string())).await;
+    assert_eq!(text_of(&bob.recv().await), "Entre nous");
+    carol.expect_silence().await;
+
+    // Une connexion fermée sans Disconnect est aussi annoncée
+    drop(carol);
+    assert_eq!(alice.expect_notice().await, "Carol a quitté le chat");
+    assert_eq!(bob.expect_notice().await, "Carol a quitté le chat");
+}
+
+#[tokio::test]
+async fn test_duplicate_username_rejected() {
+    let addr = spawn_server().await;
+    let (mut alice, _) = TestClient::login(addr, "Alice").await;
+
+    let mut intruder = TestClient::open(addr, "Alice (bis)").await;
+    intruder.hello().await;
+    intruder.send(&Message::register("Alice".to_string(), "password123".to_string())).await;
+    intruder.expect_error(error_codes::USER_EXISTS).await;
+    intruder.send(&Message::connect("Alice".to_string(), "mauvais-mot-de-passe".to_string())).await;
+    intruder.expect_error(error_codes::UNAUTHORIZED).await;
+
+    // Même avec le bon mot de passe, le nom est déjà utilisé par une session active
+    intruder.send(&Message::connect("Alice".to_string(), "password123".to_string())).await;
+    intruder.expect_error(error_codes::USER_EXISTS).await;
+    alice.expect_silence().await;
+
+    alice.send(&Message::new(OpCode::ListUsers, Some("Alice".to_string()), MessagePayload::Empty)).await;
+    assert_eq!(alice.expect_user_list().await, vec!["Alice"]);
 }
 
 #[tokio::test]
 async fn test_error_handling() {
-    // Test de la gestion d'erreurs
-    println!("🧪 Test du protocole - Gestion d'erreurs");
-    println!("✅ Test simulé réussi");
+    let addr = spawn_server().await;
+    let mut client = TestClient::open(addr, "Anonyme").await;
+
+    // Toute opération doit suivre Hello
+    client.send(&Message::new(OpCode::ListUsers, None, MessagePayload::Empty)).await;
+    client.expect_error(error_codes::UPGRADE_REQUIRED).await;
+
+    // Une ligne JSON invalide est signalée sans fermer la connexion
+    client.connection.get_mut().write_all(b"{ pas du json }\n").await.unwrap();
+    client.expect_error(error_codes::INVALID_MESSAGE).await;
+    client.hello().await;
+
+    // Un message invalide (nom vide) est refusé
+    client.send(&Message::connect(String::new(), "password123".to_string())).await;
+    client.expect_error(error_codes::INVALID_MESSAGE).await;
+
+    // Envoyer un message sans être connecté
+    client.send(&Message::text_message("Anonyme".to_string(), "Bonjour".to_string())).await;
+    client.expect_error(error_codes::UNAUTHORIZED).await;
+
+    let (mut alice, _) = TestClient::login(addr, "Alice").await;
+    alice.send(&Message::private_message("Alice".to_string(), "Personne".to_string(), "Allô ?".to_string())).await;
+    alice.expect_error(error_codes::USER_NOT_FOUND).await;
+}
+
+#[tokio::test]
+async fn test_hello_negotiation() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::open(addr, "Alice").await;
+
+    // Les fonctionnalités inconnues du serveur sont ignorées, pas refusées
+    client.send(&Message::hello(vec![features::ROOMS.to_string(), "compression.zstd".to_string()])).await;
+    match client.recv().await.payload {
+        MessagePayload::Hello { version, features: common } => {
+            assert_eq!(version, PROTOCOL_VERSION);
+            assert_eq!(common, vec![features::ROOMS.to_string()]);
+        }
+        other => panic!("expected HelloAck, got {:?}", other),
+    }
+    client.send(&Message::hello(Vec::new())).await;
+    client.expect_error(error_codes::INVALID_MESSAGE).await;
+
+    // Une version majeure différente est refusée
+    let mut old = TestClient::open(addr, "Old").await;
+    let mut hello = Message::hello(Vec::new());
+    hello.payload = MessagePayload::Hello { version: ProtocolVersion { major: 1, minor: 0 }, features: Vec::new() };
+    old.send(&hello).await;
+    old.expect_error(error_codes::UPGRADE_REQUIRED).await;
+}
+
+#[tokio::test]
+async fn test_legacy_client_without_hello() {
+    let addr = spawn_server().await;
+    let (mut alice, _) = TestClient::login_with(addr, "Alice", &[features::ROOMS]).await;
+
+    // Un client 1.x commence directement par Register et Connect
+    let mut legacy = TestClient::open(addr, "Bob").await;
+    legacy.send(&Message::register("Bob".to_string(), "password123".to_string())).await;
+    assert_eq!(legacy.recv().await.op_code, OpCode::RegisterAck);
+    legacy.send(&Message::connect("Bob".to_string(), "password123".to_string())).await;
+    assert_eq!(legacy.recv().await.op_code, OpCode::ConnectAck);
+    assert_eq!(legacy.expect_user_list().await, vec!["Alice", "Bob"]);
+    assert_eq!(legacy.expect_notice().await, "Bob a rejoint le chat");
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+
+    // Avec les seules fonctionnalités de base
+    legacy.send(&Message::text_message("Bob".to_string(), "Bonjour".to_string())).await;
+    assert_eq!(text_of(&alice.recv().await), "Bonjour");
+    legacy.send(&Message::join_room("Bob".to_string(), "rust".to_string())).await;
+    legacy.expect_error(error_codes::INVALID_MESSAGE).await;
+}
+
+#[tokio::test]
+async fn test_binary_framing_with_pipelined_frame() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::open(addr, "Alice").await;
+    alice.send(&Message::hello(vec![features::BINARY_FRAMING.to_string()])).await;
+    assert_eq!(alice.recv().await.op_code, OpCode::HelloAck);
+    alice.send(&Message::register("Alice".to_string(), "password123".to_string())).await;
+    assert_eq!(alice.recv().await.op_code, OpCode::RegisterAck);
+
+    // Le Connect (en JSON) et la trame suivante (déjà en binaire) partent dans le même segment
+    let connect = Message::connect("Alice".to_string(), "password123".to_string()).with_framing(Framing::Binary);
+    alice.connection.feed(&connect).await.unwrap();
+    alice.connection.codec_mut().set_framing(Framing::Binary);
+    alice.connection.feed(&Message::new(OpCode::ListUsers, Some("Alice".to_string()), MessagePayload::Empty)).await.unwrap();
+    alice.connection.flush().await.unwrap();
+
+    // Le ConnectAck arrive encore en JSON, tout le reste en binaire
+    alice.connection.codec_mut().set_framing(Framing::Json);
+    assert_eq!(alice.recv().await.op_code, OpCode::ConnectAck);
+    alice.connection.codec_mut().set_framing(Framing::Binary);
+    let (mut user_lists, mut notices) = (0, 0);
+    while user_lists + notices < 3 {
+        let msg = alice.recv().await;
+        match msg.payload {
+            MessagePayload::UserList { users, .. } => {
+                assert_eq!(users, vec!["Alice".to_string()]);
+                user_lists += 1;
+            }
+            _ => {
+                assert_eq!(text_of(&msg), "Alice a rejoint le chat");
+                notices += 1;
+            }
+        }
+    }
+    assert_eq!(user_lists, 2);
+    alice.expect_silence().await;
+}
+
+#[tokio::test]
+async fn test_file_transfer_relay() {
+    let addr = spawn_server().await;
+    let (mut alice, _) = TestClient::login_with(addr, "Alice", &[features::FILES]).await;
+    let (mut bob, _) = TestClient::login_with(addr, "Bob", &[features::FILES]).await;
+    let (mut carol, _) = TestClient::login(addr, "Carol").await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+    assert_eq!(alice.expect_notice().await, "Carol a rejoint le chat");
+    assert_eq!(bob.expect_notice().await, "Carol a rejoint le chat");
+    let (a, b) = ("Alice".to_string(), "Bob".to_string());
+
+    // Un utilisateur qui n'a pas négocié les fichiers ne peut pas en recevoir
+    let id = uuid::Uuid::new_v4();
+    alice.send(&Message::file_offer(a.clone(), "Carol".to_string(), id, "notes.txt".to_string(), 5)).await;
+    alice.expect_error(error_codes::INVALID_MESSAGE).await;
+
+    alice.send(&Message::file_offer(a.clone(), b.clone(), id, "notes.txt".to_string(), 5)).await;
+    let offer = bob.recv().await;
+    assert_eq!(offer.op_code, OpCode::FileOffer);
+    assert_eq!(offer.sender.as_deref(), Some("Alice"));
+
+    // Les morceaux ne sont relayés qu'après l'acceptation du destinataire
+    alice.send(&Message::file_chunk(a.clone(), b.clone(), id, 0, b"notes".to_vec())).await;
+    alice.expect_error(error_codes::FORBIDDEN).await;
+    bob.send(&Message::file_accept(b.clone(), a.clone(), id, 0)).await;
+    assert_eq!(alice.recv().await.op_code, OpCode::FileAccept);
+    alice.send(&Message::file_chunk(a.clone(), b.clone(), id, 0, b"notes".to_vec())).await;
+    assert!(matches!(bob.recv().await.payload, MessagePayload::FileChunk { seq: 0, ref data, .. } if data == b"notes"));
+    let digest = "0".repeat(64);
+    alice.send(&Message::file_complete(a.clone(), b.clone(), id, digest.clone())).await;
+    assert_eq!(bob.recv().await.op_code, OpCode::FileComplete);
+    carol.expect_silence().await;
+
+    // Le transfert terminé est oublié par le serveur
+    alice.send(&Message::file_complete(a.clone(), b.clone(), id, digest)).await;
+    alice.expect_error(error_codes::INVALID_MESSAGE).await;
+
+    // Un transfert en cours est abandonné au départ d'un participant
+    let id = uuid::Uuid::new_v4();
+    alice.send(&Message::file_offer(a.clone(), b.clone(), id, "photo.png".to_string(), 100)).await;
+    assert_eq!(bob.recv().await.op_code, OpCode::FileOffer);
+    drop(alice);
+    let reject = bob.recv().await;
+    assert_eq!(reject.op_code, OpCode::FileReject);
+    assert_eq!(reject.payload.transfer_id(), Some(id));
+    assert_eq!(bob.expect_notice().await, "Alice a quitté le chat");
+}
+
+#[tokio::test]
+async fn test_history_replayed_after_reconnect() {
+    // Historique limité à trois messages: les plus anciens sont oubliés
+    let addr = spawn_server_with(MessageServer::new().with_history(MessageHistory::in_memory(3))).await;
+    let (mut alice, _) = TestClient::login(addr, "Alice").await;
+    let (bob, _) = TestClient::login_with(addr, "Bob", &[features::HISTORY]).await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+    drop(bob);
+    assert_eq!(alice.expect_notice().await, "Bob a quitté le chat");
+
+    for content in ["un", "deux", "trois", "quatre"] {
+        alice.send(&Message::text_message("Alice".to_string(), content.to_string())).await;
+    }
+    // La réponse à un message refusé garantit que les précédents ont été conservés
+    alice.send(&Message::private_message("Alice".to_string(), "Carol".to_string(), "secret".to_string())).await;
+    alice.expect_error(error_codes::USER_NOT_FOUND).await;
+
+    let mut bob = TestClient::reconnect_with(addr, "Bob", &[features::HISTORY]).await;
+    bob.expect_user_list().await;
+    assert_eq!(bob.expect_notice().await, "Bob a rejoint le chat");
+    bob.send(&Message::history_request("Bob".to_string(), None, None)).await;
+    match bob.recv().await.payload {
+        MessagePayload::History { messages } => {
+            let contents: Vec<String> = messages.iter().map(text_of).collect();
+            assert_eq!(contents, vec!["deux", "trois", "quatre"]);
+        }
+        other => panic!("expected history, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_offline_queue_delivered_on_next_login() {
+    let config = ServerConfig {
+        offline_queue_limit: 2,
+        ..ServerConfig::default()
+    };
+    let addr = spawn_server_with(MessageServer::new().with_config(config)).await;
+    let (mut alice, _) = TestClient::login(addr, "Alice").await;
+    let (bob, _) = TestClient::login(addr, "Bob").await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+    drop(bob);
+    assert_eq!(alice.expect_notice().await, "Bob a quitté le chat");
+
+    for content in ["premier", "second"] {
+        alice.send(&Message::private_message("Alice".to_string(), "Bob".to_string(), content.to_string())).await;
+        assert_eq!(alice.expect_notice().await, "Bob est hors ligne, le message lui sera remis à sa prochaine connexion");
+    }
+    // La file de Bob est pleine
+    alice.send(&Message::private_message("Alice".to_string(), "Bob".to_string(), "de trop".to_string())).await;
+    alice.expect_error(error_codes::QUEUE_FULL).await;
+
+    // Les messages en attente suivent la liste des utilisateurs, dans l'ordre d'envoi
+    let mut bob = TestClient::reconnect_with(addr, "Bob", &[]).await;
+    bob.expect_user_list().await;
+    for content in ["premier", "second"] {
+        let queued = bob.recv().await;
+        assert_eq!(queued.sender.as_deref(), Some("Alice"));
+        assert_eq!(queued.recipient.as_deref(), Some("Bob"));
+        assert_eq!(text_of(&queued), content);
+    }
+    assert_eq!(bob.expect_notice().await, "Bob a rejoint le chat");
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+
+    // Remis une seule fois
+    drop(bob);
+    assert_eq!(alice.expect_notice().await, "Bob a quitté le chat");
+    let mut bob = TestClient::reconnect_with(addr, "Bob", &[]).await;
+    bob.expect_user_list().await;
+    assert_eq!(bob.expect_notice().await, "Bob a rejoint le chat");
+    bob.expect_silence().await;
+}
+
+#[tokio::test]
+async fn test_resume_restores_rooms_and_replaces_old_connection() {
+    let addr = spawn_server().await;
+    let (mut bob, _) = TestClient::login_with(addr, "Bob", &[features::ROOMS]).await;
+    bob.send(&Message::join_room("Bob".to_string(), "rust".to_string())).await;
+    assert_eq!(bob.recv().await.op_code, OpCode::RoomJoined);
+    assert_eq!(bob.expect_notice().await, "Bob a rejoint le salon");
+
+    let mut alice = TestClient::open(addr, "Alice").await;
+    alice.send(&Message::hello(vec![features::ROOMS.to_string(), features::RESUME.to_string()])).await;
+    assert_eq!(alice.recv().await.op_code, OpCode::HelloAck);
+    alice.send(&Message::register("Alice".to_string(), "password123".to_string())).await;
+    assert_eq!(alice.recv().await.op_code, OpCode::RegisterAck);
+    alice.send(&Message::connect("Alice".to_string(), "password123".to_string())).await;
+    let resume_token = match alice.recv().await.payload {
+        MessagePayload::Session { resume_token, resumed: false } => resume_token,
+        other => panic!("expected session ack, got {:?}", other),
+    };
+    alice.expect_user_list().await;
+    assert_eq!(alice.expect_notice().await, "Alice a rejoint le chat");
+    assert_eq!(bob.expect_notice().await, "Alice a rejoint le chat");
+
+    // Une connexion déjà authentifiée ne peut pas se reconnecter
+    alice.send(&Message::connect("Alice".to_string(), "password123".to_string())).await;
+    alice.expect_error(error_codes::INVALID_MESSAGE).await;
+
+    alice.send(&Message::join_room("Alice".to_string(), "rust".to_string())).await;
+    assert_eq!(alice.recv().await.op_code, OpCode::RoomJoined);
+    assert_eq!(alice.expect_notice().await, "Alice a rejoint le salon");
+    assert_eq!(bob.expect_notice().await, "Alice a rejoint le salon");
+
+    // Reprise alors que l'ancienne connexion semble encore ouverte: elle est fermée
+    let mut resumed = TestClient::open(addr, "Alice").await;
+    resumed.send(&Message::hello(vec![features::ROOMS.to_string(), features::RESUME.to_string()])).await;
+    assert_eq!(resumed.recv().await.op_code, OpCode::HelloAck);
+    resumed.send(&Message::connect("Alice".to_string(), "password123".to_string()).with_resume_token(resume_token)).await;
+    assert!(matches!(resumed.recv().await.payload, MessagePayload::Session { resumed: true, .. }));
+    resumed.expect_user_list().await;
+    assert_eq!(resumed.expect_notice().await, "Alice a rejoint le chat");
+
+    // L'annonce de la reprise a pu la précéder
+    let mut closing = alice.recv().await;
+    if closing.op_code == OpCode::MessageReceived {
+        assert_eq!(text_of(&closing), "Alice a rejoint le chat");
+        closing = alice.recv().await;
+    }
+    assert!(matches!(closing.payload, MessagePayload::Error { code: error_codes::KICKED, .. }));
+    assert!(matches!(tokio::time::timeout(RECV_TIMEOUT, alice.connection.next()).await, Ok(None)));
+
+    // Les salons sont retrouvés, et les messages ne vont plus qu'à la nouvelle connexion
+    assert_eq!(bob.expect_notice().await, "Alice a rejoint le chat");
+    bob.send(&Message::room_message("Bob".to_string(), "rust".to_string(), "Re".to_string())).await;
+    let received = resumed.recv().await;
+    assert_eq!(received.room.as_deref(), Some("rust"));
+    assert_eq!(text_of(&received), "Re");
+    bob.expect_silence().await;
+}
+
+#[tokio::test]
+async fn test_presence_and_typing() {
+    let addr = spawn_server().await;
+    let (mut alice, _) = TestClient::login_with(addr, "Alice", &[features::PRESENCE]).await;
+    let (mut bob, _) = TestClient::login_with(addr, "Bob", &[features::PRESENCE, features::ROOMS]).await;
+    let (mut carol, _) = TestClient::login(addr, "Carol").await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+    assert_eq!(alice.expect_notice().await, "Carol a rejoint le chat");
+    assert_eq!(bob.expect_notice().await, "Carol a rejoint le chat");
+
+    // Un changement de statut est diffusé aux clients qui ont négocié la présence
+    bob.send(&Message::presence("Bob".to_string(), PresenceStatus::Busy, Some("En réunion".to_string()))).await;
+    let update = alice.recv().await;
+    assert_eq!(update.op_code, OpCode::Presence);
+    assert_eq!(update.sender.as_deref(), Some("Bob"));
+    assert!(matches!(update.payload, MessagePayload::Presence { status: PresenceStatus::Busy, .. }));
+
+    // Et figure dans la liste des utilisateurs
+    alice.send(&Message::new(OpCode::ListUsers, Some("Alice".to_string()), MessagePayload::Empty)).await;
+    match alice.recv().await.payload {
+        MessagePayload::UserList { presence, .. } => {
+            let statuses: Vec<_> = presence.iter().map(|p| (p.username.as_str(), p.status)).collect();
+            assert_eq!(statuses, vec![
+                ("Alice", PresenceStatus::Online),
+                ("Bob", PresenceStatus::Busy),
+                ("Carol", PresenceStatus::Online),
+            ]);
+        }
+        other => panic!("expected user list, got {:?}", other),
+    }
+
+    // Les indications de saisie suivent les règles de remise des messages
+    bob.send(&Message::typing("Bob".to_string(), Some("Alice".to_string()), None, true)).await;
+    let typing = alice.recv().await;
+    assert_eq!(typing.op_code, OpCode::Typing);
+    assert_eq!(typing.recipient.as_deref(), Some("Alice"));
+    bob.send(&Message::typing("Bob".to_string(), None, Some("rust".to_string()), true)).await;
+    bob.expect_error(error_codes::FORBIDDEN).await;
+    bob.send(&Message::typing("Bob".to_string(), None, None, false)).await;
+    assert!(matches!(alice.recv().await.payload, MessagePayload::Typing { typing: false }));
+
+    // Sans la fonctionnalité, on ne reçoit ni n'envoie de présence
+    carol.send(&Message::typing("Carol".to_string(), None, None, true)).await;
+    carol.expect_error(error_codes::INVALID_MESSAGE).await;
+    carol.expect_silence().await;
+    alice.expect_silence().await;
+}
+
+#[tokio::test]
+async fn test_auto_away() {
+    let config = ServerConfig {
+        away_after: Duration::from_secs(2),
+        ..ServerConfig::default()
+    };
+    let addr = spawn_server_with(MessageServer::new().with_config(config)).await;
+    let (mut alice, _) = TestClient::login_with(addr, "Alice", &[features::PRESENCE]).await;
+    let (mut bob, _) = TestClient::login_with(addr, "Bob", &[features::PRESENCE]).await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+    // Un statut choisi n'est pas remplacé par l'absence automatique
+    bob.send(&Message::presence("Bob".to_string(), PresenceStatus::Busy, None)).await;
+    assert_eq!(alice.recv().await.sender.as_deref(), Some("Bob"));
+
+    // Alice, inactive, passe absente: elle en est prévenue comme les autres
+    for client in [&mut alice, &mut bob] {
+        let away = client.recv().await;
+        assert_eq!(away.sender.as_deref(), Some("Alice"));
+        assert!(matches!(away.payload, MessagePayload::Presence { status: PresenceStatus::Away, .. }));
+    }
+
+    // Son message suivant la fait revenir, avant d'être relayé
+    alice.send(&Message::text_message("Alice".to_string(), "Me revoilà".to_string())).await;
+    assert!(matches!(alice.recv().await.payload, MessagePayload::Presence { status: PresenceStatus::Online, .. }));
+    assert!(matches!(bob.recv().await.payload, MessagePayload::Presence { status: PresenceStatus::Online, .. }));
+    assert_eq!(text_of(&bob.recv().await), "Me revoilà");
+}
+
+#[tokio::test]
+async fn test_receipts_relayed_to_original_sender() {
+    let addr = spawn_server().await;
+    let (mut alice, _) = TestClient::login_with(addr, "Alice", &[features::RECEIPTS]).await;
+    let (mut bob, _) = TestClient::login_with(addr, "Bob", &[features::RECEIPTS]).await;
+    let (mut carol, _) = TestClient::login_with(addr, "Carol", &[features::RECEIPTS]).await;
+    for notice in ["Bob", "Carol"] {
+        assert_eq!(alice.expect_notice().await, format!("{} a rejoint le chat", notice));
+    }
+    assert_eq!(bob.expect_notice().await, "Carol a rejoint le chat");
+
+    let private = Message::private_message("Alice".to_string(), "Bob".to_string(), "Salut".to_string());
+    alice.send(&private).await;
+    assert_eq!(text_of(&bob.recv().await), "Salut");
+
+    // L'accusé va à l'expéditeur d'origine, quel que soit le destinataire indiqué
+    bob.send(&Message::delivered("Bob".to_string(), "Carol".to_string(), private.id)).await;
+    let receipt = alice.recv().await;
+    assert_eq!(receipt.op_code, OpCode::Delivered);
+    assert_eq!(receipt.sender.as_deref(), Some("Bob"));
+    assert_eq!(receipt.recipient.as_deref(), Some("Alice"));
+    assert!(matches!(receipt.payload, MessagePayload::Receipt { message_id } if message_id == private.id));
+
+    // Un message reçu par un autre, ou inconnu, ne peut pas être accusé
+    carol.send(&Message::read("Carol".to_string(), "Alice".to_string(), private.id)).await;
+    carol.expect_error(error_codes::FORBIDDEN).await;
+    carol.send(&Message::read("Carol".to_string(), "Alice".to_string(), uuid::Uuid::new_v4())).await;
+    carol.expect_error(error_codes::USER_NOT_FOUND).await;
+
+    // Un message du chat général peut être accusé par tous, sauf son auteur
+    let general = Message::text_message("Alice".to_string(), "Bonjour à tous".to_string());
+    alice.send(&general).await;
+    for client in [&mut bob, &mut carol] {
+        assert_eq!(text_of(&client.recv().await), "Bonjour à tous");
+    }
+    carol.send(&Message::read("Carol".to_string(), "Alice".to_string(), general.id)).await;
+    let receipt = alice.recv().await;
+    assert_eq!(receipt.op_code, OpCode::Read);
+    assert_eq!(receipt.sender.as_deref(), Some("Carol"));
+    assert!(matches!(receipt.payload, MessagePayload::Receipt { message_id } if message_id == general.id));
+    alice.send(&Message::read("Alice".to_string(), "Alice".to_string(), general.id)).await;
+    alice.expect_error(error_codes::FORBIDDEN).await;
+
+    alice.expect_silence().await;
+    carol.expect_silence().await;
+}
+
+#[tokio::test]
+async fn test_duplicate_message_id_rejected() {
+    let addr = spawn_server().await;
+    let (mut alice, _) = TestClient::login(addr, "Alice").await;
+    let (mut bob, _) = TestClient::login(addr, "Bob").await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+
+    let original = Message::text_message("Alice".to_string(), "Bonjour".to_string());
+    alice.send(&original).await;
+    assert_eq!(text_of(&bob.recv().await), "Bonjour");
+
+    // Ni l'auteur ni un autre ne peuvent réutiliser l'identifiant d'un message conservé
+    alice.send(&original).await;
+    alice.expect_error(error_codes::INVALID_MESSAGE).await;
+    let mut copy = Message::private_message("Bob".to_string(), "Alice".to_string(), "Bonjour".to_string());
+    copy.id = original.id;
+    bob.send(&copy).await;
+    bob.expect_error(error_codes::INVALID_MESSAGE).await;
+
+    alice.expect_silence().await;
+    bob.expect_silence().await;
+}
+
+#[tokio::test]
+async fn test_heartbeat_and_idle_timeout() {
+    let config = ServerConfig {
+        // Vérifiée chaque seconde: le délai laisse le temps de répondre au Ping
+        ping_interval: Duration::from_secs(1),
+        idle_timeout: Duration::from_secs(3),
+        ..ServerConfig::default()
+    };
+    let addr = spawn_server_with(MessageServer::new().with_config(config)).await;
+    let (mut alice, _) = TestClient::login_with(addr, "Alice", &[features::HEARTBEAT]).await;
+    let (mut bob, _) = TestClient::login(addr, "Bob").await;
+
+    // Alice répond aux Ping et reste connectée, jusqu'au départ de Bob
+    let alice = tokio::spawn(async move {
+        let mut pings = 0;
+        loop {
+            let msg = alice.recv().await;
+            if msg.op_code == OpCode::Ping {
+                pings += 1;
+                alice.send(&Message::pong()).await;
+            } else if text_of(&msg) == "Bob a quitté le chat" {
+                return pings;
+            }
+        }
+    });
+
+    // Bob, silencieux et sans heartbeat, ne reçoit pas de Ping: sa session expire
+    bob.expect_error(error_codes::IDLE_TIMEOUT).await;
+    assert!(matches!(tokio::time::timeout(RECV_TIMEOUT, bob.connection.next()).await, Ok(None)));
+    assert!(alice.await.unwrap() > 0);
+
+    // Le nom de Bob est libéré
+    let mut bob = TestClient::reconnect_with(addr, "Bob", &[]).await;
+    bob.expect_user_list().await;
+}
+
+#[tokio::test]
+async fn test_message_corrections() {
+    let config = ServerConfig {
+        admins: ["Admin".to_string()].into_iter().collect(),
+        ..ServerConfig::default()
+    };
+    let addr = spawn_server_with(MessageServer::new().with_config(config)).await;
+    let (mut alice, _) = TestClient::login_with(addr, "Alice", &[features::EDITS]).await;
+    let (mut bob, _) = TestClient::login_with(addr, "Bob", &[features::EDITS, features::HISTORY]).await;
+    let (mut carol, _) = TestClient::login(addr, "Carol").await;
+    let (mut admin, _) = TestClient::login_with(addr, "Admin", &[features::EDITS]).await;
+    for notice in ["Bob", "Carol", "Admin"] {
+        assert_eq!(alice.expect_notice().await, format!("{} a rejoint le chat", notice));
+    }
+    for notice in ["Carol", "Admin"] {
+        assert_eq!(bob.expect_notice().await, format!("{} a rejoint le chat", notice));
+    }
+    assert_eq!(carol.expect_notice().await, "Admin a rejoint le chat");
+
+    let original = Message::text_message("Alice".to_string(), "Bonjuor".to_string());
+    alice.send(&original).await;
+    for client in [&mut bob, &mut carol, &mut admin] {
+        assert_eq!(text_of(&client.recv().await), "Bonjuor");
+    }
+
+    // Seul l'auteur (ou un administrateur) peut corriger un message conservé
+    bob.send(&Message::edit_message("Bob".to_string(), original.id, "Piraté".to_string())).await;
+    bob.expect_error(error_codes::FORBIDDEN).await;
+    alice.send(&Message::edit_message("Alice".to_string(), uuid::Uuid::new_v4(), "Bonjour".to_string())).await;
+    alice.expect_error(error_codes::USER_NOT_FOUND).await;
+
+    alice.send(&Message::edit_message("Alice".to_string(), original.id, "Bonjour".to_string())).await;
+    for client in [&mut bob, &mut admin] {
+        let edit = client.recv().await;
+        assert_eq!(edit.op_code, OpCode::EditMessage);
+        assert_eq!(edit.payload.corrected_id(), Some(original.id));
+    }
+
+    // L'historique rend la version corrigée
+    bob.send(&Message::history_request("Bob".to_string(), None, None)).await;
+    match bob.recv().await.payload {
+        MessagePayload::History { messages } => {
+            assert_eq!(messages.len(), 1);
+            assert_eq!(text_of(&messages[0]), "Bonjour");
+            assert!(messages[0].edited_at.is_some());
+        }
+        other => panic!("expected history, got {:?}", other),
+    }
+
+    admin.send(&Message::delete_message("Admin".to_string(), original.id)).await;
+    for client in [&mut alice, &mut bob] {
+        let delete = client.recv().await;
+        assert_eq!(delete.op_code, OpCode::DeleteMessage);
+        assert_eq!(delete.sender.as_deref(), Some("Admin"));
+    }
+    bob.send(&Message::history_request("Bob".to_string(), None, None)).await;
+    assert!(matches!(bob.recv().await.payload, MessagePayload::History { ref messages } if messages.is_empty()));
+
+    // Les clients qui n'ont pas négocié les corrections n'en reçoivent pas
+    carol.expect_silence().await;
+}
+
+#[tokio::test]
+async fn test_flood_protection() {
+    let config = ServerConfig {
+        max_message_length: 10,
+        // Hello, Register et Connect consomment trois des cinq jetons, qui ne se reconstituent presque pas
+        rate_burst: 5,
+        rate_per_second: 0.001,
+        max_violations: 3,
+        ..ServerConfig::default()
+    };
+    let addr = spawn_server_with(MessageServer::new().with_config(config)).await;
+    let (mut alice, _) = TestClient::login(addr, "Alice").await;
+    let (mut bob, _) = TestClient::login(addr, "Bob").await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+
+    // Un message trop long est refusé sans consommer de jeton
+    bob.send(&Message::text_message("Bob".to_string(), "Beaucoup trop long".to_string())).await;
+    bob.expect_error(error_codes::LIMIT_EXCEEDED).await;
+    for content in ["Un", "Deux"] {
+        bob.send(&Message::text_message("Bob".to_string(), content.to_string())).await;
+        assert_eq!(text_of(&alice.recv().await), content);
+    }
+
+    // Le débit est dépassé, puis la récidive ferme la session
+    bob.send(&Message::text_message("Bob".to_string(), "Trois".to_string())).await;
+    bob.expect_error(error_codes::LIMIT_EXCEEDED).await;
+    bob.send(&Message::text_message("Bob".to_string(), "Quatre".to_string())).await;
+    bob.expect_error(error_codes::LIMIT_EXCEEDED).await;
+    assert!(bob.connection.next().await.is_none());
+    assert_eq!(alice.expect_notice().await, "Bob a quitté le chat");
+}
+
+#[tokio::test]
+async fn test_transfer_frames_rate_limited() {
+    let config = ServerConfig {
+        // Hello, Register et Connect consomment trois des cinq jetons
+        rate_burst: 5,
+        rate_per_second: 0.001,
+        max_violations: 3,
+        ..ServerConfig::default()
+    };
+    let addr = spawn_server_with(MessageServer::new().with_config(config)).await;
+    let (mut alice, _) = TestClient::login_with(addr, "Alice", &[features::FILES]).await;
+    let (mut bob, _) = TestClient::login_with(addr, "Bob", &[features::FILES]).await;
+    assert_eq!(alice.expect_notice().await, "Bob a rejoint le chat");
+
+    // Les morceaux d'un transfert accepté et leurs accusés ont leur propre débit
+    let transfer_id = uuid::Uuid::new_v4();
+    alice.send(&Message::file_offer("Alice".to_string(), "Bob".to_string(), transfer_id, "notes.txt".to_string(), 5)).await;
+    assert_eq!(bob.recv().await.op_code, OpCode::FileOffer);
+    bob.send(&Message::file_accept("Bob".to_string(), "Alice".to_string(), transfer_id, 0)).await;
+    assert_eq!(alice.recv().await.op_code, OpCode::FileAccept);
+    for seq in 0..5 {
+        alice.send(&Message::file_chunk("Alice".to_string(), "Bob".to_string(), transfer_id, seq, vec![b'x'])).await;
+        assert_eq!(bob.recv().await.op_code, OpCode::FileChunk);
+    }
+
+    // Des morceaux hors de tout transfert accepté comptent comme des messages: le
+    // flood est refusé une fois les jetons épuisés, puis la session est fermée
+    let unknown = uuid::Uuid::new_v4();
+    for seq in 0..5 {
+        bob.send(&Message::file_chunk("Bob".to_string(), "Alice".to_string(), unknown, seq, vec![b'x'])).await;
+    }
+    for expected in [
+        error_codes::INVALID_MESSAGE,
+        error_codes::INVALID_MESSAGE,
+        error_codes::LIMIT_EXCEEDED,
+        error_codes::LIMIT_EXCEEDED,
+        error_codes::LIMIT_EXCEEDED,
+    ] {
+        bob.expect_error(expected).await;
+    }
+    assert!(bob.connection.next().await.is_none());
+    // Le transfert inachevé est abandonné avec la session
+    assert_eq!(alice.recv().await.op_code, OpCode::FileReject);
+    assert_eq!(alice.expect_notice().await, "Bob a quitté le chat");
+}
+
+#[tokio::test]
+async fn test_moderation() {
+    let config = ServerConfig {
+        admins: ["Admin".to_string()].into_iter().collect(),
+        ..ServerConfig::default()
+    };
+    let addr = spawn_server_with(MessageServer::new().with_config(config)).await;
+    let (mut admin, _) = TestClient::login(addr, "Admin").await;
+    let (mut bob, _) = TestClient::login(addr, "Bob").await;
+    assert_eq!(admin.expect_notice().await, "Bob a rejoint le chat");
+    let moderation = |op_code, target: &str, duration| {
+        Message::moderation(op_code, "Admin".to_string(), target.to_string(), duration, Some("spam".to_string()))
+    };
+
+    // Les sanctions sont réservées aux administrateurs
+    bob.send(&Message::moderation(OpCode::Kick, "Bob".to_string(), "Admin".to_string(), None, None)).await;
+    bob.expect_error(error_codes::FORBIDDEN).await;
+
+    // Un utilisateur réduit au silence est prévenu, puis ses messages sont refusés
+    admin.send(&moderation(OpCode::Mute, "Bob", Some(3600))).await;
+    assert_eq!(admin.recv().await.op_code, OpCode::ModerationAck);
+    assert!(bob.expect_notice().await.starts_with("Vous ne pouvez plus envoyer de messages"));
+    bob.send(&Message::text_message("Bob".to_string(), "Encore moi".to_string())).await;
+    bob.expect_error(error_codes::FORBIDDEN).await;
+    admin.expect_silence().await;
+
+    // L'expulsion ferme la connexion de la cible
+    admin.send(&moderation(OpCode::Kick, "Bob", None)).await;
+    assert_eq!(admin.recv().await.op_code, OpCode::ModerationAck);
+    bob.expect_error(error_codes::KICKED).await;
+    assert!(bob.connection.next().await.is_none());
+    assert_eq!(admin.expect_notice().await, "Bob a quitté le chat");
+
+    // Bannir un utilisateur connecté ne bloque pas les autres utilisateurs de son adresse
+    let (mut carol, _) = TestClient::login(addr, "Carol").await;
+    assert_eq!(admin.expect_notice().await, "Carol a rejoint le chat");
+    admin.send(&moderation(OpCode::Ban, "Carol", None)).await;
+    assert_eq!(admin.recv().await.op_code, OpCode::ModerationAck);
+    carol.expect_error(error_codes::KICKED).await;
+    assert!(carol.connection.next().await.is_none());
+    assert_eq!(admin.expect_notice().await, "Carol a quitté le chat");
+    let (_dave, _) = TestClient::login(addr, "Dave").await;
+    assert_eq!(admin.expect_notice().await, "Dave a rejoint le chat");
+
+    // Seul un compte existant (ou une adresse) peut être banni
+    admin.send(&moderation(OpCode::Ban, "Inconnu", None)).await;
+    admin.expect_error(error_codes::USER_NOT_FOUND).await;
+
+    // Un nom banni ne peut plus se connecter
+    admin.send(&moderation(OpCode::Ban, "Bob", None)).await;
+    assert_eq!(admin.recv().await.op_code, OpCode::ModerationAck);
+    let mut bob = TestClient::open(addr, "Bob").await;
+    bob.hello().await;
+    bob.send(&Message::connect("Bob".to_string(), "password123".to_string())).await;
+    bob.expect_error(error_codes::FORBIDDEN).await;
+
+    // Une adresse bannie expulse ses sessions et refuse les nouvelles connexions
+    admin.send(&moderation(OpCode::Ban, "127.0.0.1", Some(3600))).await;
+    assert_eq!(admin.recv().await.op_code, OpCode::ModerationAck);
+    admin.expect_error(error_codes::KICKED).await;
+    let mut newcomer = TestClient::open(addr, "Eve").await;
+    newcomer.expect_error(error_codes::FORBIDDEN).await;
 }
 
-/// Script de test manuel pour valider le protocole
-/// 
-/// Pour exécuter un test complet :
-/// 
-/// 1. Terminal 1: `cargo run server`
-/// 2. Terminal 2: `cargo run client Alice`
-/// 3. Terminal 3: `cargo run client Bob`
-/// 
-/// Puis testez les interactions entre les clients.
+// Script de test manuel pour valider le protocole
+// 
+// Pour exécuter un test complet :
+// 
+// 1. Terminal 1: `cargo run server`
+// 2. Terminal 2: `cargo run client Alice`
+// 3. Terminal 3: `cargo run client Bob`
+// 
+// Puis testez les interactions entre les clients.
 
 #[cfg(test)]
 mod integration_tests {
@@ -55,11 +902,12 @@ mod integration_tests {
     
     #[test]
     fn test_message_creation() {
-        let msg = Message::connect("test_user".to_string());
+        let msg = Message::connect("test_user".to_string(), "password".to_string());
         assert_eq!(msg.op_code, OpCode::Connect);
         
-        if let MessagePayload::Connect { username } = msg.payload {
+        if let MessagePayload::Connect { username, password, .. } = msg.payload {
             assert_eq!(username, "test_user");
+            assert_eq!(password, "password");
         } else {
             panic!("Wrong payload type");
         }
@@ -78,11 +926,11 @@ mod integration_tests {
     #[test]
     fn test_message_validation() {
         // Test message valide
-        let valid_msg = Message::connect("valid_user".to_string());
+        let valid_msg = Message::connect("valid_user".to_string(), "password".to_string());
         assert!(valid_msg.validate().is_ok());
         
         // Test message invalide (username vide)
-        let invalid_msg = Message::connect("".to_string());
+        let invalid_msg = Message::connect("".to_string(), "password".to_string());
         assert!(invalid_msg.validate().is_err());
         
         // Test message texte sans expéditeur
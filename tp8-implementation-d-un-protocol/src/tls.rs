@@ -0,0 +1,246 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Flux sur lequel circulent les trames: TCP en clair ou session TLS
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+/// Paramètres TLS du client: autorités de confiance et nom attendu dans le certificat du serveur
+#[derive(Clone)]
+pub struct TlsClientSettings {
+    pub connector: TlsConnector,
+    pub server_name: ServerName<'static>,
+}
+
+/// Crée l'accepteur TLS du serveur à partir de son certificat et de sa clé (PEM)
+///
+/// Avec `client_ca_path`, les clients doivent présenter un certificat signé par
+/// cette autorité (TLS mutuel).
+pub fn server_acceptor(
+    cert_path: &Path,
+    key_path: &Path,
+    client_ca_path: Option<&Path>,
+) -> Result<TlsAcceptor, String> {
+    let certs = load_certs(cert_path)?;
+    let key = load_key(key_path)?;
+    let provider = Arc::new(ring::default_provider());
+
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+    let builder = match client_ca_path {
+        Some(ca_path) => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(load_roots(ca_path)?), provider)
+                .build()
+                .map_err(|e| format!("{}: {}", ca_path.display(), e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("{}: {}", cert_path.display(), e))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Crée le connecteur TLS du client, qui ne fait confiance qu'à l'autorité `ca_path`
+///
+/// `identity` (certificat, clé) est présenté au serveur quand il exige le TLS mutuel.
+pub fn client_connector(ca_path: &Path, identity: Option<(&Path, &Path)>) -> Result<TlsConnector, String> {
+    let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(load_roots(ca_path)?);
+
+    let config = match identity {
+        Some((cert_path, key_path)) => builder
+            .with_client_auth_cert(load_certs(cert_path)?, load_key(key_path)?)
+            .map_err(|e| format!("{}: {}", cert_path.display(), e))?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Nom de serveur à vérifier dans son certificat: `host` seul, ou la partie hôte de `host:port`
+pub fn server_name(addr: &str) -> Result<ServerName<'static>, String> {
+    let host = match addr.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => host.trim_start_matches('[').trim_end_matches(']'),
+        _ => addr,
+    };
+    ServerName::try_from(host.to_string()).map_err(|e| format!("{}: {}", host, e))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("{}: no certificate found", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn load_roots(path: &Path) -> Result<RootCertStore, String> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use futures::{SinkExt, StreamExt};
+    use rcgen::{BasicConstraints, CertificateParams, CertifiedKey, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::Framed;
+
+    use super::*;
+    use crate::codec::{Framing, MessageCodec};
+    use crate::protocol::{Message, OpCode};
+
+    /// Autorité et certificats auto-signés écrits dans un répertoire temporaire
+    struct TestPki {
+        dir: PathBuf,
+    }
+
+    impl TestPki {
+        fn generate() -> Self {
+            let dir = std::env::temp_dir().join(format!("tp8-tls-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+
+            let ca = Self::certify(CertificateParams::new(Vec::<String>::new()).unwrap(), None, true);
+            fs::write(dir.join("ca.pem"), ca.cert.pem()).unwrap();
+
+            let server = Self::certify(CertificateParams::new(vec!["localhost".to_string()]).unwrap(), Some(&ca), false);
+            fs::write(dir.join("server.pem"), server.cert.pem()).unwrap();
+            fs::write(dir.join("server.key"), server.key_pair.serialize_pem()).unwrap();
+
+            let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+            params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+            let client = Self::certify(params, Some(&ca), false);
+            fs::write(dir.join("client.pem"), client.cert.pem()).unwrap();
+            fs::write(dir.join("client.key"), client.key_pair.serialize_pem()).unwrap();
+
+            // Une autre autorité, à laquelle personne ne fait confiance
+            let other = Self::certify(CertificateParams::new(Vec::<String>::new()).unwrap(), None, true);
+            fs::write(dir.join("other-ca.pem"), other.cert.pem()).unwrap();
+
+            Self { dir }
+        }
+
+        fn certify(mut params: CertificateParams, issuer: Option<&CertifiedKey>, is_ca: bool) -> CertifiedKey {
+            if is_ca {
+                params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            }
+            let key_pair = KeyPair::generate().unwrap();
+            let cert = match issuer {
+                Some(issuer) => params.signed_by(&key_pair, &issuer.cert, &issuer.key_pair).unwrap(),
+                None => params.self_signed(&key_pair).unwrap(),
+            };
+            CertifiedKey { cert, key_pair }
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.join(name)
+        }
+    }
+
+    impl Drop for TestPki {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Établit une connexion TLS et fait transiter un message du client vers le serveur
+    async fn exchange(acceptor: TlsAcceptor, connector: TlsConnector) -> Result<Message, String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let stream = acceptor.accept(stream).await.map_err(|e| e.to_string())?;
+            let mut connection = Framed::new(Box::new(stream) as Box<dyn Transport>, MessageCodec::new(Framing::Json));
+            match connection.next().await {
+                Some(Ok(Ok(msg))) => Ok(msg),
+                other => Err(format!("{:?}", other.map(|r| r.map(|m| m.map(|m| m.op_code))))),
+            }
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let client_result = async {
+            let stream = connector
+                .connect(server_name("localhost").unwrap(), stream)
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut connection = Framed::new(Box::new(stream) as Box<dyn Transport>, MessageCodec::new(Framing::Json));
+            connection.send(&Message::ping()).await.map_err(|e| e.to_string())
+        }
+        .await;
+
+        let server_result = server.await.unwrap();
+        client_result.and(server_result)
+    }
+
+    #[tokio::test]
+    async fn test_tls_roundtrip() {
+        let pki = TestPki::generate();
+        let acceptor = server_acceptor(&pki.path("server.pem"), &pki.path("server.key"), None).unwrap();
+        let connector = client_connector(&pki.path("ca.pem"), None).unwrap();
+
+        let msg = exchange(acceptor, connector).await.unwrap();
+        assert_eq!(msg.op_code, OpCode::Ping);
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_server_rejected() {
+        let pki = TestPki::generate();
+        let acceptor = server_acceptor(&pki.path("server.pem"), &pki.path("server.key"), None).unwrap();
+        let connector = client_connector(&pki.path("other-ca.pem"), None).unwrap();
+
+        assert!(exchange(acceptor, connector).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls() {
+        let pki = TestPki::generate();
+        let server_cert = pki.path("server.pem");
+        let server_key = pki.path("server.key");
+        let ca = pki.path("ca.pem");
+
+        // Sans certificat client, la connexion est refusée
+        let acceptor = server_acceptor(&server_cert, &server_key, Some(&ca)).unwrap();
+        assert!(exchange(acceptor, client_connector(&ca, None).unwrap()).await.is_err());
+
+        let acceptor = server_acceptor(&server_cert, &server_key, Some(&ca)).unwrap();
+        let identity = (pki.path("client.pem"), pki.path("client.key"));
+        let connector = client_connector(&ca, Some((&identity.0, &identity.1))).unwrap();
+        assert_eq!(exchange(acceptor, connector).await.unwrap().op_code, OpCode::Ping);
+    }
+
+    #[test]
+    fn test_server_name_from_address() {
+        assert_eq!(server_name("localhost:8080").unwrap(), ServerName::try_from("localhost").unwrap());
+        assert_eq!(server_name("chat.example.org").unwrap(), ServerName::try_from("chat.example.org").unwrap());
+        assert!(matches!(server_name("127.0.0.1:8080").unwrap(), ServerName::IpAddress(_)));
+        assert!(matches!(server_name("[::1]:8080").unwrap(), ServerName::IpAddress(_)));
+        assert!(server_acceptor(Path::new("/nonexistent.pem"), Path::new("/nonexistent.key"), None).is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::protocol::{Message, MessagePayload, FILE_CHUNK_SIZE};
+
+/// Nombre de morceaux envoyés d'avance, sans attendre l'accusé du destinataire
+const SEND_WINDOW: u64 = 8;
+
+/// Extension des fichiers en cours de réception, conservés pour reprendre le transfert
+const PART_EXTENSION: &str = "part";
+
+/// Empreinte SHA-256 d'un fichier, en hexadécimal
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; FILE_CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Forme courte d'un identifiant de transfert, à taper dans `/accept` et `/reject`
+pub fn short_id(transfer_id: &Uuid) -> String {
+    transfer_id.to_string()[..8].to_string()
+}
+
+/// Fichier proposé par un autre utilisateur, en attente de réponse
+#[derive(Debug, Clone)]
+pub struct Offer {
+    pub transfer_id: Uuid,
+    pub sender: String,
+    pub name: String,
+    pub size: u64,
+}
+
+/// Fichier en cours d'envoi
+struct Outgoing {
+    recipient: String,
+    name: String,
+    path: PathBuf,
+    size: u64,
+    sha256: String,
+    /// Ouvert à l'acceptation, positionné sur le prochain morceau à envoyer
+    file: Option<File>,
+    next_seq: u64,
+    /// Octets dont le destinataire a accusé réception
+    acked: u64,
+}
+
+/// Fichier en cours de réception, écrit dans `<nom>.part`
+struct Incoming {
+    sender: String,
+    name: String,
+    size: u64,
+    part_path: PathBuf,
+    file: File,
+    next_seq: u64,
+    received: u64,
+}
+
+/// Transferts de fichiers d'un client, des deux côtés
+///
+/// L'expéditeur propose le fichier, le destinataire l'accepte en indiquant où
+/// reprendre (un `.part` d'un transfert interrompu est complété), puis accuse
+/// chaque morceau reçu; l'expéditeur garde au plus `SEND_WINDOW` morceaux
+/// d'avance, et termine par l'empreinte SHA-256 que le destinataire vérifie.
+pub struct Transfers {
+    username: String,
+    download_dir: PathBuf,
+    outgoing: HashMap<Uuid, Outgoing>,
+    offers: Vec<Offer>,
+    incoming: HashMap<Uuid, Incoming>,
+}
+
+impl Transfers {
+    pub fn new(username: String, download_dir: PathBuf) -> Self {
+        Self {
+            username,
+            download_dir,
+            outgoing: HashMap::new(),
+            offers: Vec::new(),
+            incoming: HashMap::new(),
+        }
+    }
+
+    /// Propose le fichier `path` à `recipient`
+    pub fn offer(&mut self, recipient: &str, path: &Path) -> Result<Message, String> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("{}: nom de fichier invalide", path.display()))?
+            .to_string();
+        let metadata = fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if !metadata.is_file() {
+            return Err(format!("{}: ce n'est pas un fichier", path.display()));
+        }
+        let sha256 = sha256_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let transfer_id = Uuid::new_v4();
+        self.outgoing.insert(transfer_id, Outgoing {
+            recipient: recipient.to_string(),
+            name: name.clone(),
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            sha256,
+            file: None,
+            next_seq: 0,
+            acked: 0,
+        });
+        Ok(Message::file_offer(self.username.clone(), recipient.to_string(), transfer_id, name, metadata.len()))
+    }
+
+    /// Propositions reçues en attente de réponse, de la plus ancienne à la plus récente
+    pub fn offers(&self) -> &[Offer] {
+        &self.offers
+    }
+
+    /// Accepte la proposition dont l'identifiant commence par `prefix` (la plus ancienne
+    /// par défaut), en reprenant un éventuel transfert interrompu du même fichier
+    pub fn accept(&mut self, prefix: Option<&str>) -> Result<Message, String> {
+        let offer = self.take_offer(prefix)?;
+        fs::create_dir_all(&self.download_dir).map_err(|e| format!("{}: {}", self.download_dir.display(), e))?;
+        let part_path = self.download_dir.join(format!("{}.{}", offer.name, PART_EXTENSION));
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&part_path)
+            .map_err(|e| format!("{}: {}", part_path.display(), e))?;
+        // Seuls les morceaux complets d'un fichier partiel plausible sont conservés
+        let existing = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        let existing = if existing > offer.size { 0 } else { existing };
+        let offset = existing - existing % FILE_CHUNK_SIZE as u64;
+        let mut file = file;
+        file.set_len(offset)
+            .and_then(|_| file.seek(SeekFrom::Start(offset)))
+            .map_err(|e| format!("{}: {}", part_path.display(), e))?;
+
+        if offset > 0 {
+            println!("📥 Reprise de {} à {} / {} octets", offer.name, offset, offer.size);
+        } else {
+            println!("📥 Réception de {} ({} octets) dans {}", offer.name, offer.size, self.download_dir.display());
+        }
+        self.incoming.insert(offer.transfer_id, Incoming {
+            sender: offer.sender.clone(),
+            name: offer.name,
+            size: offer.size,
+            part_path,
+            file,
+            next_seq: offset / FILE_CHUNK_SIZE as u64,
+            received: offset,
+        });
+        Ok(Message::file_accept(self.username.clone(), offer.sender, offer.transfer_id, offset))
+    }
+
+    /// Refuse la proposition dont l'identifiant commence par `prefix` (la plus ancienne par défaut)
+    pub fn reject(&mut self, prefix: Option<&str>) -> Result<Message, String> {
+        let offer = self.take_offer(prefix)?;
+        println!("🚫 {} de {} refusé", offer.name, offer.sender);
+        Ok(Message::file_reject(self.username.clone(), offer.sender, offer.transfer_id, None))
+    }
+
+    /// Oublie les transferts en cours après une coupure (le serveur les a abandonnés);
+    /// les fichiers partiels sont gardés pour une reprise
+    pub fn clear(&mut self) {
+        if !self.outgoing.is_empty() || !self.incoming.is_empty() {
+            println!("⚠️  Transferts interrompus: renvoyez les fichiers pour les reprendre");
+        }
+        self.outgoing.clear();
+        self.offers.clear();
+        self.incoming.clear();
+    }
+
+    /// Traite un message de transfert reçu; renvoie les messages à envoyer en réponse
+    pub fn handle(&mut self, msg: &Message) -> Vec<Message> {
+        let sender = msg.sender.clone().unwrap_or_default();
+        match &msg.payload {
+            MessagePayload::FileOffer { transfer_id, name, size } => {
+                println!(
+                    "📎 {} vous propose {} ({} octets): /accept {} ou /reject {}",
+                    sender, name, size, short_id(transfer_id), short_id(transfer_id)
+                );
+                self.offers.push(Offer { transfer_id: *transfer_id, sender, name: name.clone(), size: *size });
+                Vec::new()
+            }
+            MessagePayload::FileAccept { transfer_id, offset } => self.send_chunks(*transfer_id, &sender, *offset),
+            MessagePayload::FileChunk { transfer_id, seq, data } => self.receive_chunk(*transfer_id, &sender, *seq, data),
+            MessagePayload::FileComplete { transfer_id, sha256 } => {
+                self.complete(*transfer_id, &sender, sha256);
+                Vec::new()
+            }
+            MessagePayload::FileReject { transfer_id, reason } => {
+                let reason = reason.as_deref().map(|reason| format!(": {}", reason)).unwrap_or_default();
+                if let Some(transfer) = self.outgoing.remove(transfer_id) {
+                    println!("🚫 {} a refusé {}{}", sender, transfer.name, reason);
+                } else if let Some(transfer) = self.incoming.remove(transfer_id) {
+                    println!("⚠️  Réception de {} interrompue{} (renvoi possible pour reprendre)", transfer.name, reason);
+                } else if let Some(index) = self.offers.iter().position(|offer| offer.transfer_id == *transfer_id) {
+                    let offer = self.offers.remove(index);
+                    println!("🚫 {} a retiré sa proposition de {}{}", sender, offer.name, reason);
+                }
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn take_offer(&mut self, prefix: Option<&str>) -> Result<Offer, String> {
+        let index = match prefix {
+            None => (!self.offers.is_empty()).then_some(0),
+            Some(prefix) => self.offers.iter().position(|offer| offer.transfer_id.to_string().starts_with(prefix)),
+        };
+        index
+            .map(|index| self.offers.remove(index))
+            .ok_or_else(|| "Aucune proposition de fichier correspondante".to_string())
+    }
+
+    /// Accusé du destinataire: envoie les morceaux suivants, ou la fin du transfert
+    fn send_chunks(&mut self, transfer_id: Uuid, sender: &str, offset: u64) -> Vec<Message> {
+        let Some(transfer) = self.outgoing.get_mut(&transfer_id) else {
+            return Vec::new();
+        };
+        if transfer.recipient != sender {
+            return Vec::new();
+        }
+
+        // Premier accusé: le destinataire indique où reprendre
+        if transfer.file.is_none() {
+            if offset > transfer.size || !offset.is_multiple_of(FILE_CHUNK_SIZE as u64) {
+                let transfer = self.outgoing.remove(&transfer_id).map(|t| t.name).unwrap_or_default();
+                println!("❌ Position de reprise invalide pour {}", transfer);
+                return vec![self.abort(transfer_id, sender, "Invalid resume offset")];
+            }
+            let opened = File::open(&transfer.path).and_then(|mut file| {
+                file.seek(SeekFrom::Start(offset))?;
+                Ok(file)
+            });
+            match opened {
+                Ok(file) => transfer.file = Some(file),
+                Err(e) => {
+                    println!("❌ {}: {}", transfer.path.display(), e);
+                    self.outgoing.remove(&transfer_id);
+                    return vec![self.abort(transfer_id, sender, "Read error")];
+                }
+            }
+            transfer.next_seq = offset / FILE_CHUNK_SIZE as u64;
+            if offset > 0 {
+                println!("📤 {} reprend {} à {} / {} octets", sender, transfer.name, offset, transfer.size);
+            } else {
+                println!("📤 {} a accepté {}, envoi en cours...", sender, transfer.name);
+            }
+        }
+        transfer.acked = transfer.acked.max(offset);
+
+        if transfer.acked >= transfer.size {
+            let transfer = self.outgoing.remove(&transfer_id).expect("transfert en cours");
+            println!("✅ {} envoyé à {} ({} octets)", transfer.name, transfer.recipient, transfer.size);
+            return vec![Message::file_complete(self.username.clone(), transfer.recipient, transfer_id, transfer.sha256)];
+        }
+
+        let mut chunks = Vec::new();
+        let window_end = transfer.acked + SEND_WINDOW * FILE_CHUNK_SIZE as u64;
+        while transfer.next_seq * (FILE_CHUNK_SIZE as u64) < transfer.size.min(window_end) {
+            let mut data = vec![0; FILE_CHUNK_SIZE];
+            let read = match transfer.file.as_mut().map(|file| read_full(file, &mut data)) {
+                Some(Ok(read)) if read > 0 => read,
+                _ => {
+                    println!("❌ Lecture de {} impossible", transfer.path.display());
+                    self.outgoing.remove(&transfer_id);
+                    chunks.push(self.abort(transfer_id, sender, "Read error"));
+                    return chunks;
+                }
+            };
+            data.truncate(read);
+            chunks.push(Message::file_chunk(self.username.clone(), sender.to_string(), transfer_id, transfer.next_seq, data));
+            transfer.next_seq += 1;
+        }
+        chunks
+    }
+
+    /// Écrit un morceau reçu et l'accuse
+    fn receive_chunk(&mut self, transfer_id: Uuid, sender: &str, seq: u64, data: &[u8]) -> Vec<Message> {
+        let Some(transfer) = self.incoming.get_mut(&transfer_id) else {
+            return Vec::new();
+        };
+        if transfer.sender != sender {
+            return Vec::new();
+        }
+
+        let error = if seq != transfer.next_seq || transfer.received + data.len() as u64 > transfer.size {
+            Some("Unexpected chunk".to_string())
+        } else {
+            transfer.file.write_all(data).err().map(|e| format!("Write error: {}", e))
+        };
+        if let Some(error) = error {
+            let transfer = self.incoming.remove(&transfer_id).expect("transfert en cours");
+            println!("❌ Réception de {} interrompue: {}", transfer.name, error);
+            return vec![self.abort(transfer_id, sender, &error)];
+        }
+
+        transfer.next_seq += 1;
+        transfer.received += data.len() as u64;
+        vec![Message::file_accept(self.username.clone(), sender.to_string(), transfer_id, transfer.received)]
+    }
+
+    /// Vérifie l'empreinte du fichier reçu puis lui donne son nom définitif
+    fn complete(&mut self, transfer_id: Uuid, sender: &str, sha256: &str) {
+        if self.incoming.get(&transfer_id).is_none_or(|transfer| transfer.sender != sender) {
+            return;
+        }
+        let Some(transfer) = self.incoming.remove(&transfer_id) else {
+            return;
+        };
+        let Incoming { name, size, part_path, file, received, .. } = transfer;
+        drop(file);
+
+        let verified = received == size
+            && sha256_file(&part_path).is_ok_and(|digest| digest.eq_ignore_ascii_case(sha256));
+        if !verified {
+            // Le fichier partiel est faux: une reprise repartirait d'un mauvais contenu
+            let _ = fs::remove_file(&part_path);
+            println!("❌ {} reçu de {} est corrompu (empreinte SHA-256 différente), fichier supprimé", name, sender);
+            return;
+        }
+
+        let path = unique_path(&self.download_dir, &name);
+        match fs::rename(&part_path, &path) {
+            Ok(()) => println!("✅ {} reçu de {} ({} octets, SHA-256 vérifié): {}", name, sender, size, path.display()),
+            Err(e) => println!("❌ {}: {}", path.display(), e),
+        }
+    }
+
+    fn abort(&self, transfer_id: Uuid, peer: &str, reason: &str) -> Message {
+        Message::file_reject(self.username.clone(), peer.to_string(), transfer_id, Some(reason.to_string()))
+    }
+}
+
+/// Remplit `buffer` autant que possible; moins d'octets seulement à la fin du fichier
+fn read_full(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+/// Chemin libre dans `dir` pour `name`: `rapport.pdf`, puis `rapport (1).pdf`...
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .expect("nom de fichier disponible")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::OpCode;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tp8-transfer-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Relaie les messages entre les deux côtés jusqu'à la fin du transfert, comme le serveur;
+    /// `cut_after` morceaux au plus sont remis avant une coupure
+    fn relay(alice: &mut Transfers, bob: &mut Transfers, first: Message, cut_after: Option<usize>) {
+        let mut pending = vec![first];
+        let mut chunks = 0;
+        while let Some(msg) = pending.pop() {
+            if msg.op_code == OpCode::FileChunk {
+                chunks += 1;
+                if cut_after.is_some_and(|max| chunks > max) {
+                    return;
+                }
+            }
+            let receiver = if msg.recipient.as_deref() == Some("Bob") { &mut *bob } else { &mut *alice };
+            let mut replies = receiver.handle(&msg);
+            replies.reverse();
+            pending.extend(replies);
+        }
+    }
+
+    #[test]
+    fn test_transfer_and_resume() {
+        let dir = temp_dir();
+        let source = dir.join("source.bin");
+        let content: Vec<u8> = (0..FILE_CHUNK_SIZE * 3 + 1000).map(|i| (i % 251) as u8).collect();
+        fs::write(&source, &content).unwrap();
+        let downloads = dir.join("downloads");
+
+        let mut alice = Transfers::new("Alice".to_string(), dir.clone());
+        let mut bob = Transfers::new("Bob".to_string(), downloads.clone());
+
+        // Premier envoi coupé après deux morceaux: le fichier partiel est conservé
+        let offer = alice.offer("Bob", &source).unwrap();
+        bob.handle(&offer);
+        let accept = bob.accept(None).unwrap();
+        relay(&mut alice, &mut bob, accept, Some(2));
+        alice.clear();
+        bob.clear();
+        assert_eq!(fs::metadata(downloads.join("source.bin.part")).unwrap().len(), 2 * FILE_CHUNK_SIZE as u64);
+
+        // Le renvoi reprend après les morceaux déjà reçus
+        let offer = alice.offer("Bob", &source).unwrap();
+        bob.handle(&offer);
+        let prefix = short_id(&bob.offers()[0].transfer_id);
+        let accept = bob.accept(Some(&prefix)).unwrap();
+        assert!(matches!(accept.payload, MessagePayload::FileAccept { offset, .. } if offset == 2 * FILE_CHUNK_SIZE as u64));
+        relay(&mut alice, &mut bob, accept, None);
+
+        assert_eq!(fs::read(downloads.join("source.bin")).unwrap(), content);
+        assert!(!downloads.join("source.bin.part").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_transfer_discarded() {
+        let dir = temp_dir();
+        let source = dir.join("notes.txt");
+        fs::write(&source, "contenu").unwrap();
+        let mut alice = Transfers::new("Alice".to_string(), dir.clone());
+        let mut bob = Transfers::new("Bob".to_string(), dir.join("downloads"));
+
+        let offer = alice.offer("Bob", &source).unwrap();
+        bob.handle(&offer);
+        let transfer_id = offer.payload.transfer_id().unwrap();
+        bob.accept(None).unwrap();
+        bob.handle(&Message::file_chunk("Alice".to_string(), "Bob".to_string(), transfer_id, 0, b"CONTENU".to_vec()));
+        bob.handle(&Message::file_complete("Alice".to_string(), "Bob".to_string(), transfer_id, sha256_file(&source).unwrap()));
+
+        assert!(!dir.join("downloads").join("notes.txt").exists());
+        assert!(!dir.join("downloads").join("notes.txt.part").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let dir = temp_dir();
+        assert_eq!(unique_path(&dir, "rapport.pdf"), dir.join("rapport.pdf"));
+        fs::write(dir.join("rapport.pdf"), "").unwrap();
+        assert_eq!(unique_path(&dir, "rapport.pdf"), dir.join("rapport (1).pdf"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -33,8 +33,8 @@ Write-Host "✅ Tests unitaires réussis" -ForegroundColor Green
 Write-Host "`n📋 Instructions pour le test manuel:" -ForegroundColor Cyan
 Write-Host "1. Ouvrez 3 terminaux" -ForegroundColor White
 Write-Host "2. Terminal 1: cargo run server" -ForegroundColor White
-Write-Host "3. Terminal 2: cargo run client Alice" -ForegroundColor White
-Write-Host "4. Terminal 3: cargo run client Bob" -ForegroundColor White
+Write-Host "3. Terminal 2: cargo run register Alice (puis cargo run client Alice)" -ForegroundColor White
+Write-Host "4. Terminal 3: cargo run register Bob (puis cargo run client Bob)" -ForegroundColor White
 Write-Host "5. Testez les interactions entre les clients" -ForegroundColor White
 
 Write-Host "`n🎯 Le protocole est prêt à être testé!" -ForegroundColor Green